@@ -1,10 +1,12 @@
 use std::{hint::black_box, mem::size_of};
 
 use alkahest::*;
+use bytemuck::{Pod, Zeroable};
 use rand::{distributions::Standard, prelude::Distribution, Rng};
 
-#[derive(Clone, Copy)]
-#[alkahest(Formula, Serialize, SerializeRef, Deserialize)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+#[alkahest(pod)]
 pub struct Vector3 {
     pub x: f32,
     pub y: f32,
@@ -21,8 +23,9 @@ impl Distribution<Vector3> for Standard {
     }
 }
 
-#[derive(Clone, Copy)]
-#[alkahest(Formula, Serialize, SerializeRef, Deserialize)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+#[alkahest(pod)]
 pub struct Triangle {
     pub v0: Vector3,
     pub v1: Vector3,
@@ -43,7 +46,8 @@ impl Distribution<Triangle> for Standard {
 
 #[alkahest(Formula)]
 pub struct MeshFormula {
-    pub triangles: [Triangle],
+    // Copied as single block of bytes, `[Triangle]` would copy triangles one by one.
+    pub triangles: PodSlice<Triangle>,
 }
 
 #[derive(Clone)]
@@ -54,7 +58,7 @@ pub struct Mesh {
 
 #[alkahest(Deserialize<'a, MeshFormula>)]
 pub struct LazyMesh<'a> {
-    pub triangles: Lazy<'a, [Triangle]>,
+    pub triangles: Lazy<'a, PodSlice<Triangle>>,
 }
 
 #[inline(always)]
@@ -74,8 +78,7 @@ fn main() {
 
     let mut mesh = black_box(mesh);
 
-    let mut buffer = Vec::new();
-    buffer.resize(TRIG_COUNT * size_of::<Triangle>() + 32, 0);
+    let mut buffer = vec![0; TRIG_COUNT * size_of::<Triangle>() + 32];
 
     for _ in 0..10_000 {
         let size = do_serialize(&mesh, &mut buffer);
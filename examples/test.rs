@@ -66,8 +66,8 @@ fn main() {
     let size = serialize::<[Foo], _>([value], &mut buffer).unwrap();
     assert_eq!(size.0, buffer.len());
 
-    let foo = deserialize::<[Foo], Vec<(&str, &str)>>(&buffer).unwrap();
-    assert_eq!(foo, vec![("qwe", "rty")]);
+    let values = deserialize::<[Foo], Vec<(&str, &str)>>(&buffer).unwrap();
+    assert_eq!(values, vec![("qwe", "rty")]);
 
     type MyFormula = Test<Vec<Vec<u32>>>;
 
@@ -2,6 +2,11 @@ proc_easy::easy_token!(Formula);
 proc_easy::easy_token!(Serialize);
 proc_easy::easy_token!(SerializeRef);
 proc_easy::easy_token!(Deserialize);
+proc_easy::easy_token!(lazy);
+proc_easy::easy_token!(transparent);
+proc_easy::easy_token!(pod);
+proc_easy::easy_token!(bits);
+proc_easy::easy_token!(width);
 
 proc_easy::easy_parse! {
     struct Params {
@@ -53,14 +58,46 @@ proc_easy::easy_parse! {
     }
 }
 
-struct ImplBlocks {
-    blocks: syn::punctuated::Punctuated<ImplBlock, syn::Token![,]>,
+proc_easy::easy_parse! {
+    struct WidthParam {
+        eq_token: syn::Token![=],
+        ty: Box<syn::Type>,
+    }
+}
+
+proc_easy::easy_parse! {
+    enum Flag {
+        Lazy(lazy),
+        Transparent(transparent),
+        Pod(pod),
+        Bits(bits),
+        Width(width, WidthParam),
+    }
+}
+
+enum Item {
+    Flag(Flag),
+    Block(Box<ImplBlock>),
+}
+
+impl syn::parse::Parse for Item {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        if <Flag as proc_easy::EasyPeek>::peek_stream(input) {
+            Ok(Item::Flag(input.parse()?))
+        } else {
+            Ok(Item::Block(input.parse()?))
+        }
+    }
 }
 
-impl syn::parse::Parse for ImplBlocks {
+struct Items {
+    items: syn::punctuated::Punctuated<Item, syn::Token![,]>,
+}
+
+impl syn::parse::Parse for Items {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        Ok(ImplBlocks {
-            blocks: syn::punctuated::Punctuated::parse_separated_nonempty(input)?,
+        Ok(Items {
+            items: syn::punctuated::Punctuated::parse_separated_nonempty(input)?,
         })
     }
 }
@@ -86,11 +123,15 @@ impl ImplBlock {
 
 pub struct FormulaArgs {
     pub generics: Option<syn::Generics>,
+    pub width: Option<syn::Type>,
 }
 
 impl FormulaArgs {
     pub fn empty() -> Self {
-        FormulaArgs { generics: None }
+        FormulaArgs {
+            generics: None,
+            width: None,
+        }
     }
 }
 
@@ -131,22 +172,87 @@ pub struct Args {
     pub serialize: Option<SerializeArgs>,
     pub serialize_ref: Option<SerializeArgs>,
     pub deserialize: Option<DeserializeArgs>,
+    pub lazy: bool,
+    pub transparent: Option<proc_macro2::Span>,
+    pub pod: Option<proc_macro2::Span>,
+    pub bits: Option<proc_macro2::Span>,
+}
+
+fn duplicate(span: proc_macro2::Span, name: &str) -> syn::Error {
+    syn::Error::new(span, format!("`{name}` is specified more than once"))
 }
 
 impl Args {
+    #[allow(clippy::too_many_lines)]
     pub fn parse_attributes(attrs: proc_macro2::TokenStream) -> syn::Result<Self> {
-        let blocks: ImplBlocks = syn::parse2(attrs)?;
+        let items: Items = syn::parse2(attrs)?;
 
         let mut formula: Option<FormulaArgs> = None;
         let mut serialize: Option<SerializeArgs> = None;
         let mut serialize_ref: Option<SerializeArgs> = None;
         let mut deserialize: Option<DeserializeArgs> = None;
+        let mut lazy = false;
+        let mut transparent = None;
+        let mut pod = None;
+        let mut bits = None;
+        let mut width: Option<(proc_macro2::Span, syn::Type)> = None;
+
+        for item in items.items {
+            let block = match item {
+                Item::Flag(Flag::Lazy(token)) => {
+                    if lazy {
+                        return Err(duplicate(token.span, "lazy"));
+                    }
+                    lazy = true;
+                    continue;
+                }
+                Item::Flag(Flag::Transparent(token)) => {
+                    if transparent.is_some() {
+                        return Err(duplicate(token.span, "transparent"));
+                    }
+                    transparent = Some(token.span);
+                    continue;
+                }
+                Item::Flag(Flag::Pod(token)) => {
+                    if pod.is_some() {
+                        return Err(duplicate(token.span, "pod"));
+                    }
+                    pod = Some(token.span);
+                    continue;
+                }
+                Item::Flag(Flag::Bits(token)) => {
+                    if bits.is_some() {
+                        return Err(duplicate(token.span, "bits"));
+                    }
+                    bits = Some(token.span);
+                    continue;
+                }
+                Item::Flag(Flag::Width(token, param)) => {
+                    if width.is_some() {
+                        return Err(duplicate(token.span, "width"));
+                    }
+                    width = Some((token.span, *param.ty));
+                    continue;
+                }
+                Item::Block(block) => *block,
+            };
 
-        for block in blocks.blocks {
             let (impl_trait, generics) = block.split();
             match impl_trait {
-                ImplTrait::Formula(_) => formula = Some(FormulaArgs { generics }),
-                ImplTrait::Serialize(_, params) => {
+                ImplTrait::Formula(token) => {
+                    if formula.is_some() {
+                        return Err(duplicate(token.span, "Formula"));
+                    }
+                    formula = Some(FormulaArgs {
+                        generics,
+                        width: None,
+                    });
+                }
+                ImplTrait::Serialize(token, params) => {
+                    if serialize.is_some() {
+                        return Err(duplicate(token.span, "Serialize"));
+                    }
+
                     let (formula, variant) = match params {
                         proc_easy::EasyMaybe::Just(params) => (
                             Some(path_make_expr_style(params.formula)),
@@ -164,7 +270,11 @@ impl Args {
                         variant,
                     });
                 }
-                ImplTrait::SerializeRef(_, params) => {
+                ImplTrait::SerializeRef(token, params) => {
+                    if serialize_ref.is_some() {
+                        return Err(duplicate(token.span, "SerializeRef"));
+                    }
+
                     let (formula, variant) = match params {
                         proc_easy::EasyMaybe::Just(params) => (
                             Some(path_make_expr_style(params.formula)),
@@ -182,7 +292,11 @@ impl Args {
                         variant,
                     });
                 }
-                ImplTrait::Deserialize(_, params) => {
+                ImplTrait::Deserialize(token, params) => {
+                    if deserialize.is_some() {
+                        return Err(duplicate(token.span, "Deserialize"));
+                    }
+
                     let (formula, lifetime) = match params {
                         proc_easy::EasyMaybe::Just(params) => (
                             Some(path_make_expr_style(params.formula)),
@@ -200,11 +314,27 @@ impl Args {
             }
         }
 
+        if let Some((span, ty)) = width {
+            match &mut formula {
+                Some(formula) => formula.width = Some(ty),
+                None => {
+                    return Err(syn::Error::new(
+                        span,
+                        "`width` sets size prefixes of the formula and requires `Formula`",
+                    ))
+                }
+            }
+        }
+
         Ok(Args {
             formula,
             serialize,
             serialize_ref,
             deserialize,
+            lazy,
+            transparent,
+            pod,
+            bits,
         })
     }
 }
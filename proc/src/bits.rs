@@ -0,0 +1,373 @@
+use proc_macro2::TokenStream;
+
+use crate::is_generic_ty;
+
+/// Widths set with `#[alkahest(bits = N)]` field attributes.
+/// One entry per variant, single entry for structs.
+pub type FieldWidths = Vec<Vec<Option<syn::LitInt>>>;
+
+fn data_fields_mut(data: &mut syn::Data) -> Vec<&mut syn::Fields> {
+    match data {
+        syn::Data::Struct(data) => vec![&mut data.fields],
+        syn::Data::Enum(data) => data.variants.iter_mut().map(|v| &mut v.fields).collect(),
+        syn::Data::Union(_) => Vec::new(),
+    }
+}
+
+fn parse_width(attr: &syn::Attribute) -> syn::Result<syn::LitInt> {
+    let mut width = None;
+    attr.parse_nested_meta(|meta| {
+        if !meta.path.is_ident("bits") {
+            return Err(meta.error("expected `bits = N`"));
+        }
+        if width.is_some() {
+            return Err(meta.error("`bits` is specified more than once"));
+        }
+        let lit: syn::LitInt = meta.value()?.parse()?;
+        lit.base10_parse::<u8>()?;
+        width = Some(lit);
+        Ok(())
+    })?;
+    width.ok_or_else(|| syn::Error::new_spanned(attr, "expected `bits = N`"))
+}
+
+/// Removes `#[alkahest(...)]` attributes from fields,
+/// since the item is emitted as is and fields cannot have attribute macros.
+///
+/// Returns parsed widths and whether any attribute was removed.
+pub fn take_field_widths(input: &mut syn::DeriveInput) -> (syn::Result<FieldWidths>, bool) {
+    let mut stripped = false;
+    let mut error: Option<syn::Error> = None;
+
+    let widths = data_fields_mut(&mut input.data)
+        .into_iter()
+        .map(|fields| {
+            fields
+                .iter_mut()
+                .map(|field| {
+                    let mut width = None;
+                    field.attrs.retain(|attr| {
+                        if !attr.path().is_ident("alkahest") {
+                            return true;
+                        }
+                        stripped = true;
+                        match parse_width(attr) {
+                            Ok(lit) if width.is_none() => width = Some(lit),
+                            Ok(lit) => {
+                                let err = syn::Error::new_spanned(
+                                    lit,
+                                    "`bits` is specified more than once",
+                                );
+                                error.get_or_insert(err);
+                            }
+                            Err(err) => match &mut error {
+                                None => error = Some(err),
+                                Some(error) => error.combine(err),
+                            },
+                        }
+                        false
+                    });
+                    width
+                })
+                .collect()
+        })
+        .collect();
+
+    match error {
+        None => (Ok(widths), stripped),
+        Some(err) => (Err(err), stripped),
+    }
+}
+
+/// Returns first field width attribute, if any.
+pub fn first_width(widths: &FieldWidths) -> Option<&syn::LitInt> {
+    widths.iter().flatten().flatten().next()
+}
+
+/// Number of bits required to store indices of `count` variants.
+fn tag_bits(count: usize) -> usize {
+    match count {
+        0 | 1 => 0,
+        _ => (usize::BITS - (count - 1).leading_zeros()) as usize,
+    }
+}
+
+fn field_width(field: &syn::Field, width: Option<&syn::LitInt>) -> TokenStream {
+    let ty = &field.ty;
+    match width {
+        None => quote::quote! { <#ty as ::alkahest::private::BitField>::BITS },
+        Some(width) => {
+            let error = format!("Field does not fit {} bits", width.base10_digits());
+            quote::quote_spanned! { width.span() =>
+                {
+                    const { ::alkahest::private::assert!(#width <= <#ty as ::alkahest::private::BitField>::BITS, #error) };
+                    #width
+                }
+            }
+        }
+    }
+}
+
+/// Names used to bind fields in patterns.
+fn field_bindings(fields: &syn::Fields) -> Vec<syn::Ident> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(idx, field)| match &field.ident {
+            Some(ident) => ident.clone(),
+            None => quote::format_ident!("_{}", idx),
+        })
+        .collect()
+}
+
+/// Pattern or constructor with fields bound to `bindings`.
+fn fields_pattern(
+    path: &TokenStream,
+    fields: &syn::Fields,
+    bindings: &[syn::Ident],
+    values: &[TokenStream],
+) -> TokenStream {
+    match fields {
+        syn::Fields::Named(_) => {
+            quote::quote! { #path { #(#bindings: #values,)* } }
+        }
+        syn::Fields::Unnamed(_) => quote::quote! { #path ( #(#values,)* ) },
+        syn::Fields::Unit => quote::quote! { #path },
+    }
+}
+
+/// Code that packs fields bound by reference into `__bits` starting at `__shift`.
+fn pack_fields(widths: &[TokenStream], bindings: &[syn::Ident]) -> TokenStream {
+    quote::quote! {
+        #(
+            __bits |= ::alkahest::private::pack_bits(::alkahest::private::BitField::to_bits(#bindings), __shift, #widths);
+            __shift += #widths;
+        )*
+        let _ = __shift;
+    }
+}
+
+/// Expressions that unpack fields from `__bits` starting at `__shift`.
+fn unpack_fields(widths: &[TokenStream]) -> Vec<TokenStream> {
+    widths
+        .iter()
+        .map(|width| {
+            quote::quote! {
+                {
+                    let __value = ::alkahest::private::BitField::from_bits(::alkahest::private::unpack_bits(__bits, __shift, #width))?;
+                    __shift += #width;
+                    __value
+                }
+            }
+        })
+        .collect()
+}
+
+/// Generates impls for struct or enum which fields are packed into bits.
+pub fn derive(input: &syn::DeriveInput, widths: &FieldWidths) -> syn::Result<TokenStream> {
+    let ident = &input.ident;
+
+    let mut generics = input.generics.clone();
+    let all_fields: Vec<&syn::Field> = match &input.data {
+        syn::Data::Struct(data) => data.fields.iter().collect(),
+        syn::Data::Enum(data) => data.variants.iter().flat_map(|v| v.fields.iter()).collect(),
+        syn::Data::Union(data) => {
+            return Err(syn::Error::new_spanned(
+                data.union_token,
+                "`bits` formula must be a struct or an enum",
+            ))
+        }
+    };
+    for field in all_fields {
+        let ty = &field.ty;
+        if is_generic_ty(ty, &input.generics.params.iter()) {
+            generics
+                .make_where_clause()
+                .predicates
+                .push(syn::parse_quote! { #ty: ::alkahest::private::BitField });
+        }
+    }
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+
+    let mut de_generics = generics.clone();
+    de_generics.params.insert(0, syn::parse_quote! { '__de });
+    let (de_impl_generics, _, _) = de_generics.split_for_impl();
+
+    let (total_bits, to_bits, from_bits) = match &input.data {
+        syn::Data::Struct(data) => {
+            let widths: Vec<_> = data
+                .fields
+                .iter()
+                .zip(&widths[0])
+                .map(|(field, width)| field_width(field, width.as_ref()))
+                .collect();
+            let bindings = field_bindings(&data.fields);
+            let path = quote::quote! { #ident };
+            let refs: Vec<_> = bindings.iter().map(|b| quote::quote! { ref #b }).collect();
+            let pattern = fields_pattern(&path, &data.fields, &bindings, &refs);
+            let pack = pack_fields(&widths, &bindings);
+            let values = unpack_fields(&widths);
+            let construct = fields_pattern(&path, &data.fields, &bindings, &values);
+
+            (
+                quote::quote! { 0 #(+ #widths)* },
+                quote::quote! {
+                    let #pattern = *self;
+                    let mut __bits = 0;
+                    let mut __shift = 0;
+                    #pack
+                    __bits
+                },
+                quote::quote! {
+                    let mut __shift = 0;
+                    let __value = #construct;
+                    let _ = __shift;
+                    ::alkahest::private::Result::Ok(__value)
+                },
+            )
+        }
+        syn::Data::Enum(data) => {
+            let tag_bits = tag_bits(data.variants.len());
+
+            let mut variant_bits = Vec::new();
+            let mut pack_arms = Vec::new();
+            let mut unpack_arms = Vec::new();
+
+            for (idx, (variant, widths)) in data.variants.iter().zip(widths).enumerate() {
+                let widths: Vec<_> = variant
+                    .fields
+                    .iter()
+                    .zip(widths)
+                    .map(|(field, width)| field_width(field, width.as_ref()))
+                    .collect();
+                let bindings = field_bindings(&variant.fields);
+                let v = &variant.ident;
+                let path = quote::quote! { #ident::#v };
+                let refs: Vec<_> = bindings.iter().map(|b| quote::quote! { ref #b }).collect();
+                let pattern = fields_pattern(&path, &variant.fields, &bindings, &refs);
+                let pack = pack_fields(&widths, &bindings);
+                let values = unpack_fields(&widths);
+                let construct = fields_pattern(&path, &variant.fields, &bindings, &values);
+                let idx_u128 = idx as u128;
+
+                pack_arms.push(quote::quote! {
+                    #pattern => {
+                        let mut __bits = #idx_u128;
+                        let mut __shift = #tag_bits;
+                        #pack
+                        __bits
+                    }
+                });
+
+                unpack_arms.push(quote::quote! {
+                    #idx_u128 => {
+                        let mut __shift = #tag_bits;
+                        let __value = #construct;
+                        ::alkahest::private::check_unused_bits(__bits, __shift)?;
+                        ::alkahest::private::Result::Ok(__value)
+                    }
+                });
+
+                variant_bits.push(quote::quote! { 0 #(+ #widths)* });
+            }
+
+            (
+                quote::quote! {
+                    #tag_bits + {
+                        let mut __max = 0;
+                        #(
+                            let __bits = #variant_bits;
+                            if __bits > __max {
+                                __max = __bits;
+                            }
+                        )*
+                        __max
+                    }
+                },
+                quote::quote! {
+                    match *self {
+                        #(#pack_arms)*
+                    }
+                },
+                quote::quote! {
+                    match ::alkahest::private::unpack_bits(__bits, 0, #tag_bits) {
+                        #(#unpack_arms)*
+                        __tag => ::alkahest::private::Result::Err(
+                            ::alkahest::private::DeserializeError::WrongVariant(__tag as ::alkahest::private::u32),
+                        ),
+                    }
+                },
+            )
+        }
+        syn::Data::Union(_) => unreachable!(),
+    };
+
+    Ok(quote::quote! {
+        impl #impl_generics ::alkahest::private::BitField for #ident #type_generics #where_clause {
+            const BITS: ::alkahest::private::usize = #total_bits;
+
+            #[inline(always)]
+            #[allow(unused_mut)]
+            fn to_bits(&self) -> ::alkahest::private::u128 {
+                #to_bits
+            }
+
+            #[inline(always)]
+            #[allow(unused_mut, clippy::cast_possible_truncation)]
+            fn from_bits(__bits: ::alkahest::private::u128) -> ::alkahest::private::Result<Self, ::alkahest::private::DeserializeError> {
+                #from_bits
+            }
+        }
+
+        impl #impl_generics ::alkahest::private::Formula for #ident #type_generics #where_clause {
+            const MAX_STACK_SIZE: ::alkahest::private::Option<::alkahest::private::usize> = ::alkahest::private::Option::Some(::alkahest::private::bytes_for(<Self as ::alkahest::private::BitField>::BITS));
+            const EXACT_SIZE: ::alkahest::private::bool = true;
+            const HEAPLESS: ::alkahest::private::bool = true;
+        }
+
+        impl #impl_generics ::alkahest::private::BareFormula for #ident #type_generics #where_clause {}
+
+        impl #impl_generics ::alkahest::private::Serialize<Self> for #ident #type_generics #where_clause {
+            #[inline(always)]
+            fn serialize<__alkahest_Buffer>(self, sizes: &mut ::alkahest::private::Sizes, buffer: __alkahest_Buffer) -> ::alkahest::private::Result<(), __alkahest_Buffer::Error>
+            where
+                __alkahest_Buffer: ::alkahest::private::Buffer,
+            {
+                ::alkahest::private::write_bit_field(&self, sizes, buffer)
+            }
+
+            #[inline(always)]
+            fn size_hint(&self) -> ::alkahest::private::Option<::alkahest::private::Sizes> {
+                ::alkahest::private::Option::Some(::alkahest::private::Sizes::with_stack(::alkahest::private::bytes_for(<Self as ::alkahest::private::BitField>::BITS)))
+            }
+        }
+
+        impl #impl_generics ::alkahest::private::SerializeRef<Self> for #ident #type_generics #where_clause {
+            #[inline(always)]
+            fn serialize<__alkahest_Buffer>(&self, sizes: &mut ::alkahest::private::Sizes, buffer: __alkahest_Buffer) -> ::alkahest::private::Result<(), __alkahest_Buffer::Error>
+            where
+                __alkahest_Buffer: ::alkahest::private::Buffer,
+            {
+                ::alkahest::private::write_bit_field(self, sizes, buffer)
+            }
+
+            #[inline(always)]
+            fn size_hint(&self) -> ::alkahest::private::Option<::alkahest::private::Sizes> {
+                ::alkahest::private::Option::Some(::alkahest::private::Sizes::with_stack(::alkahest::private::bytes_for(<Self as ::alkahest::private::BitField>::BITS)))
+            }
+        }
+
+        impl #de_impl_generics ::alkahest::private::Deserialize<'__de, Self> for #ident #type_generics #where_clause {
+            #[inline(always)]
+            fn deserialize(de: ::alkahest::private::Deserializer<'__de>) -> ::alkahest::private::Result<Self, ::alkahest::private::DeserializeError> {
+                ::alkahest::private::read_bit_field(de)
+            }
+
+            #[inline(always)]
+            fn deserialize_in_place(&mut self, de: ::alkahest::private::Deserializer<'__de>) -> ::alkahest::private::Result<(), ::alkahest::private::DeserializeError> {
+                *self = ::alkahest::private::read_bit_field(de)?;
+                ::alkahest::private::Result::Ok(())
+            }
+        }
+    })
+}
@@ -3,8 +3,8 @@ use std::collections::HashSet;
 use proc_macro2::TokenStream;
 
 use crate::{
-    attrs::DeserializeArgs, enum_field_order_checks, filter_type_param, is_generic_ty,
-    struct_field_order_checks,
+    attrs::DeserializeArgs, enum_field_checks, field_patterns, is_generic_ty, is_param_lifetime,
+    place_field_checks, struct_field_checks, variant_index,
 };
 
 fn default_de_lifetime() -> syn::Lifetime {
@@ -72,9 +72,8 @@ impl Config {
                     syn::Data::Struct(data) => {
                         let mut all_generic_field_types: HashSet<_> =
                             data.fields.iter().map(|f| &f.ty).collect();
-                        all_generic_field_types.retain(|ty| {
-                            is_generic_ty(ty, &filter_type_param(generics.params.iter()))
-                        });
+                        all_generic_field_types
+                            .retain(|ty| is_generic_ty(ty, &generics.params.iter()));
 
                         if !all_generic_field_types.is_empty() {
                             let predicates = all_generic_field_types.iter().map(|&ty| -> syn::WherePredicate {
@@ -92,9 +91,8 @@ impl Config {
 
                         let mut all_generic_field_types: HashSet<_> =
                             all_fields.map(|f| &f.ty).collect();
-                        all_generic_field_types.retain(|ty| {
-                            is_generic_ty(ty, &filter_type_param(generics.params.iter()))
-                        });
+                        all_generic_field_types
+                            .retain(|ty| is_generic_ty(ty, &generics.params.iter()));
 
                         if !all_generic_field_types.is_empty() {
                             let predicates = all_generic_field_types.iter().map(|&ty| -> syn::WherePredicate {
@@ -141,7 +139,7 @@ impl Config {
                 Config {
                     formula,
                     generics: formula_generics,
-                    check_fields: false,
+                    check_fields: true,
                     de,
                 }
             }
@@ -163,6 +161,25 @@ impl Config {
 pub fn derive(args: DeserializeArgs, input: &syn::DeriveInput) -> syn::Result<TokenStream> {
     let ident = &input.ident;
 
+    if let Some(lifetime) = &args.lifetime {
+        let declared = input
+            .generics
+            .params
+            .iter()
+            .chain(args.generics.iter().flat_map(|g| g.params.iter()));
+
+        if lifetime.ident != "_"
+            && lifetime.ident != "static"
+            && !is_param_lifetime(lifetime, &declared)
+        {
+            let message = format!(
+                "Lifetime `{lifetime}` is not declared, \
+                 add it to `{ident}` generics or to `for<{lifetime}>` parameters"
+            );
+            return Err(syn::Error::new_spanned(lifetime, message));
+        }
+    }
+
     let cfg = Config::for_type(args, &input.data, &input.generics);
 
     match &input.data {
@@ -172,7 +189,7 @@ pub fn derive(args: DeserializeArgs, input: &syn::DeriveInput) -> syn::Result<To
         )),
         syn::Data::Struct(data) => {
             let field_checks = if cfg.check_fields {
-                struct_field_order_checks(&data, None, &input.ident, &cfg.formula)
+                struct_field_checks(data, None, &input.ident, &cfg.formula)
             } else {
                 TokenStream::new()
             };
@@ -185,9 +202,7 @@ pub fn derive(args: DeserializeArgs, input: &syn::DeriveInput) -> syn::Result<To
 
             deserialize_generics.lt_token = deserialize_generics.lt_token.or(cfg.generics.lt_token);
             deserialize_generics.gt_token = deserialize_generics.gt_token.or(cfg.generics.gt_token);
-            deserialize_generics
-                .params
-                .extend(cfg.generics.params.into_iter());
+            deserialize_generics.params.extend(cfg.generics.params);
 
             if let Some(where_clause) = cfg.generics.where_clause {
                 deserialize_generics
@@ -233,31 +248,6 @@ pub fn derive(args: DeserializeArgs, input: &syn::DeriveInput) -> syn::Result<To
                 syn::Fields::Unit => quote::quote! {},
             };
 
-            let bind_ref_names = match &data.fields {
-                syn::Fields::Named(fields) => {
-                    let names = fields
-                        .named
-                        .iter()
-                        .map(|field| field.ident.as_ref().unwrap().clone());
-
-                    quote::quote! {
-                        { #(ref #names),* }
-                    }
-                }
-                syn::Fields::Unnamed(fields) => {
-                    let names = fields
-                        .unnamed
-                        .iter()
-                        .enumerate()
-                        .map(|(idx, _)| quote::format_ident!("_{}", idx));
-
-                    quote::quote! {
-                        ( #(ref #names),* )
-                    }
-                }
-                syn::Fields::Unit => quote::quote! {},
-            };
-
             let bind_ref_mut_names = match &data.fields {
                 syn::Fields::Named(fields) => {
                     let names = fields
@@ -285,10 +275,17 @@ pub fn derive(args: DeserializeArgs, input: &syn::DeriveInput) -> syn::Result<To
 
             let field_count = data.fields.len();
 
+            let field_patterns = field_patterns(&data.fields);
+
             let (_impl_generics, type_generics, _where_clause) = input.generics.split_for_impl();
             let (impl_deserialize_generics, _type_deserialize_generics, where_serialize_clause) =
                 deserialize_generics.split_for_impl();
+            let (field_checks_item, field_checks) =
+                place_field_checks(field_checks, formula_path, &deserialize_generics);
+
             Ok(quote::quote! {
+                #field_checks_item
+
                 impl #impl_deserialize_generics ::alkahest::private::Deserialize<#de, #formula_path> for #ident #type_generics #where_serialize_clause {
                     #[inline(always)]
                     fn deserialize(mut de: ::alkahest::private::Deserializer<#de>) -> ::alkahest::private::Result<Self, ::alkahest::private::DeserializeError> {
@@ -296,7 +293,7 @@ pub fn derive(args: DeserializeArgs, input: &syn::DeriveInput) -> syn::Result<To
 
                         #(
                             let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
-                                #formula_path #bind_ref_names => #bound_names,
+                                #formula_path #field_patterns => #bound_names,
                                 _ => unreachable!(),
                             });
                             let #bound_names = with_formula.read_field(&mut de, #field_count == 1 + #field_ids)?;
@@ -316,7 +313,7 @@ pub fn derive(args: DeserializeArgs, input: &syn::DeriveInput) -> syn::Result<To
 
                         #(
                             let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
-                                #formula_path #bind_ref_names => #bound_names,
+                                #formula_path #field_patterns => #bound_names,
                                 _ => unreachable!(),
                             });
                             with_formula.read_in_place(#bound_names, &mut de, #field_count == 1 + #field_ids)?;
@@ -330,7 +327,7 @@ pub fn derive(args: DeserializeArgs, input: &syn::DeriveInput) -> syn::Result<To
         }
         syn::Data::Enum(data) => {
             let field_checks = if cfg.check_fields {
-                enum_field_order_checks(&data, &input.ident, &cfg.formula)
+                enum_field_checks(data, &input.ident, &cfg.formula)
             } else {
                 TokenStream::new()
             };
@@ -343,9 +340,7 @@ pub fn derive(args: DeserializeArgs, input: &syn::DeriveInput) -> syn::Result<To
 
             deserialize_generics.lt_token = deserialize_generics.lt_token.or(cfg.generics.lt_token);
             deserialize_generics.gt_token = deserialize_generics.gt_token.or(cfg.generics.gt_token);
-            deserialize_generics
-                .params
-                .extend(cfg.generics.params.into_iter());
+            deserialize_generics.params.extend(cfg.generics.params);
 
             if let Some(where_clause) = cfg.generics.where_clause {
                 deserialize_generics
@@ -408,7 +403,7 @@ pub fn derive(args: DeserializeArgs, input: &syn::DeriveInput) -> syn::Result<To
                 })
                 .collect::<Vec<_>>();
 
-            let bind_ref_names = data
+            let bind_ref_mut_names = data
                 .variants
                 .iter()
                 .map(|v| match v.fields {
@@ -419,7 +414,7 @@ pub fn derive(args: DeserializeArgs, input: &syn::DeriveInput) -> syn::Result<To
                             .map(|field| field.ident.as_ref().unwrap().clone());
 
                         quote::quote! {
-                            { #(ref #names),* }
+                            { #(ref mut #names),* }
                         }
                     }
                     syn::Fields::Unnamed(_) => {
@@ -430,54 +425,34 @@ pub fn derive(args: DeserializeArgs, input: &syn::DeriveInput) -> syn::Result<To
                             .map(|(idx, _)| quote::format_ident!("_{}", idx));
 
                         quote::quote! {
-                            ( #(ref #names),* )
+                            ( #(ref mut #names),* )
                         }
                     }
                     syn::Fields::Unit => quote::quote! {},
                 })
                 .collect::<Vec<_>>();
 
-            let bind_ref_mut_names = data
+            let field_patterns = data
                 .variants
                 .iter()
-                .map(|v| match v.fields {
-                    syn::Fields::Named(_) => {
-                        let names = v
-                            .fields
-                            .iter()
-                            .map(|field| field.ident.as_ref().unwrap().clone());
-
-                        quote::quote! {
-                            { #(ref mut #names),* }
-                        }
-                    }
-                    syn::Fields::Unnamed(_) => {
-                        let names = v
-                            .fields
-                            .iter()
-                            .enumerate()
-                            .map(|(idx, _)| quote::format_ident!("_{}", idx));
-
-                        quote::quote! {
-                            ( #(ref mut #names),* )
-                        }
-                    }
-                    syn::Fields::Unit => quote::quote! {},
-                })
+                .map(|v| field_patterns(&v.fields))
                 .collect::<Vec<_>>();
 
-            let variant_name_ids: Vec<syn::Ident> = data
+            let variant_ids = data
                 .variants
                 .iter()
-                .map(|variant| {
-                    quote::format_ident!("__ALKAHEST_FORMULA_VARIANT_{}_IDX", variant.ident,)
-                })
-                .collect();
+                .map(|v| variant_index(formula_path, &v.ident))
+                .collect::<Vec<_>>();
 
             let (_impl_generics, type_generics, _where_clause) = input.generics.split_for_impl();
             let (impl_deserialize_generics, _type_deserialize_generics, where_serialize_clause) =
                 deserialize_generics.split_for_impl();
+            let (field_checks_item, field_checks) =
+                place_field_checks(field_checks, formula_path, &deserialize_generics);
+
             Ok(quote::quote! {
+                #field_checks_item
+
                 impl #impl_deserialize_generics ::alkahest::private::Deserialize<#de, #formula_path> for #ident #type_generics #where_serialize_clause {
                     #[inline(always)]
                     fn deserialize(mut de: ::alkahest::private::Deserializer<#de>) -> ::alkahest::private::Result<Self, ::alkahest::private::DeserializeError> {
@@ -486,11 +461,11 @@ pub fn derive(args: DeserializeArgs, input: &syn::DeriveInput) -> syn::Result<To
                         let variant_idx = de.read_value::<::alkahest::private::u32, _>(false)?;
                         match variant_idx {
                             #(
-                                #formula_path::#variant_name_ids => {
+                                __variant_idx if __variant_idx == #variant_ids => {
                                     #(
                                         let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
                                             #[allow(unused_variables)]
-                                            #formula_path::#variant_names #bind_ref_names => #bound_names,
+                                            #formula_path::#variant_names #field_patterns => #bound_names,
                                             _ => unreachable!(),
                                         });
                                         let #bound_names = with_formula.read_field(&mut de, #field_counts == 1 + #field_ids)?;
@@ -511,11 +486,11 @@ pub fn derive(args: DeserializeArgs, input: &syn::DeriveInput) -> syn::Result<To
                         let variant_idx = de.read_value::<::alkahest::private::u32, _>(false)?;
                         match (variant_idx, self) {
                             #(
-                                (#formula_path::#variant_name_ids, #ident::#variant_names #bind_ref_mut_names) => {
+                                (__variant_idx, #ident::#variant_names #bind_ref_mut_names) if __variant_idx == #variant_ids => {
                                     #(
                                         let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
                                             #[allow(unused_variables)]
-                                            #formula_path::#variant_names #bind_ref_names => #bound_names,
+                                            #formula_path::#variant_names #field_patterns => #bound_names,
                                             _ => unreachable!(),
                                         });
                                         with_formula.read_in_place(#bound_names, &mut de, #field_counts == 1 + #field_ids)?;
@@ -526,11 +501,11 @@ pub fn derive(args: DeserializeArgs, input: &syn::DeriveInput) -> syn::Result<To
                                 }
                             )*
                             #(
-                                (#formula_path::#variant_name_ids, me) => {
+                                (__variant_idx, me) if __variant_idx == #variant_ids => {
                                     #(
                                         let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
                                             #[allow(unused_variables)]
-                                            #formula_path::#variant_names #bind_ref_names => #bound_names,
+                                            #formula_path::#variant_names #field_patterns => #bound_names,
                                             _ => unreachable!(),
                                         });
                                         let #bound_names = with_formula.read_field(&mut de, #field_counts == 1 + #field_ids)?;
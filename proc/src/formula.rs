@@ -3,10 +3,11 @@ use std::collections::HashSet;
 use proc_macro2::TokenStream;
 use syn::spanned::Spanned;
 
-use crate::{attrs::FormulaArgs, filter_type_param, is_generic_ty};
+use crate::{attrs::FormulaArgs, is_generic_ty};
 
 struct Config {
     formula_generics: syn::Generics,
+    width: syn::Type,
 }
 
 impl Config {
@@ -28,8 +29,7 @@ impl Config {
                 let mut all_generic_field_types: HashSet<_> =
                     all_field_types.iter().copied().collect();
 
-                all_generic_field_types
-                    .retain(|ty| is_generic_ty(ty, &filter_type_param(generics.params.iter())));
+                all_generic_field_types.retain(|ty| is_generic_ty(ty, &generics.params.iter()));
 
                 let mut formula_generics = generics.clone();
                 if !all_generic_field_types.is_empty() {
@@ -57,15 +57,36 @@ impl Config {
             }
         };
 
-        Config { formula_generics }
+        let width = args
+            .width
+            .unwrap_or_else(|| syn::parse_quote!(::alkahest::FixedUsize));
+
+        Config {
+            formula_generics,
+            width,
+        }
     }
 }
 
+/// Names of the fields in declaration order.
+/// Tuple fields are named by their index.
+fn field_names(fields: &syn::Fields) -> Vec<String> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(idx, field)| match &field.ident {
+            Some(ident) => ident.to_string(),
+            None => idx.to_string(),
+        })
+        .collect()
+}
+
 #[allow(clippy::too_many_lines)]
 pub fn derive(args: FormulaArgs, input: &syn::DeriveInput) -> syn::Result<TokenStream> {
     let ident = &input.ident;
 
     let config = Config::from_args(args, &input.generics, &input.data);
+    let width = &config.width;
 
     match &input.data {
         syn::Data::Union(data) => Err(syn::Error::new_spanned(
@@ -76,21 +97,7 @@ pub fn derive(args: FormulaArgs, input: &syn::DeriveInput) -> syn::Result<TokenS
             let all_field_types: Vec<_> = data.fields.iter().map(|field| &field.ty).collect();
             let last_field_type = all_field_types.last().copied().into_iter();
 
-            let field_names_order = match &data.fields {
-                syn::Fields::Named(fields) => fields
-                    .named
-                    .iter()
-                    .map(|field| {
-                        quote::format_ident!(
-                            "__ALKAHEST_FORMULA_FIELD_{}_IDX",
-                            field.ident.as_ref().unwrap(),
-                        )
-                    })
-                    .collect(),
-                _ => Vec::new(),
-            };
-
-            let field_ids: Vec<_> = (0..data.fields.len()).collect();
+            let field_names = field_names(&data.fields);
 
             let (formula_impl_generics, formula_type_generics, formula_where_clause) =
                 config.formula_generics.split_for_impl();
@@ -115,13 +122,8 @@ pub fn derive(args: FormulaArgs, input: &syn::DeriveInput) -> syn::Result<TokenS
 
             let tokens = quote::quote! {
                 impl #formula_impl_generics #ident #formula_type_generics #formula_where_clause {
-                    #(
-                        #[doc(hidden)]
-                        #[allow(non_upper_case_globals)]
-                        pub const #field_names_order: ::alkahest::private::usize = #field_ids;
-                    )*
-
-                    // #(#with_fields)*
+                    #[doc(hidden)]
+                    pub const __ALKAHEST_FORMULA_FIELDS: &'static [&'static ::alkahest::private::str] = &[#(#field_names),*];
 
                     #[doc(hidden)]
                     #[allow(dead_code, unused_variables)]
@@ -147,6 +149,10 @@ pub fn derive(args: FormulaArgs, input: &syn::DeriveInput) -> syn::Result<TokenS
                 }
 
                 impl #formula_impl_generics ::alkahest::private::BareFormula for #ident #formula_type_generics #formula_where_clause {}
+
+                impl #formula_impl_generics ::alkahest::private::FieldWidth for #ident #formula_type_generics #formula_where_clause {
+                    type Width = #width;
+                }
             };
 
             Ok(tokens)
@@ -163,40 +169,15 @@ pub fn derive(args: FormulaArgs, input: &syn::DeriveInput) -> syn::Result<TokenS
                 .map(|variants| variants.last().copied().into_iter().collect())
                 .collect();
 
-            let field_names_order: Vec<Vec<syn::Ident>> = data
-                .variants
-                .iter()
-                .map(|variant| match &variant.fields {
-                    syn::Fields::Named(fields) => fields
-                        .named
-                        .iter()
-                        .map(|field| {
-                            quote::format_ident!(
-                                "__ALKAHEST_FORMULA_VARIANT_{}_FIELD_{}_IDX",
-                                variant.ident,
-                                field.ident.as_ref().unwrap(),
-                            )
-                        })
-                        .collect(),
-                    _ => Vec::new(),
-                })
-                .collect();
-
-            let field_ids: Vec<Vec<usize>> = data
-                .variants
-                .iter()
-                .map(|v| (0..v.fields.len()).collect())
-                .collect();
+            let variant_names: Vec<String> =
+                data.variants.iter().map(|v| v.ident.to_string()).collect();
 
-            let variant_name_ids: Vec<syn::Ident> = data
+            let variant_field_names: Vec<Vec<String>> = data
                 .variants
                 .iter()
-                .map(|v| quote::format_ident!("__ALKAHEST_FORMULA_VARIANT_{}_IDX", v.ident))
+                .map(|v| field_names(&v.fields))
                 .collect();
 
-            #[allow(clippy::cast_possible_truncation)]
-            let variant_ids: Vec<_> = (0..data.variants.len() as u32).collect();
-
             let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
 
             let (formula_impl_generics, formula_type_generics, formula_where_clause) =
@@ -264,17 +245,11 @@ pub fn derive(args: FormulaArgs, input: &syn::DeriveInput) -> syn::Result<TokenS
 
             Ok(quote::quote! {
                 impl #impl_generics #ident #type_generics #where_clause {
-                    #(#(
-                        #[doc(hidden)]
-                        #[allow(non_upper_case_globals)]
-                        pub const #field_names_order: ::alkahest::private::usize = #field_ids;
-                    )*)*
-
-                    #(
-                        #[doc(hidden)]
-                        #[allow(non_upper_case_globals)]
-                        pub const #variant_name_ids: u32 = #variant_ids;
-                    )*
+                    #[doc(hidden)]
+                    pub const __ALKAHEST_FORMULA_VARIANTS: &'static [&'static ::alkahest::private::str] = &[#(#variant_names),*];
+
+                    #[doc(hidden)]
+                    pub const __ALKAHEST_FORMULA_VARIANT_FIELDS: &'static [&'static [&'static ::alkahest::private::str]] = &[#(&[#(#variant_field_names),*]),*];
 
                     #[doc(hidden)]
                     #[allow(dead_code, unused_variables)]
@@ -342,6 +317,10 @@ pub fn derive(args: FormulaArgs, input: &syn::DeriveInput) -> syn::Result<TokenS
                 }
 
                 impl #formula_impl_generics ::alkahest::private::BareFormula for #ident #formula_type_generics #formula_where_clause {}
+
+                impl #formula_impl_generics ::alkahest::private::FieldWidth for #ident #formula_type_generics #formula_where_clause {
+                    type Width = #width;
+                }
             })
         }
     }
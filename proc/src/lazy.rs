@@ -0,0 +1,122 @@
+use std::collections::HashSet;
+
+use proc_macro2::TokenStream;
+use syn::spanned::Spanned;
+
+use crate::is_generic_ty;
+
+/// Generates extension trait with lazy field accessors
+/// implemented for `Lazy<'de, Formula>`.
+/// Size prefixes of unsized fields are read with specified width,
+/// `FixedUsize` by default.
+pub fn derive(input: &syn::DeriveInput, width: Option<&syn::Type>) -> syn::Result<TokenStream> {
+    let data = match &input.data {
+        syn::Data::Struct(data) => data,
+        syn::Data::Enum(data) => {
+            return Err(syn::Error::new_spanned(
+                data.enum_token,
+                "Lazy field accessors can be generated only for structs",
+            ))
+        }
+        syn::Data::Union(data) => {
+            return Err(syn::Error::new_spanned(
+                data.union_token,
+                "Lazy field accessors can be generated only for structs",
+            ))
+        }
+    };
+
+    let ident = &input.ident;
+    let vis = &input.vis;
+    let trait_ident = quote::format_ident!("{}LazyFields", ident);
+    let de_lifetime = syn::Lifetime::new("'__de", proc_macro2::Span::call_site());
+
+    let field_types: Vec<_> = data.fields.iter().map(|field| &field.ty).collect();
+
+    let accessors: Vec<syn::Ident> = data
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(idx, field)| match &field.ident {
+            Some(ident) => quote::format_ident!("field_{}", ident),
+            None => quote::format_ident!("field_{}", idx),
+        })
+        .collect();
+
+    let docs = data
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(idx, field)| match &field.ident {
+            Some(ident) => format!("Returns lazy value of the field `{ident}`."),
+            None => format!("Returns lazy value of the field `{idx}`."),
+        });
+
+    let width = match width {
+        Some(width) => quote::quote! { #width },
+        None => quote::quote! { ::alkahest::FixedUsize },
+    };
+
+    let bodies = field_types.iter().enumerate().map(|(idx, ty)| {
+        let skipped = &field_types[..idx];
+        let last = idx + 1 == field_types.len();
+        quote::quote! {
+            let mut de = ::alkahest::private::lazy_deserializer(self);
+            #(
+                ::alkahest::private::skip_field::<#skipped, #width>(&mut de)?;
+            )*
+            ::alkahest::private::read_lazy_field::<#ty, #width>(&mut de, #last)
+        }
+    });
+
+    let mut trait_generics = input.generics.clone();
+    trait_generics.params.insert(
+        0,
+        syn::GenericParam::Lifetime(syn::LifetimeParam::new(de_lifetime.clone())),
+    );
+
+    let mut impl_generics = trait_generics.clone();
+
+    let mut generic_field_types: HashSet<_> = field_types.iter().copied().collect();
+    generic_field_types.retain(|ty| is_generic_ty(ty, &input.generics.params.iter()));
+
+    if !generic_field_types.is_empty() {
+        let predicates = generic_field_types.iter().map(|ty| -> syn::WherePredicate {
+            syn::parse_quote_spanned! { ty.span() => #ty: ::alkahest::private::Formula }
+        });
+        impl_generics
+            .make_where_clause()
+            .predicates
+            .extend(predicates);
+    }
+
+    let (trait_impl_generics, trait_type_generics, trait_where_clause) =
+        trait_generics.split_for_impl();
+    let (impl_impl_generics, _, impl_where_clause) = impl_generics.split_for_impl();
+    let (_, type_generics, _) = input.generics.split_for_impl();
+
+    let trait_doc = format!("Lazy field accessors for [`{ident}`] formula.");
+
+    Ok(quote::quote! {
+        #[doc = #trait_doc]
+        #vis trait #trait_ident #trait_impl_generics #trait_where_clause {
+            #(
+                #[doc = #docs]
+                ///
+                /// # Errors
+                ///
+                /// Returns `DeserializeError` if preceding fields cannot be skipped.
+                fn #accessors(&self) -> ::alkahest::private::Result<::alkahest::Lazy<#de_lifetime, #field_types>, ::alkahest::private::DeserializeError>;
+            )*
+        }
+
+        impl #impl_impl_generics #trait_ident #trait_type_generics for ::alkahest::Lazy<#de_lifetime, #ident #type_generics> #impl_where_clause {
+            #(
+                #[inline(always)]
+                fn #accessors(&self) -> ::alkahest::private::Result<::alkahest::Lazy<#de_lifetime, #field_types>, ::alkahest::private::DeserializeError> {
+                    #bodies
+                }
+            )*
+        }
+    })
+}
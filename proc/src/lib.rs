@@ -1,9 +1,13 @@
 extern crate proc_macro;
 
 mod attrs;
+mod bits;
 mod deserialize;
 mod formula;
+mod lazy;
+mod pod;
 mod serialize;
+mod transparent;
 
 use attrs::{DeserializeArgs, FormulaArgs, SerializeArgs};
 use proc_macro::TokenStream;
@@ -11,9 +15,15 @@ use proc_macro::TokenStream;
 #[proc_macro_attribute]
 pub fn alkahest(attr: TokenStream, item: TokenStream) -> TokenStream {
     let mut output = item.clone();
-    let input = syn::parse_macro_input!(item as syn::DeriveInput);
+    let mut input = syn::parse_macro_input!(item as syn::DeriveInput);
 
-    match alkahest_impl(attr, input) {
+    // Field attributes are consumed here, so the item is emitted without them.
+    let (widths, stripped) = bits::take_field_widths(&mut input);
+    if stripped {
+        output = quote::quote!(#input).into();
+    }
+
+    match widths.and_then(|widths| alkahest_impl(attr, &input, &widths)) {
         Ok(tokens) => output.extend(TokenStream::from(tokens)),
         Err(err) => output.extend(TokenStream::from(err.to_compile_error())),
     }
@@ -22,22 +32,75 @@ pub fn alkahest(attr: TokenStream, item: TokenStream) -> TokenStream {
 
 fn alkahest_impl(
     attr: TokenStream,
-    input: syn::DeriveInput,
+    input: &syn::DeriveInput,
+    widths: &bits::FieldWidths,
 ) -> syn::Result<proc_macro2::TokenStream> {
     let mut output = proc_macro2::TokenStream::new();
     let attr = proc_macro2::TokenStream::from(attr);
     let args = attrs::Args::parse_attributes(attr)?;
+    if let Some(span) = args.pod {
+        if args.formula.is_some()
+            || args.serialize.is_some()
+            || args.serialize_ref.is_some()
+            || args.deserialize.is_some()
+            || args.lazy
+            || args.transparent.is_some()
+            || args.bits.is_some()
+        {
+            return Err(syn::Error::new(
+                span,
+                "`pod` generates all impls and cannot be combined with other items",
+            ));
+        }
+        return pod::derive(input);
+    }
+    if let Some(span) = args.bits {
+        if args.formula.is_some()
+            || args.serialize.is_some()
+            || args.serialize_ref.is_some()
+            || args.deserialize.is_some()
+            || args.lazy
+            || args.transparent.is_some()
+        {
+            return Err(syn::Error::new(
+                span,
+                "`bits` generates all impls and cannot be combined with other items",
+            ));
+        }
+        return bits::derive(input, widths);
+    }
+    if let Some(width) = bits::first_width(widths) {
+        return Err(syn::Error::new_spanned(
+            width,
+            "`bits = N` field attribute requires `bits` attribute on the item",
+        ));
+    }
+    if let Some(span) = args.transparent {
+        let impls = transparent::Impls::from_args(&args, span)?;
+        output.extend(transparent::derive(&impls, input)?);
+        if args.lazy {
+            output.extend(lazy::derive(input, None)?);
+        }
+        return Ok(output);
+    }
+    let width = args
+        .formula
+        .as_ref()
+        .and_then(|formula| formula.width.clone());
     if let Some(args) = args.formula {
-        output.extend(formula::derive(args, &input)?);
+        output.extend(formula::derive(args, input)?);
     }
     if let Some(args) = args.serialize {
-        output.extend(serialize::derive(args, &input, false)?);
+        output.extend(serialize::derive(args, input, false)?);
     }
     if let Some(args) = args.serialize_ref {
-        output.extend(serialize::derive(args, &input, true)?);
+        output.extend(serialize::derive(args, input, true)?);
     }
     if let Some(args) = args.deserialize {
-        output.extend(deserialize::derive(args, &input)?);
+        output.extend(deserialize::derive(args, input)?);
+    }
+    if args.lazy {
+        output.extend(lazy::derive(input, width.as_ref())?);
     }
     Ok(output)
 }
@@ -94,22 +157,51 @@ pub fn derive_deserialize(input: TokenStream) -> TokenStream {
     }
 }
 
+fn is_param_ident<'a>(
+    ident: &syn::Ident,
+    params: &(impl Clone + Iterator<Item = &'a syn::GenericParam>),
+) -> bool {
+    params.clone().any(|param| match param {
+        syn::GenericParam::Type(param) => param.ident == *ident,
+        syn::GenericParam::Const(param) => param.ident == *ident,
+        syn::GenericParam::Lifetime(_) => false,
+    })
+}
+
+/// Checks if formula path names the type itself.
+fn is_self_formula(formula: &syn::Path, ident: &syn::Ident) -> bool {
+    formula.is_ident("Self") || formula.is_ident(ident)
+}
+
+fn is_param_lifetime<'a>(
+    lifetime: &syn::Lifetime,
+    params: &(impl Clone + Iterator<Item = &'a syn::GenericParam>),
+) -> bool {
+    params.clone().any(|param| match param {
+        syn::GenericParam::Lifetime(param) => param.lifetime == *lifetime,
+        _ => false,
+    })
+}
+
 fn is_generic_path<'a>(
     path: &syn::Path,
-    params: &(impl Clone + Iterator<Item = &'a syn::TypeParam>),
+    params: &(impl Clone + Iterator<Item = &'a syn::GenericParam>),
 ) -> bool {
     path.segments.iter().any(|seg| {
-        if params.clone().any(|p| {
-            // if p.ident == "T" {
-            //     panic!();
-            // }
-            p.ident == seg.ident
-        }) {
+        if is_param_ident(&seg.ident, params) {
             return true;
         }
         match &seg.arguments {
             syn::PathArguments::AngleBracketed(args) => args.args.iter().any(|arg| match arg {
+                // Const arguments that are single identifier are parsed as types.
                 syn::GenericArgument::Type(ty) => is_generic_ty(ty, params),
+                syn::GenericArgument::Lifetime(lifetime) => is_param_lifetime(lifetime, params),
+                syn::GenericArgument::Const(expr) => is_generic_expr(expr, params),
+                syn::GenericArgument::AssocType(assoc) => is_generic_ty(&assoc.ty, params),
+                syn::GenericArgument::AssocConst(assoc) => is_generic_expr(&assoc.value, params),
+                syn::GenericArgument::Constraint(constraint) => {
+                    is_generic_bounds(constraint.bounds.iter(), params)
+                }
                 _ => false,
             }),
             syn::PathArguments::Parenthesized(args) => {
@@ -125,29 +217,58 @@ fn is_generic_path<'a>(
     })
 }
 
-// fn has_type_param<'a>(mut params: impl Iterator<Item = &'a syn::GenericParam>) -> bool {
-//     params.any(|param| matches!(param, syn::GenericParam::Type(_)))
-// }
-
-fn filter_type_param<'a>(
-    params: impl Clone + Iterator<Item = &'a syn::GenericParam>,
-) -> impl Clone + Iterator<Item = &'a syn::TypeParam> {
-    params.filter_map(|param| match param {
-        syn::GenericParam::Type(param) => Some(param),
-        _ => None,
+fn is_generic_bounds<'a, 'b>(
+    mut bounds: impl Iterator<Item = &'b syn::TypeParamBound>,
+    params: &(impl Clone + Iterator<Item = &'a syn::GenericParam>),
+) -> bool {
+    bounds.any(|bound| match bound {
+        syn::TypeParamBound::Trait(trait_bound) => is_generic_path(&trait_bound.path, params),
+        syn::TypeParamBound::Lifetime(lifetime) => is_param_lifetime(lifetime, params),
+        _ => false,
     })
 }
 
+/// Checks if expression, such as array length, refers to any of the generic parameters.
+/// Const generic parameters may appear anywhere in the expression.
+fn is_generic_expr<'a>(
+    expr: &syn::Expr,
+    params: &(impl Clone + Iterator<Item = &'a syn::GenericParam>),
+) -> bool {
+    fn is_generic_tokens<'a>(
+        tokens: proc_macro2::TokenStream,
+        params: &(impl Clone + Iterator<Item = &'a syn::GenericParam>),
+    ) -> bool {
+        tokens.into_iter().any(|tt| match tt {
+            proc_macro2::TokenTree::Ident(ident) => is_param_ident(&ident, params),
+            proc_macro2::TokenTree::Group(group) => is_generic_tokens(group.stream(), params),
+            _ => false,
+        })
+    }
+
+    is_generic_tokens(quote::quote!(#expr), params)
+}
+
+/// Checks if type refers to any of the generic parameters,
+/// including lifetimes and constants.
 fn is_generic_ty<'a>(
     ty: &syn::Type,
-    params: &(impl Clone + Iterator<Item = &'a syn::TypeParam>),
+    params: &(impl Clone + Iterator<Item = &'a syn::GenericParam>),
 ) -> bool {
     match ty {
-        syn::Type::Array(syn::TypeArray { elem, .. })
-        | syn::Type::Group(syn::TypeGroup { elem, .. })
+        syn::Type::Array(syn::TypeArray { elem, len, .. }) => {
+            is_generic_ty(elem, params) || is_generic_expr(len, params)
+        }
+        syn::Type::Reference(syn::TypeReference { lifetime, elem, .. }) => {
+            if let Some(lifetime) = lifetime {
+                if is_param_lifetime(lifetime, params) {
+                    return true;
+                }
+            }
+            is_generic_ty(elem, params)
+        }
+        syn::Type::Group(syn::TypeGroup { elem, .. })
         | syn::Type::Paren(syn::TypeParen { elem, .. })
         | syn::Type::Ptr(syn::TypePtr { elem, .. })
-        | syn::Type::Reference(syn::TypeReference { elem, .. })
         | syn::Type::Slice(syn::TypeSlice { elem, .. }) => is_generic_ty(elem, params),
         syn::Type::BareFn(syn::TypeBareFn { inputs, output, .. }) => {
             if let syn::ReturnType::Type(_, ty) = output {
@@ -165,13 +286,9 @@ fn is_generic_ty<'a>(
             }
             is_generic_path(path, params)
         }
-        syn::Type::TraitObject(syn::TypeTraitObject { bounds, .. }) => {
-            bounds.iter().any(|bound| match bound {
-                syn::TypeParamBound::Trait(trait_bound) => {
-                    is_generic_path(&trait_bound.path, params)
-                }
-                _ => false,
-            })
+        syn::Type::TraitObject(syn::TypeTraitObject { bounds, .. })
+        | syn::Type::ImplTrait(syn::TypeImplTrait { bounds, .. }) => {
+            is_generic_bounds(bounds.iter(), params)
         }
         syn::Type::Tuple(syn::TypeTuple { elems, .. }) => {
             elems.iter().any(|ty| is_generic_ty(ty, params))
@@ -180,59 +297,168 @@ fn is_generic_ty<'a>(
     }
 }
 
-fn struct_field_order_checks(
+/// Returns formula path as written by user, for error messages.
+fn formula_name(formula: &syn::Path, variant: Option<&syn::Ident>) -> String {
+    let mut name = quote::quote!(#formula).to_string().replace(' ', "");
+    if let Some(variant) = variant {
+        name.push_str("::");
+        name.push_str(&variant.to_string());
+    }
+    name
+}
+
+/// Patterns that bind single field of the formula by reference.
+/// Other fields are skipped, so errors point at the field in question.
+fn field_patterns(fields: &syn::Fields) -> Vec<proc_macro2::TokenStream> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(idx, field)| match &field.ident {
+            Some(ident) => quote::quote! { { ref #ident, .. } },
+            None => {
+                let skip = (0..idx).map(|_| quote::quote! { _, });
+                let name = quote::format_ident!("_{}", idx);
+                quote::quote! { ( #(#skip)* ref #name, .. ) }
+            }
+        })
+        .collect()
+}
+
+/// Expression that evaluates to the index of the formula's variant at compile time.
+fn variant_index(formula: &syn::Path, variant: &syn::Ident) -> proc_macro2::TokenStream {
+    let name = variant.to_string();
+    quote::quote! {
+        const { ::alkahest::private::variant_index(<#formula>::__ALKAHEST_FORMULA_VARIANTS, #name) }
+    }
+}
+
+/// Generates compile-time checks that fields of the type match
+/// fields of the formula or its variant.
+///
+/// Fields not found in the formula, order of fields
+/// and formula fields missing in the type are checked
+/// with constant assertions at the field in question.
+/// Compiler also reports fields not found in the formula
+/// where fields are bound by pattern.
+fn fields_checks(
+    fields: &syn::Fields,
+    variant: Option<&syn::Ident>,
+    this: &str,
+    span: proc_macro2::Span,
+    formula: &syn::Path,
+) -> proc_macro2::TokenStream {
+    let formula_fields = match variant {
+        None => quote::quote! {
+            ::alkahest::private::Option::Some(<#formula>::__ALKAHEST_FORMULA_FIELDS)
+        },
+        Some(v) => {
+            let name = v.to_string();
+            quote::quote! {
+                ::alkahest::private::variant_fields(
+                    <#formula>::__ALKAHEST_FORMULA_VARIANTS,
+                    <#formula>::__ALKAHEST_FORMULA_VARIANT_FIELDS,
+                    #name,
+                )
+            }
+        }
+    };
+
+    let formula_name = formula_name(formula, variant);
+
+    let mut checks = proc_macro2::TokenStream::new();
+
+    if let syn::Fields::Named(fields) = fields {
+        for (idx, field) in fields.named.iter().enumerate() {
+            let f = field.ident.as_ref().unwrap();
+            let name = f.to_string();
+            let missing = format!("field `{f}` missing in formula `{formula_name}`");
+            let error =
+                format!("Order of field `{this}.{f}` differs from formula `{formula_name}`");
+            checks.extend(quote::quote_spanned! { f.span() =>
+                ::alkahest::private::assert!(
+                    ::alkahest::private::has_name(__fields, #name),
+                    #missing,
+                );
+                ::alkahest::private::assert!(
+                    ::alkahest::private::field_order_matches(__fields, #name, #idx),
+                    #error,
+                );
+            });
+        }
+    }
+
+    let field_count = fields.len();
+    let error = format!("Fields of formula `{formula_name}` are missing in `{this}`");
+    checks.extend(quote::quote_spanned! { span =>
+        ::alkahest::private::assert!(__fields.len() <= #field_count, #error);
+    });
+
+    quote::quote! {
+        if let ::alkahest::private::Option::Some(__fields) = #formula_fields {
+            #checks
+        }
+    }
+}
+
+fn struct_field_checks(
     data: &syn::DataStruct,
     variant: Option<&syn::Ident>,
     this: &syn::Ident,
     formula: &syn::Path,
 ) -> proc_macro2::TokenStream {
-    let no_named_fields = syn::punctuated::Punctuated::<syn::Field, syn::Token![,]>::new();
-
-    match &data.fields {
-        syn::Fields::Named(fields) => fields.named.iter(),
-        _ => no_named_fields.iter(),
-    }.enumerate()
-    .map(|(idx, field)| {
-        let order = match variant {
-            None => quote::format_ident!(
-                "__ALKAHEST_FORMULA_FIELD_{}_IDX",
-                field.ident.as_ref().unwrap(),
-            ),
-            Some(v) => quote::format_ident!(
-                "__ALKAHEST_FORMULA_VARIANT_{}_FIELD_{}_IDX",
-                v,
-                field.ident.as_ref().unwrap(),
-            ),
-        };
-        let f = field.ident.as_ref().unwrap();
-        let error = format!("Field `{this}.{f}` is out of order with formula's");
-        quote::quote_spanned!(f.span() => ::alkahest::private::debug_assert_eq!(#idx, #formula::#order, #error);)
-    })
-    .collect()
+    let span = variant.map_or_else(|| this.span(), syn::Ident::span);
+    fields_checks(&data.fields, variant, &this.to_string(), span, formula)
 }
 
-fn enum_field_order_checks(
+fn enum_field_checks(
     data: &syn::DataEnum,
     this: &syn::Ident,
     formula: &syn::Path,
 ) -> proc_macro2::TokenStream {
-    let no_named_fields = syn::punctuated::Punctuated::<syn::Field, syn::Token![,]>::new();
-
-    data.variants.iter().flat_map(|v| {
-        match &v.fields {
-            syn::Fields::Named(fields) => fields.named.iter(),
-            _ => no_named_fields.iter(),
-        }
-        .enumerate()
-        .map(move |(idx, field)| {
-            let f = field.ident.as_ref().unwrap();
-            let order = quote::format_ident!(
-                "__ALKAHEST_FORMULA_VARIANT_{}_FIELD_{}_IDX",
-                v.ident,
-                field.ident.as_ref().unwrap(),
-            );
-            let error = format!("Field `{this}.{f}` is out of order with formula's");
-            quote::quote_spanned!(f.span() => ::alkahest::private::debug_assert_eq!(#idx, #formula::#order, #error);)
+    let formula_name = formula_name(formula, None);
+    data.variants
+        .iter()
+        .map(|v| {
+            let name = v.ident.to_string();
+            let missing = format!("no variant `{name}` in formula `{formula_name}`");
+            let variant_check = quote::quote_spanned! { v.ident.span() =>
+                ::alkahest::private::assert!(
+                    ::alkahest::private::has_name(<#formula>::__ALKAHEST_FORMULA_VARIANTS, #name),
+                    #missing,
+                );
+            };
+            let this = format!("{}::{}", this, v.ident);
+            let fields_checks =
+                fields_checks(&v.fields, Some(&v.ident), &this, v.ident.span(), formula);
+            quote::quote! { #variant_check #fields_checks }
         })
-    }).collect()
+        .collect()
+}
+
+/// Places field checks where they are evaluated.
+///
+/// Checks are evaluated at item level, even if the impl is never used.
+/// When formula refers to generic parameters of the impl
+/// checks are placed into method bodies instead
+/// and evaluated when those are instantiated.
+///
+/// Returns item-level and body-level tokens.
+fn place_field_checks(
+    checks: proc_macro2::TokenStream,
+    formula: &syn::Path,
+    generics: &syn::Generics,
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    if checks.is_empty() {
+        (checks, proc_macro2::TokenStream::new())
+    } else if is_generic_path(formula, &generics.params.iter()) {
+        let body = quote::quote! {
+            const { #checks };
+        };
+        (proc_macro2::TokenStream::new(), body)
+    } else {
+        let item = quote::quote! {
+            const _: () = { #checks };
+        };
+        (item, proc_macro2::TokenStream::new())
+    }
 }
@@ -0,0 +1,114 @@
+use proc_macro2::TokenStream;
+
+use crate::is_generic_ty;
+
+/// Generates impls for plain-old-data struct
+/// serialized as its in-memory representation.
+pub fn derive(input: &syn::DeriveInput) -> syn::Result<TokenStream> {
+    let data = match &input.data {
+        syn::Data::Struct(data) => data,
+        syn::Data::Enum(data) => {
+            return Err(syn::Error::new_spanned(
+                data.enum_token,
+                "`pod` formula must be a struct",
+            ))
+        }
+        syn::Data::Union(data) => {
+            return Err(syn::Error::new_spanned(
+                data.union_token,
+                "`pod` formula must be a struct",
+            ))
+        }
+    };
+
+    let ident = &input.ident;
+
+    let mut generics = input.generics.clone();
+    let (_, type_generics, _) = input.generics.split_for_impl();
+    let where_clause = generics.make_where_clause();
+    where_clause
+        .predicates
+        .push(syn::parse_quote! { #ident #type_generics: ::alkahest::private::Pod });
+    for field in &data.fields {
+        let ty = &field.ty;
+        if is_generic_ty(ty, &input.generics.params.iter()) {
+            where_clause
+                .predicates
+                .push(syn::parse_quote! { #ty: ::alkahest::private::SwapBytes });
+        }
+    }
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+
+    let mut de_generics = generics.clone();
+    de_generics.params.insert(0, syn::parse_quote! { '__de });
+    let (de_impl_generics, _, _) = de_generics.split_for_impl();
+
+    let members = data
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(idx, field)| match &field.ident {
+            Some(ident) => syn::Member::Named(ident.clone()),
+            None => syn::Member::Unnamed(syn::Index::from(idx)),
+        });
+
+    Ok(quote::quote! {
+        impl #impl_generics ::alkahest::private::Formula for #ident #type_generics #where_clause {
+            const MAX_STACK_SIZE: ::alkahest::private::Option<::alkahest::private::usize> = ::alkahest::private::Option::Some(::alkahest::private::size_of::<Self>());
+            const EXACT_SIZE: ::alkahest::private::bool = true;
+            const HEAPLESS: ::alkahest::private::bool = true;
+        }
+
+        impl #impl_generics ::alkahest::private::BareFormula for #ident #type_generics #where_clause {}
+
+        impl #impl_generics ::alkahest::private::SwapBytes for #ident #type_generics #where_clause {
+            #[inline(always)]
+            fn swap_bytes(&mut self) {
+                #(::alkahest::private::SwapBytes::swap_bytes(&mut self.#members);)*
+            }
+        }
+
+        impl #impl_generics ::alkahest::private::Serialize<Self> for #ident #type_generics #where_clause {
+            #[inline(always)]
+            fn serialize<__alkahest_Buffer>(self, sizes: &mut ::alkahest::private::Sizes, buffer: __alkahest_Buffer) -> ::alkahest::private::Result<(), __alkahest_Buffer::Error>
+            where
+                __alkahest_Buffer: ::alkahest::private::Buffer,
+            {
+                ::alkahest::private::write_pod(&self, sizes, buffer)
+            }
+
+            #[inline(always)]
+            fn size_hint(&self) -> ::alkahest::private::Option<::alkahest::private::Sizes> {
+                ::alkahest::private::Option::Some(::alkahest::private::Sizes::with_stack(::alkahest::private::size_of::<Self>()))
+            }
+        }
+
+        impl #impl_generics ::alkahest::private::SerializeRef<Self> for #ident #type_generics #where_clause {
+            #[inline(always)]
+            fn serialize<__alkahest_Buffer>(&self, sizes: &mut ::alkahest::private::Sizes, buffer: __alkahest_Buffer) -> ::alkahest::private::Result<(), __alkahest_Buffer::Error>
+            where
+                __alkahest_Buffer: ::alkahest::private::Buffer,
+            {
+                ::alkahest::private::write_pod(self, sizes, buffer)
+            }
+
+            #[inline(always)]
+            fn size_hint(&self) -> ::alkahest::private::Option<::alkahest::private::Sizes> {
+                ::alkahest::private::Option::Some(::alkahest::private::Sizes::with_stack(::alkahest::private::size_of::<Self>()))
+            }
+        }
+
+        impl #de_impl_generics ::alkahest::private::Deserialize<'__de, Self> for #ident #type_generics #where_clause {
+            #[inline(always)]
+            fn deserialize(de: ::alkahest::private::Deserializer<'__de>) -> ::alkahest::private::Result<Self, ::alkahest::private::DeserializeError> {
+                ::alkahest::private::read_pod(de)
+            }
+
+            #[inline(always)]
+            fn deserialize_in_place(&mut self, de: ::alkahest::private::Deserializer<'__de>) -> ::alkahest::private::Result<(), ::alkahest::private::DeserializeError> {
+                *self = ::alkahest::private::read_pod(de)?;
+                ::alkahest::private::Result::Ok(())
+            }
+        }
+    })
+}
@@ -3,8 +3,8 @@ use std::collections::HashSet;
 use proc_macro2::TokenStream;
 
 use crate::{
-    attrs::SerializeArgs, enum_field_order_checks, filter_type_param, is_generic_ty,
-    struct_field_order_checks,
+    attrs::SerializeArgs, enum_field_checks, field_patterns, is_generic_ty, is_self_formula,
+    place_field_checks, struct_field_checks, variant_index,
 };
 
 struct Config {
@@ -52,8 +52,7 @@ impl Config {
                     syn::Data::Struct(data) => {
                         let mut all_generic_field_types: HashSet<_> =
                             data.fields.iter().map(|f| &f.ty).collect();
-                        all_generic_field_types
-                            .retain(|ty| is_generic_ty(ty, &filter_type_param(params.iter())));
+                        all_generic_field_types.retain(|ty| is_generic_ty(ty, &params.iter()));
 
                         if !all_generic_field_types.is_empty() {
                             if by_ref {
@@ -76,9 +75,7 @@ impl Config {
 
                         let mut all_generic_field_types: HashSet<_> =
                             all_fields.map(|f| &f.ty).collect();
-                        all_generic_field_types.retain(|ty| {
-                            is_generic_ty(ty, &filter_type_param(generics.params.iter()))
-                        });
+                        all_generic_field_types.retain(|ty| is_generic_ty(ty, &params.iter()));
 
                         if !all_generic_field_types.is_empty() {
                             if by_ref {
@@ -109,13 +106,13 @@ impl Config {
                 formula: syn::parse_quote! { Self },
                 generics,
                 variant: args.variant,
-                check_fields: true,
+                check_fields: false,
             },
             (Some(formula), None) => Config {
                 formula,
                 generics: syn::Generics::default(),
                 variant: args.variant,
-                check_fields: false,
+                check_fields: true,
             },
             (Some(formula), Some(generics)) => Config {
                 formula,
@@ -145,8 +142,17 @@ pub fn derive(
             "Serialize cannot be derived for unions",
         )),
         syn::Data::Struct(data) => {
+            if let Some(variant) = &cfg.variant {
+                if is_self_formula(&cfg.formula, ident) {
+                    return Err(syn::Error::new_spanned(
+                        variant,
+                        format!("Formula `{ident}` is a struct and has no variants"),
+                    ));
+                }
+            }
+
             let field_checks = if cfg.check_fields {
-                struct_field_order_checks(&data, cfg.variant.as_ref(), &input.ident, &cfg.formula)
+                struct_field_checks(data, cfg.variant.as_ref(), &input.ident, &cfg.formula)
             } else {
                 TokenStream::new()
             };
@@ -220,6 +226,8 @@ pub fn derive(
                 Some(v) => quote::quote! { :: #v },
             };
 
+            let field_patterns = field_patterns(&data.fields);
+
             let start_stack_size = match &cfg.variant {
                 None => quote::quote! { 0usize },
                 Some(_) => quote::quote! { ::alkahest::private::VARIANT_SIZE },
@@ -230,9 +238,8 @@ pub fn derive(
             let write_variant = match &cfg.variant {
                 None => quote::quote! {},
                 Some(v) => {
-                    let variant_name_idx =
-                        quote::format_ident!("__ALKAHEST_FORMULA_VARIANT_{}_IDX", v);
-                    quote::quote! { ::alkahest::private::write_exact_size_field::<u32, u32, _>(#formula_path::#variant_name_idx, __sizes, __buffer.reborrow())?; }
+                    let variant_idx = variant_index(formula_path, v);
+                    quote::quote! { ::alkahest::private::write_exact_size_field::<u32, u32, _>(#variant_idx, __sizes, __buffer.reborrow())?; }
                 }
             };
 
@@ -240,7 +247,7 @@ pub fn derive(
 
             generics.lt_token = generics.lt_token.or(cfg.generics.lt_token);
             generics.gt_token = generics.gt_token.or(cfg.generics.gt_token);
-            generics.params.extend(cfg.generics.params.into_iter());
+            generics.params.extend(cfg.generics.params);
 
             if let Some(where_clause) = cfg.generics.where_clause {
                 generics
@@ -249,10 +256,15 @@ pub fn derive(
                     .extend(where_clause.predicates);
             }
 
+            let (field_checks_item, field_checks) =
+                place_field_checks(field_checks, formula_path, &generics);
+
             let (impl_generics, _type_generics, where_clause) = generics.split_for_impl();
 
             let tokens = if by_ref {
                 quote::quote! {
+                    #field_checks_item
+
                     impl #impl_generics ::alkahest::private::SerializeRef<#formula_path> for #ident #type_generics #where_clause {
                         #[inline(always)]
                         fn serialize<__alkahest_Buffer>(&self, __sizes: &mut ::alkahest::private::Sizes, mut __buffer: __alkahest_Buffer) -> ::alkahest::private::Result<(), __alkahest_Buffer::Error>
@@ -266,7 +278,7 @@ pub fn derive(
                             #write_variant
                             #(
                                 let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
-                                    #formula_path #with_variant #bind_ref_names => #bound_names,
+                                    #formula_path #with_variant #field_patterns => #bound_names,
                                     _ => unreachable!(),
                                 });
                                 with_formula.write_field(#bound_names, __sizes, __buffer.reborrow(), #field_count == 1 + #field_ids)?;
@@ -285,7 +297,7 @@ pub fn derive(
                             let mut __total = ::alkahest::private::Sizes::with_stack(#start_stack_size);
                             #(
                                 let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
-                                    #formula_path #with_variant #bind_ref_names => #bound_names,
+                                    #formula_path #with_variant #field_patterns => #bound_names,
                                     _ => unreachable!(),
                                 });
                                 __total += with_formula.size_hint(&#bound_names, #field_count == 1 + #field_ids)?;
@@ -296,6 +308,8 @@ pub fn derive(
                 }
             } else {
                 quote::quote! {
+                    #field_checks_item
+
                     impl #impl_generics ::alkahest::private::Serialize<#formula_path> for #ident #type_generics #where_clause {
                         #[inline(always)]
                         fn serialize<__alkahest_Buffer>(self, __sizes: &mut ::alkahest::private::Sizes, mut __buffer: __alkahest_Buffer) -> ::alkahest::private::Result<(), __alkahest_Buffer::Error>
@@ -309,7 +323,7 @@ pub fn derive(
                             #write_variant
                             #(
                                 let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
-                                    #formula_path #with_variant #bind_ref_names => #bound_names,
+                                    #formula_path #with_variant #field_patterns => #bound_names,
                                     _ => unreachable!(),
                                 });
                                 with_formula.write_field(#bound_names, __sizes, __buffer.reborrow(), #field_count == 1 + #field_ids)?;
@@ -328,7 +342,7 @@ pub fn derive(
                             let mut __total = ::alkahest::private::Sizes::with_stack(#start_stack_size);
                             #(
                                 let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
-                                    #formula_path #with_variant #bind_ref_names => #bound_names,
+                                    #formula_path #with_variant #field_patterns => #bound_names,
                                     _ => unreachable!(),
                                 });
                                 __total += with_formula.size_hint(#bound_names, #field_count == 1 + #field_ids)?;
@@ -343,7 +357,7 @@ pub fn derive(
         }
         syn::Data::Enum(data) => {
             let field_checks = if cfg.check_fields {
-                enum_field_order_checks(&data, &input.ident, &cfg.formula)
+                enum_field_checks(data, &input.ident, &cfg.formula)
             } else {
                 TokenStream::new()
             };
@@ -436,23 +450,27 @@ pub fn derive(
                 })
                 .collect::<Vec<_>>();
 
-            let variant_name_ids: Vec<syn::Ident> = data
+            let field_patterns = data
                 .variants
                 .iter()
-                .map(|variant| {
-                    quote::format_ident!("__ALKAHEST_FORMULA_VARIANT_{}_IDX", variant.ident,)
-                })
-                .collect();
+                .map(|v| field_patterns(&v.fields))
+                .collect::<Vec<_>>();
 
             let field_counts: Vec<_> = data.variants.iter().map(|v| v.fields.len()).collect();
 
             let formula_path = &cfg.formula;
 
+            let variant_ids = data
+                .variants
+                .iter()
+                .map(|v| variant_index(formula_path, &v.ident))
+                .collect::<Vec<_>>();
+
             let mut generics = input.generics.clone();
 
             generics.lt_token = generics.lt_token.or(cfg.generics.lt_token);
             generics.gt_token = generics.gt_token.or(cfg.generics.gt_token);
-            generics.params.extend(cfg.generics.params.into_iter());
+            generics.params.extend(cfg.generics.params);
 
             if let Some(where_clause) = cfg.generics.where_clause {
                 generics
@@ -461,10 +479,15 @@ pub fn derive(
                     .extend(where_clause.predicates);
             }
 
+            let (field_checks_item, field_checks) =
+                place_field_checks(field_checks, formula_path, &generics);
+
             let (impl_generics, _type_generics, where_clause) = generics.split_for_impl();
 
             let tokens = if by_ref {
                 quote::quote! {
+                    #field_checks_item
+
                     impl #impl_generics ::alkahest::private::SerializeRef<#formula_path> for #ident #type_generics #where_clause {
                         #[inline(always)]
                         fn serialize<__alkahest_Buffer>(&self, __sizes: &mut ::alkahest::private::Sizes, mut __buffer: __alkahest_Buffer) -> ::alkahest::private::Result<(), __alkahest_Buffer::Error>
@@ -476,10 +499,10 @@ pub fn derive(
                             match *self {
                                 #(
                                     #ident::#variant_names #bind_ref_names => {
-                                        ::alkahest::private::write_exact_size_field::<u32, u32, _>(#formula_path::#variant_name_ids, __sizes, __buffer.reborrow())?;
+                                        ::alkahest::private::write_exact_size_field::<u32, u32, _>(#variant_ids, __sizes, __buffer.reborrow())?;
                                         #(
                                             let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
-                                                #formula_path::#variant_names #bind_ref_names => #bound_names,
+                                                #formula_path::#variant_names #field_patterns => #bound_names,
                                                 _ => unreachable!(),
                                             });
                                             with_formula.write_field(#bound_names, __sizes, __buffer.reborrow(), #field_counts == 1 + #field_ids)?;
@@ -503,7 +526,7 @@ pub fn derive(
                                         let mut __total = ::alkahest::private::Sizes::with_stack(::alkahest::private::VARIANT_SIZE);
                                         #(
                                             let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
-                                                #formula_path::#variant_names #bind_ref_names => #bound_names,
+                                                #formula_path::#variant_names #field_patterns => #bound_names,
                                                 _ => unreachable!(),
                                             });
                                             __total += with_formula.size_hint(&#bound_names, #field_counts == 1 + #field_ids)?;
@@ -517,6 +540,8 @@ pub fn derive(
                 }
             } else {
                 quote::quote! {
+                    #field_checks_item
+
                     impl #impl_generics ::alkahest::private::Serialize<#formula_path> for #ident #type_generics #where_clause {
                         #[inline(always)]
                         fn serialize<__alkahest_Buffer>(self, __sizes: &mut ::alkahest::private::Sizes, mut __buffer: __alkahest_Buffer) -> ::alkahest::private::Result<(), __alkahest_Buffer::Error>
@@ -528,10 +553,10 @@ pub fn derive(
                             match self {
                                 #(
                                     #ident::#variant_names #bind_names => {
-                                        ::alkahest::private::write_exact_size_field::<u32, u32, _>(#formula_path::#variant_name_ids, __sizes, __buffer.reborrow())?;
+                                        ::alkahest::private::write_exact_size_field::<u32, u32, _>(#variant_ids, __sizes, __buffer.reborrow())?;
                                         #(
                                             let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
-                                                #formula_path::#variant_names #bind_ref_names => #bound_names,
+                                                #formula_path::#variant_names #field_patterns => #bound_names,
                                                 _ => unreachable!(),
                                             });
                                             with_formula.write_field(#bound_names, __sizes, __buffer.reborrow(), #field_counts == 1 + #field_ids)?;
@@ -555,7 +580,7 @@ pub fn derive(
                                         let mut __total = ::alkahest::private::Sizes::with_stack(::alkahest::private::VARIANT_SIZE);
                                         #(
                                             let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
-                                                #formula_path::#variant_names #bind_ref_names => #bound_names,
+                                                #formula_path::#variant_names #field_patterns => #bound_names,
                                                 _ => unreachable!(),
                                             });
                                             __total += with_formula.size_hint(#bound_names, #field_counts == 1 + #field_ids)?;
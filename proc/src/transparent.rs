@@ -0,0 +1,222 @@
+use proc_macro2::TokenStream;
+
+use crate::{attrs::Args, is_generic_ty};
+
+/// Which impls are generated for transparent newtype.
+pub struct Impls {
+    formula: bool,
+    serialize: bool,
+    serialize_ref: bool,
+    deserialize: bool,
+}
+
+impl Impls {
+    /// Collects impls requested alongside `transparent` flag.
+    /// When none are listed all of them are generated.
+    pub fn from_args(args: &Args, span: proc_macro2::Span) -> syn::Result<Self> {
+        let explicit = args
+            .serialize
+            .iter()
+            .chain(&args.serialize_ref)
+            .any(|args| {
+                args.formula.is_some() || args.generics.is_some() || args.variant.is_some()
+            })
+            || args
+                .formula
+                .as_ref()
+                .is_some_and(|args| args.generics.is_some() || args.width.is_some())
+            || args.deserialize.as_ref().is_some_and(|args| {
+                args.formula.is_some() || args.generics.is_some() || args.lifetime.is_some()
+            });
+
+        if explicit {
+            return Err(syn::Error::new(
+                span,
+                "`transparent` newtype forwards to the field's formula, explicit formulas, generics and widths are not supported",
+            ));
+        }
+
+        let mut impls = Impls {
+            formula: args.formula.is_some(),
+            serialize: args.serialize.is_some(),
+            serialize_ref: args.serialize_ref.is_some(),
+            deserialize: args.deserialize.is_some(),
+        };
+
+        if !impls.formula && !impls.serialize && !impls.serialize_ref && !impls.deserialize {
+            impls = Impls {
+                formula: true,
+                serialize: true,
+                serialize_ref: true,
+                deserialize: true,
+            };
+        }
+
+        Ok(impls)
+    }
+}
+
+/// Generates impls for newtype that reuses formula of its only field.
+pub fn derive(impls: &Impls, input: &syn::DeriveInput) -> syn::Result<TokenStream> {
+    let data = match &input.data {
+        syn::Data::Struct(data) => data,
+        syn::Data::Enum(data) => {
+            return Err(syn::Error::new_spanned(
+                data.enum_token,
+                "`transparent` requires a struct with exactly one field",
+            ))
+        }
+        syn::Data::Union(data) => {
+            return Err(syn::Error::new_spanned(
+                data.union_token,
+                "`transparent` requires a struct with exactly one field",
+            ))
+        }
+    };
+
+    if data.fields.len() != 1 {
+        return Err(syn::Error::new_spanned(
+            &data.fields,
+            "`transparent` requires a struct with exactly one field",
+        ));
+    }
+
+    let field = data.fields.iter().next().unwrap();
+    let ty = &field.ty;
+    let member = match &field.ident {
+        Some(ident) => syn::Member::Named(ident.clone()),
+        None => syn::Member::Unnamed(syn::Index::from(0)),
+    };
+
+    let ident = &input.ident;
+    let generic = is_generic_ty(ty, &input.generics.params.iter());
+
+    let with_predicates = |predicates: Vec<syn::WherePredicate>| {
+        let mut generics = input.generics.clone();
+        if generic {
+            generics.make_where_clause().predicates.extend(predicates);
+        }
+        generics
+    };
+
+    let (_, type_generics, _) = input.generics.split_for_impl();
+
+    let mut output = TokenStream::new();
+
+    if impls.formula {
+        let generics = with_predicates(vec![
+            syn::parse_quote! { #ty: ::alkahest::private::Formula },
+        ]);
+        let (impl_generics, _, where_clause) = generics.split_for_impl();
+
+        output.extend(quote::quote! {
+            impl #impl_generics ::alkahest::private::Formula for #ident #type_generics #where_clause {
+                const MAX_STACK_SIZE: ::alkahest::private::Option<::alkahest::private::usize> = <#ty as ::alkahest::private::Formula>::MAX_STACK_SIZE;
+                const EXACT_SIZE: ::alkahest::private::bool = <#ty as ::alkahest::private::Formula>::EXACT_SIZE;
+                const HEAPLESS: ::alkahest::private::bool = <#ty as ::alkahest::private::Formula>::HEAPLESS;
+            }
+        });
+
+        // Newtype is bare only if inner formula is.
+        // Higher-ranked bound keeps the impl conditional for concrete inner types,
+        // where plain bound that does not hold would be an error.
+        let mut generics = generics;
+        generics
+            .make_where_clause()
+            .predicates
+            .push(syn::parse_quote! { for<'__alkahest> #ty: ::alkahest::private::BareFormula });
+        let (impl_generics, _, where_clause) = generics.split_for_impl();
+
+        output.extend(quote::quote! {
+            impl #impl_generics ::alkahest::private::BareFormula for #ident #type_generics #where_clause {}
+        });
+    }
+
+    if impls.serialize {
+        let generics = with_predicates(vec![
+            syn::parse_quote! { #ty: ::alkahest::private::Formula + ::alkahest::private::Serialize<#ty> },
+        ]);
+        let (impl_generics, _, where_clause) = generics.split_for_impl();
+
+        output.extend(quote::quote! {
+            impl #impl_generics ::alkahest::private::Serialize<Self> for #ident #type_generics #where_clause {
+                #[inline(always)]
+                fn serialize<__alkahest_Buffer>(self, sizes: &mut ::alkahest::private::Sizes, buffer: __alkahest_Buffer) -> ::alkahest::private::Result<(), __alkahest_Buffer::Error>
+                where
+                    __alkahest_Buffer: ::alkahest::private::Buffer,
+                {
+                    <#ty as ::alkahest::private::Serialize<#ty>>::serialize(self.#member, sizes, buffer)
+                }
+
+                #[inline(always)]
+                fn size_hint(&self) -> ::alkahest::private::Option<::alkahest::private::Sizes> {
+                    <#ty as ::alkahest::private::Serialize<#ty>>::size_hint(&self.#member)
+                }
+            }
+        });
+    }
+
+    if impls.serialize_ref {
+        let generics = with_predicates(vec![
+            syn::parse_quote! { #ty: ::alkahest::private::Formula },
+            syn::parse_quote! { for<'__ser> &'__ser #ty: ::alkahest::private::Serialize<#ty> },
+        ]);
+        let (impl_generics, _, where_clause) = generics.split_for_impl();
+
+        output.extend(quote::quote! {
+            impl #impl_generics ::alkahest::private::SerializeRef<Self> for #ident #type_generics #where_clause {
+                #[inline(always)]
+                fn serialize<__alkahest_Buffer>(&self, sizes: &mut ::alkahest::private::Sizes, buffer: __alkahest_Buffer) -> ::alkahest::private::Result<(), __alkahest_Buffer::Error>
+                where
+                    __alkahest_Buffer: ::alkahest::private::Buffer,
+                {
+                    <&#ty as ::alkahest::private::Serialize<#ty>>::serialize(&self.#member, sizes, buffer)
+                }
+
+                #[inline(always)]
+                fn size_hint(&self) -> ::alkahest::private::Option<::alkahest::private::Sizes> {
+                    <&#ty as ::alkahest::private::Serialize<#ty>>::size_hint(&&self.#member)
+                }
+            }
+        });
+    }
+
+    if impls.deserialize {
+        let de = syn::Lifetime::new("'__de", proc_macro2::Span::call_site());
+
+        let mut generics = with_predicates(vec![
+            syn::parse_quote! { #ty: ::alkahest::private::Formula + ::alkahest::private::Deserialize<#de, #ty> },
+        ]);
+        let bounds: syn::punctuated::Punctuated<_, syn::Token![+]> = input
+            .generics
+            .lifetimes()
+            .map(|lt| lt.lifetime.clone())
+            .collect();
+        generics
+            .params
+            .push(syn::GenericParam::Lifetime(syn::LifetimeParam {
+                attrs: Vec::new(),
+                lifetime: de.clone(),
+                colon_token: (!bounds.is_empty()).then(Default::default),
+                bounds,
+            }));
+        let (impl_generics, _, where_clause) = generics.split_for_impl();
+
+        output.extend(quote::quote! {
+            impl #impl_generics ::alkahest::private::Deserialize<#de, Self> for #ident #type_generics #where_clause {
+                #[inline(always)]
+                fn deserialize(de: ::alkahest::private::Deserializer<#de>) -> ::alkahest::private::Result<Self, ::alkahest::private::DeserializeError> {
+                    let value = <#ty as ::alkahest::private::Deserialize<#de, #ty>>::deserialize(de)?;
+                    ::alkahest::private::Result::Ok(#ident { #member: value })
+                }
+
+                #[inline(always)]
+                fn deserialize_in_place(&mut self, de: ::alkahest::private::Deserializer<#de>) -> ::alkahest::private::Result<(), ::alkahest::private::DeserializeError> {
+                    <#ty as ::alkahest::private::Deserialize<#de, #ty>>::deserialize_in_place(&mut self.#member, de)
+                }
+            }
+        });
+    }
+
+    Ok(output)
+}
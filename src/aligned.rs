@@ -0,0 +1,224 @@
+use core::{
+    marker::PhantomData,
+    mem::{align_of, size_of, size_of_val},
+};
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use crate::{
+    buffer::Buffer,
+    bytes::Bytes,
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::{reference_size, BareFormula, Formula},
+    pod::{write_pod_slice, SwapBytes},
+    serialize::{Serialize, SerializeRef, Sizes},
+    size::FixedUsize,
+    width::Width,
+};
+
+/// A formula for slices of plain-old-data values
+/// that can be borrowed from the input without copying.
+///
+/// Use as `Aligned<[u32]>` or `Aligned<[f32]>`.
+/// Elements are stored in dynamic payload as a single block of bytes
+/// equal to their in-memory representation, like [`PodSlice`](crate::PodSlice).
+/// The block is padded so that it starts at offset
+/// that is multiple of `align_of::<T>()` from the start of the buffer.
+///
+/// Deserializable into `&[T]` without copying if the input buffer itself
+/// is aligned to `align_of::<T>()`, and into `Vec<T>` regardless of alignment.
+/// Borrowing is available only on little-endian targets,
+/// as big-endian targets need to swap bytes of each element.
+///
+/// # Panics
+///
+/// Using zero-sized element type is a compile-time error.
+///
+/// # Examples
+///
+/// ```
+/// # use alkahest::*;
+///
+/// #[repr(C, align(8))]
+/// struct Buffer([u8; 64]);
+///
+/// let mut buffer = Buffer([0; 64]);
+/// let (size, root) = serialize::<(u8, Aligned<[f32]>), _>((1u8, &[1.0f32, 2.0][..]), &mut buffer.0).unwrap();
+///
+/// let (a, b) = deserialize_with_size::<(u8, Aligned<[f32]>), (u8, &[f32])>(&buffer.0[..size], root).unwrap();
+/// assert_eq!(a, 1);
+/// assert_eq!(b, [1.0, 2.0]);
+/// ```
+pub struct Aligned<F: ?Sized> {
+    marker: PhantomData<fn(&F) -> &F>,
+}
+
+impl<T> Formula for Aligned<[T]>
+where
+    T: SwapBytes,
+{
+    const MAX_STACK_SIZE: Option<usize> = Some(reference_size::<Bytes>());
+    const EXACT_SIZE: bool = true;
+    const HEAPLESS: bool = false;
+}
+
+impl<T> BareFormula for Aligned<[T]> where T: SwapBytes {}
+
+#[inline]
+fn write_aligned<T, B>(values: &[T], sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+where
+    T: SwapBytes,
+    B: Buffer,
+{
+    const { assert!(size_of::<T>() != 0, "Zero-sized elements are not supported") }
+
+    let old_stack = sizes.stack;
+    write_pod_slice(values, sizes, buffer.reborrow())?;
+
+    // Padding goes before elements in the heap.
+    let padding = sizes.heap.wrapping_neg() % align_of::<T>();
+    buffer.pad_stack(sizes.heap, sizes.stack, padding)?;
+    sizes.stack += padding;
+
+    let len = sizes.to_heap(old_stack);
+    buffer.move_to_heap(sizes.heap - len, sizes.stack + len, len);
+
+    FixedUsize::write(size_of_val(values), sizes, buffer.reborrow())?;
+    FixedUsize::write(sizes.heap, sizes, buffer)
+}
+
+#[cfg(target_endian = "little")]
+#[inline]
+fn read_aligned<'de, T>(de: Deserializer<'de>) -> Result<&'de [T], DeserializeError>
+where
+    T: SwapBytes,
+{
+    const { assert!(size_of::<T>() != 0, "Zero-sized elements are not supported") }
+
+    let bytes = de.deref_with::<Bytes, FixedUsize>()?.read_all_bytes();
+    match bytemuck::try_cast_slice(bytes) {
+        Ok(values) => Ok(values),
+        Err(bytemuck::PodCastError::TargetAlignmentGreaterAndInputNotAligned) => {
+            Err(DeserializeError::Misaligned)
+        }
+        Err(_) => Err(DeserializeError::WrongLength),
+    }
+}
+
+impl<T> SerializeRef<Aligned<[T]>> for [T]
+where
+    T: SwapBytes,
+{
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_aligned(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        // Padding depends on position in the buffer.
+        None
+    }
+}
+
+impl<T, const N: usize> SerializeRef<Aligned<[T]>> for [T; N]
+where
+    T: SwapBytes,
+{
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_aligned(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        None
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> SerializeRef<Aligned<[T]>> for Vec<T>
+where
+    T: SwapBytes,
+{
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_aligned(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        None
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> Serialize<Aligned<[T]>> for Vec<T>
+where
+    T: SwapBytes,
+{
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_aligned(&self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        None
+    }
+}
+
+/// Available only on little-endian targets,
+/// where in-memory representation matches the wire format.
+#[cfg(target_endian = "little")]
+impl<'de, 'fe: 'de, T> Deserialize<'fe, Aligned<[T]>> for &'de [T]
+where
+    T: SwapBytes,
+{
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'fe>) -> Result<Self, DeserializeError> {
+        read_aligned(de)
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'fe>) -> Result<(), DeserializeError> {
+        *self = read_aligned(de)?;
+        Ok(())
+    }
+}
+
+/// Copies elements, so the input does not have to be aligned.
+#[cfg(feature = "alloc")]
+impl<'de, T> Deserialize<'de, Aligned<[T]>> for Vec<T>
+where
+    T: SwapBytes,
+{
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        let mut vec = Vec::new();
+        <Self as Deserialize<'de, Aligned<[T]>>>::deserialize_in_place(&mut vec, de)?;
+        Ok(vec)
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        const { assert!(size_of::<T>() != 0, "Zero-sized elements are not supported") }
+
+        let bytes = de.deref_with::<Bytes, FixedUsize>()?.read_all_bytes();
+        self.clear();
+        crate::pod::extend_pod(self, bytes)
+    }
+}
@@ -0,0 +1,185 @@
+use core::marker::PhantomData;
+
+use crate::{
+    deserialize::{Deserialize, DeserializeError},
+    formula::Formula,
+    lazy::Lazy,
+    packet::packet_deserializer,
+    size::SIZE_STACK,
+};
+
+#[cfg(feature = "alloc")]
+use crate::{buffer::VecBuffer, packet::write_packet_into, serialize::Serialize};
+
+/// Magic bytes at the start of an archive.
+pub const ARCHIVE_MAGIC: [u8; 8] = *b"ALKAHEST";
+
+/// Version of the archive format.
+pub const ARCHIVE_VERSION: u16 = 1;
+
+/// Size of the archive header in bytes.
+pub const ARCHIVE_HEADER_SIZE: usize = 16;
+
+/// Returns archive header for current configuration.
+const fn archive_header() -> [u8; ARCHIVE_HEADER_SIZE] {
+    let mut header = [0; ARCHIVE_HEADER_SIZE];
+    let mut i = 0;
+    while i < ARCHIVE_MAGIC.len() {
+        header[i] = ARCHIVE_MAGIC[i];
+        i += 1;
+    }
+    let version = ARCHIVE_VERSION.to_le_bytes();
+    header[8] = version[0];
+    header[9] = version[1];
+    #[allow(clippy::cast_possible_truncation)]
+    {
+        header[10] = SIZE_STACK as u8;
+    }
+    header
+}
+
+/// Serialized value stored with archive header.
+///
+/// Archive is a byte container, e.g. a memory-mapped file,
+/// that starts with a header followed by a packet with the root value.
+///
+/// # Format
+///
+/// | Offset | Size | Content                                              |
+/// |-------:|-----:|:-----------------------------------------------------|
+/// | 0      | 8    | Magic bytes `ALKAHEST`                               |
+/// | 8      | 2    | Format version, little endian. Currently `1`         |
+/// | 10     | 1    | Size of `FixedUsize` in bytes                        |
+/// | 11     | 5    | Reserved, zeros                                      |
+/// | 16     | ..   | Packet with the root value, see [`write_packet`]     |
+///
+/// Packet starts with the reference to the root value.
+/// The first `FixedUsize` of the reference is the size of the packet.
+/// So the root value is found by reading only the header and the reference,
+/// and [`Archive::lazy`] touches only bytes of the fields that are accessed.
+///
+/// Archives larger than 4 GiB require `fixed64` feature.
+/// Archive written with different `FixedUsize` size is rejected.
+///
+/// [`write_packet`]: crate::write_packet
+pub struct Archive<B, F: ?Sized> {
+    bytes: B,
+    size: usize,
+    marker: PhantomData<fn(&F) -> &F>,
+}
+
+impl<B, F> Archive<B, F>
+where
+    B: AsRef<[u8]>,
+    F: Formula + ?Sized,
+{
+    /// Opens archive stored in the bytes.
+    /// Validates the header and bounds of the root packet.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError::Incompatible` if header does not match
+    /// this format version and `FixedUsize` size.
+    /// Returns `DeserializeError::OutOfBounds` if bytes are too short.
+    pub fn new(bytes: B) -> Result<Self, DeserializeError> {
+        let input = bytes.as_ref();
+        if input.len() < ARCHIVE_HEADER_SIZE {
+            return Err(DeserializeError::OutOfBounds);
+        }
+        if input[..ARCHIVE_HEADER_SIZE] != archive_header() {
+            return Err(DeserializeError::Incompatible);
+        }
+
+        let (_, size) = packet_deserializer::<F>(&input[ARCHIVE_HEADER_SIZE..])?;
+
+        Ok(Archive {
+            bytes,
+            size,
+            marker: PhantomData,
+        })
+    }
+
+    /// Returns lazy root value of the archive.
+    #[must_use]
+    #[inline]
+    pub fn lazy(&self) -> Lazy<'_, F> {
+        let input = &self.bytes.as_ref()[ARCHIVE_HEADER_SIZE..][..self.size];
+        match packet_deserializer::<F>(input) {
+            Ok((de, _)) => Lazy::new(de),
+            Err(_) => unreachable!("Packet is validated"),
+        }
+    }
+
+    /// Deserializes root value of the archive.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if deserialization fails.
+    #[inline]
+    pub fn get<'a, T>(&'a self) -> Result<T, DeserializeError>
+    where
+        T: Deserialize<'a, F>,
+    {
+        self.lazy().get()
+    }
+
+    /// Returns size of the archive in bytes, including header.
+    /// Bytes after the archive are ignored.
+    #[must_use]
+    #[inline]
+    pub fn size(&self) -> usize {
+        ARCHIVE_HEADER_SIZE + self.size
+    }
+
+    /// Returns reference to the underlying bytes.
+    #[inline]
+    pub fn get_ref(&self) -> &B {
+        &self.bytes
+    }
+
+    /// Returns the underlying bytes.
+    #[inline]
+    pub fn into_inner(self) -> B {
+        self.bytes
+    }
+}
+
+/// Writes archive with the value into byte vector.
+/// Returns the number of bytes written.
+///
+/// Grows the vector if needed.
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn write_archive_to_vec<F, T>(value: T, output: &mut alloc::vec::Vec<u8>) -> usize
+where
+    F: Formula + ?Sized,
+    T: Serialize<F>,
+{
+    output.clear();
+    output.extend_from_slice(&archive_header());
+    let size = match write_packet_into::<F, T, _>(value, VecBuffer::append(output)) {
+        Ok(size) => size,
+        Err(never) => match never {},
+    };
+    output.truncate(ARCHIVE_HEADER_SIZE + size);
+    ARCHIVE_HEADER_SIZE + size
+}
+
+/// Writes archive with the value into [`std::io::Write`].
+/// Returns the number of bytes written.
+///
+/// # Errors
+///
+/// Returns error if writer fails.
+#[cfg(feature = "std")]
+#[inline]
+pub fn write_archive<F, T, W>(value: T, writer: &mut W) -> std::io::Result<usize>
+where
+    F: Formula + ?Sized,
+    T: Serialize<F>,
+    W: std::io::Write + ?Sized,
+{
+    writer.write_all(&archive_header())?;
+    let size = crate::packet::write_packet_to_writer::<F, T, W>(value, writer)?;
+    Ok(ARCHIVE_HEADER_SIZE + size)
+}
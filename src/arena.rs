@@ -0,0 +1,208 @@
+use bumpalo::{
+    collections::{String, Vec},
+    Bump,
+};
+
+use crate::{
+    bytes::Bytes,
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::Formula,
+    iter::deserialize_extend_iter,
+    packet::{packet_deserializer, PacketFormula},
+};
+
+#[inline(always)]
+fn expect_arena<'de>(de: &Deserializer<'de>) -> Result<&'de Bump, DeserializeError> {
+    de.arena().ok_or(DeserializeError::MissingArena)
+}
+
+impl<'de, F, T> Deserialize<'de, [F]> for Vec<'de, T>
+where
+    F: Formula,
+    T: Deserialize<'de, F>,
+{
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        let arena = expect_arena(&de)?;
+        let iter = de.into_unsized_iter();
+        let (lower, _) = Iterator::size_hint(&iter);
+        let mut vec = Vec::with_capacity_in(lower, arena);
+        deserialize_extend_iter(&mut vec, iter)?;
+        Ok(vec)
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        self.clear();
+        let iter = de.into_unsized_iter();
+        let (lower, _) = Iterator::size_hint(&iter);
+        self.reserve(lower);
+        deserialize_extend_iter(self, iter)
+    }
+}
+
+impl<'de, F, T, const N: usize> Deserialize<'de, [F; N]> for Vec<'de, T>
+where
+    F: Formula,
+    T: Deserialize<'de, F>,
+{
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        let mut vec = Vec::with_capacity_in(N, expect_arena(&de)?);
+        deserialize_extend_iter(&mut vec, de.into_unsized_array_iter(N))?;
+        Ok(vec)
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        self.clear();
+        self.reserve(N);
+        deserialize_extend_iter(self, de.into_unsized_array_iter(N))
+    }
+}
+
+impl<'de> Deserialize<'de, Bytes> for Vec<'de, u8> {
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        let mut vec = Vec::new_in(expect_arena(&de)?);
+        vec.extend_from_slice(de.read_all_bytes());
+        Ok(vec)
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        self.clear();
+        self.extend_from_slice(de.read_all_bytes());
+        Ok(())
+    }
+}
+
+impl<'de> Deserialize<'de, str> for String<'de> {
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        let arena = expect_arena(&de)?;
+        let string = <&str as Deserialize<'de, str>>::deserialize(de)?;
+        Ok(String::from_str_in(string, arena))
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        let string = <&str as Deserialize<'de, str>>::deserialize(de)?;
+        self.clear();
+        self.push_str(string);
+        Ok(())
+    }
+}
+
+/// Deserializes value from the input using the arena.
+/// The value must occupy the whole input slice.
+/// The value must be either sized or heap-less.
+///
+/// Input is copied into the arena first,
+/// so borrowed values like `&'a str` and `&'a [u8]` point into the arena
+/// and outlive the input.
+/// Collections from [`bumpalo::collections`] allocate in the arena.
+///
+/// Whole input is copied on every call, even if the value
+/// borrows nothing from it or skips most of it,
+/// and the copy stays in the arena until it is reset.
+/// So each call takes input length plus the size of allocated collections
+/// from the arena.
+/// If the input outlives the arena, use [`deserialize_with_arena`]
+/// that does not copy it.
+///
+/// # Errors
+///
+/// Returns `DeserializeError` if deserialization fails.
+#[inline]
+pub fn deserialize_in_arena<'a, F, T>(arena: &'a Bump, input: &[u8]) -> Result<T, DeserializeError>
+where
+    F: Formula + ?Sized,
+    T: Deserialize<'a, F>,
+{
+    deserialize_with_arena::<F, T>(arena, arena.alloc_slice_copy(input))
+}
+
+/// Deserializes value from the input using the arena.
+/// The value must occupy the whole input slice.
+/// The value must be either sized or heap-less.
+///
+/// Input is not copied, borrowed values like `&'a str` and `&'a [u8]`
+/// point into the input, so it must live as long as the arena borrow.
+/// Collections from [`bumpalo::collections`] allocate in the arena.
+///
+/// # Errors
+///
+/// Returns `DeserializeError` if deserialization fails.
+#[inline]
+pub fn deserialize_with_arena<'a, F, T>(
+    arena: &'a Bump,
+    input: &'a [u8],
+) -> Result<T, DeserializeError>
+where
+    F: Formula + ?Sized,
+    T: Deserialize<'a, F>,
+{
+    let stack = match F::MAX_STACK_SIZE {
+        None => input.len(),
+        Some(max_stack) => max_stack.min(input.len()),
+    };
+
+    let de = Deserializer::new_unchecked(stack, input).with_arena(arena);
+    <T as Deserialize<'a, F>>::deserialize(de)
+}
+
+/// Reads packet with value from the input using the arena.
+/// Returns deserialized value and number of bytes consumed.
+///
+/// Packet is copied into the arena first,
+/// so borrowed values like `&'a str` and `&'a [u8]` point into the arena
+/// and outlive the input.
+/// Collections from [`bumpalo::collections`] allocate in the arena.
+///
+/// Whole packet is copied on every call, even if the value
+/// borrows nothing from it, and the copy stays in the arena until it is reset.
+/// Bytes after the packet are not copied.
+/// If the input outlives the arena, use [`read_packet_with_arena`]
+/// that does not copy it.
+///
+/// # Errors
+///
+/// Returns `DeserializeError` if deserialization fails.
+#[inline]
+pub fn read_packet_in_arena<'a, F, T>(
+    arena: &'a Bump,
+    input: &[u8],
+) -> Result<(T, usize), DeserializeError>
+where
+    F: PacketFormula + ?Sized,
+    T: Deserialize<'a, F::Formula>,
+{
+    let (_, address) = packet_deserializer::<F>(input)?;
+    read_packet_with_arena::<F, T>(arena, arena.alloc_slice_copy(&input[..address]))
+}
+
+/// Reads packet with value from the input using the arena.
+/// Returns deserialized value and number of bytes consumed.
+///
+/// Packet is not copied, borrowed values like `&'a str` and `&'a [u8]`
+/// point into the input, so it must live as long as the arena borrow.
+/// Collections from [`bumpalo::collections`] allocate in the arena.
+///
+/// # Errors
+///
+/// Returns `DeserializeError` if deserialization fails.
+#[inline]
+pub fn read_packet_with_arena<'a, F, T>(
+    arena: &'a Bump,
+    input: &'a [u8],
+) -> Result<(T, usize), DeserializeError>
+where
+    F: PacketFormula + ?Sized,
+    T: Deserialize<'a, F::Formula>,
+{
+    let (de, address) = packet_deserializer::<F>(input)?;
+    let value = <T as Deserialize<'a, F::Formula>>::deserialize(de.with_arena(arena))?;
+
+    Ok((value, address))
+}
@@ -133,11 +133,11 @@ where
 
 /// Returns the size of the serialized data if it can be determined fast.
 #[inline(always)]
-pub fn ref_array_fast_sizes<'a, F, I, T: 'a>(iter: I) -> Option<Sizes>
+pub fn ref_array_fast_sizes<'a, F, I, T>(iter: I) -> Option<Sizes>
 where
     F: Formula + ?Sized,
     I: Iterator<Item = &'a T>,
-    T: Serialize<F>,
+    T: Serialize<F> + 'a,
 {
     match (F::HEAPLESS, F::MAX_STACK_SIZE) {
         (true, Some(0)) => Some(Sizes::ZERO),
@@ -0,0 +1,200 @@
+use core::iter::FusedIterator;
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+#[cfg(feature = "alloc")]
+use crate::{buffer::VecBuffer, packet::write_packet_into, serialize::Serialize};
+
+use crate::{
+    deserialize::DeserializeError,
+    packet::{header_size, read_packet_size, PacketFormula},
+};
+
+/// Batch of packets written one after another into single buffer.
+///
+/// Keeps track of frame boundaries.
+/// Cleared batch keeps allocated memory and can be reused.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, Default)]
+pub struct PacketBatch {
+    buffer: Vec<u8>,
+    ends: Vec<usize>,
+}
+
+#[cfg(feature = "alloc")]
+impl PacketBatch {
+    /// Creates new empty batch.
+    #[must_use]
+    #[inline]
+    pub const fn new() -> Self {
+        PacketBatch {
+            buffer: Vec::new(),
+            ends: Vec::new(),
+        }
+    }
+
+    /// Creates new empty batch with preallocated buffer
+    /// of `bytes` bytes and space for `packets` frames.
+    #[must_use]
+    #[inline]
+    pub fn with_capacity(bytes: usize, packets: usize) -> Self {
+        PacketBatch {
+            buffer: Vec::with_capacity(bytes),
+            ends: Vec::with_capacity(packets),
+        }
+    }
+
+    /// Writes packet with the value at the end of the batch.
+    /// Returns the number of bytes written.
+    #[inline]
+    pub fn push<F, T>(&mut self, value: T) -> usize
+    where
+        F: PacketFormula + ?Sized,
+        T: Serialize<F::Formula>,
+    {
+        let start = self.buffer.len();
+        let size = match write_packet_into::<F, T, _>(value, VecBuffer::append(&mut self.buffer)) {
+            Ok(size) => size,
+            Err(never) => match never {},
+        };
+        self.buffer.truncate(start + size);
+        self.ends.push(start + size);
+        size
+    }
+
+    /// Returns number of packets in the batch.
+    #[must_use]
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    /// Returns `true` if batch has no packets.
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+
+    /// Returns all packets of the batch as contiguous bytes.
+    #[must_use]
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    /// Returns packet at specified index.
+    #[must_use]
+    #[inline]
+    pub fn get(&self, idx: usize) -> Option<&[u8]> {
+        if idx < self.ends.len() {
+            Some(self.frame(idx))
+        } else {
+            None
+        }
+    }
+
+    /// Returns iterator over packets in the batch.
+    #[inline]
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &[u8]> + DoubleEndedIterator + '_ {
+        (0..self.ends.len()).map(|idx| self.frame(idx))
+    }
+
+    #[inline(always)]
+    fn frame(&self, idx: usize) -> &[u8] {
+        let start = match idx {
+            0 => 0,
+            _ => self.ends[idx - 1],
+        };
+        &self.buffer[start..self.ends[idx]]
+    }
+
+    /// Removes all packets from the batch.
+    /// Keeps allocated memory.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+        self.ends.clear();
+    }
+
+    /// Writes all packets to the writer and clears the batch.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if writer fails.
+    /// Batch is not cleared in this case.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn drain_to<W>(&mut self, writer: &mut W) -> std::io::Result<()>
+    where
+        W: std::io::Write + ?Sized,
+    {
+        writer.write_all(&self.buffer)?;
+        self.clear();
+        Ok(())
+    }
+}
+
+/// Iterator over packets in a byte slice.
+///
+/// Packets are recognized with [`read_packet_size`].
+/// Iteration stops before incomplete packet at the end of the input.
+/// It can be retrieved with [`PacketIter::remaining`].
+pub struct PacketIter<'a, F: ?Sized> {
+    input: &'a [u8],
+    failed: bool,
+    marker: core::marker::PhantomData<fn(&F) -> &F>,
+}
+
+impl<'a, F> PacketIter<'a, F>
+where
+    F: PacketFormula + ?Sized,
+{
+    /// Creates new iterator over packets in the input.
+    #[must_use]
+    #[inline]
+    pub const fn new(input: &'a [u8]) -> Self {
+        PacketIter {
+            input,
+            failed: false,
+            marker: core::marker::PhantomData,
+        }
+    }
+
+    /// Returns the part of the input that is not yet iterated over.
+    #[must_use]
+    #[inline]
+    pub const fn remaining(&self) -> &'a [u8] {
+        self.input
+    }
+}
+
+impl<'a, F> Iterator for PacketIter<'a, F>
+where
+    F: PacketFormula + ?Sized,
+{
+    type Item = Result<&'a [u8], DeserializeError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Result<&'a [u8], DeserializeError>> {
+        if self.failed {
+            return None;
+        }
+
+        let size = read_packet_size::<F>(self.input)?;
+        if size < const { header_size::<F>() } {
+            self.failed = true;
+            return Some(Err(DeserializeError::WrongAddress));
+        }
+        if size > self.input.len() {
+            return None;
+        }
+
+        let (packet, tail) = self.input.split_at(size);
+        self.input = tail;
+        Some(Ok(packet))
+    }
+}
+
+impl<F> FusedIterator for PacketIter<'_, F> where F: PacketFormula + ?Sized {}
@@ -48,11 +48,11 @@ where
         };
         let size: usize = size.into();
 
-        match buffer.reserve_heap(sizes.heap, sizes.stack, size) {
+        match buffer.reserve_heap_at(sizes.heap, sizes.stack, size) {
             Err(err) => return Err(err),
             Ok([]) => {} // Nothing to do.
             Ok(bytes) => {
-                let mut cursor = Cursor::new(&mut bytes[sizes.heap..]);
+                let mut cursor = Cursor::new(bytes);
                 if let Err(err) = bincode::Options::serialize_into(options, &mut cursor, &self) {
                     panic!("Bincode serialization error: {}", err);
                 };
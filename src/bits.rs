@@ -0,0 +1,563 @@
+use crate::{
+    buffer::Buffer,
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::{BareFormula, Formula},
+    serialize::{write_bytes, Serialize, SerializeRef, Sizes},
+};
+
+/// Returns number of bytes required to store `bits` bits.
+pub const fn bytes_for(bits: usize) -> usize {
+    assert!(bits <= 128, "`Bits` formula supports up to 128 bits");
+    bits.div_ceil(8)
+}
+
+/// Formula for `N` bits packed into `(N + 7) / 8` bytes.
+///
+/// Serializable from unsigned integers with value that fits `N` bits
+/// and from `[bool; N]` arrays, one bit per flag.
+/// Deserializable into the same types.
+///
+/// Several flags or narrow integers are packed together
+/// by using single `Bits<N>` field for all of them.
+/// Each `Bits<N>` field occupies whole bytes, adjacent fields
+/// of a struct or tuple formula are not packed into shared bytes.
+/// To pack adjacent `bool` fields, narrow integers, small enums
+/// and `Option` tags of a struct, use `#[alkahest(bits)]` attribute,
+/// see [`BitField`].
+///
+/// # Panics
+///
+/// Serialization panics if integer value does not fit `N` bits.
+/// This includes `Bits<N>` fields of derived formulas:
+/// serializing a struct panics if its field value does not fit,
+/// so values from untrusted sources should be checked beforehand.
+/// Using `N` larger than 128 is a compile-time error.
+///
+/// # Examples
+///
+/// ```
+/// # use alkahest::*;
+///
+/// let mut buffer = [0u8; 16];
+///
+/// let (size, root) = serialize::<Bits<10>, _>([true, false, true, true, false, false, false, false, false, true], &mut buffer).unwrap();
+/// assert_eq!(size, 2);
+/// assert_eq!(&buffer[..2], &[0b0000_1101, 0b10]);
+///
+/// let value = deserialize_with_size::<Bits<10>, u16>(&buffer[..size], root).unwrap();
+/// assert_eq!(value, 0b10_0000_1101);
+/// ```
+pub struct Bits<const N: usize>;
+
+impl<const N: usize> Formula for Bits<N> {
+    const MAX_STACK_SIZE: Option<usize> = Some(bytes_for(N));
+    const EXACT_SIZE: bool = true;
+    const HEAPLESS: bool = true;
+}
+
+impl<const N: usize> BareFormula for Bits<N> {}
+
+#[inline(always)]
+fn write_bits<const N: usize, B>(value: u128, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+where
+    B: Buffer,
+{
+    if N < 128 && value >> N != 0 {
+        panic!("Value {} does not fit {} bits", value, N);
+    }
+    write_bytes(&value.to_le_bytes()[..bytes_for(N)], sizes, buffer)
+}
+
+#[inline(always)]
+fn read_bits<const N: usize>(mut de: Deserializer) -> Result<u128, DeserializeError> {
+    let mut bytes = [0; 16];
+    bytes[..bytes_for(N)].copy_from_slice(de.read_bytes(bytes_for(N))?);
+    let value = u128::from_le_bytes(bytes);
+    if N < 128 && value >> N != 0 {
+        return Err(DeserializeError::Incompatible);
+    }
+    Ok(value)
+}
+
+#[inline(always)]
+fn pack_flags(flags: &[bool]) -> u128 {
+    flags
+        .iter()
+        .enumerate()
+        .fold(0, |acc, (idx, &flag)| acc | (u128::from(flag) << idx))
+}
+
+macro_rules! impl_bits_int {
+    ($($ty:ident)*) => {
+        $(
+            impl<const N: usize> Serialize<Bits<N>> for $ty {
+                #[inline(always)]
+                fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+                where
+                    B: Buffer,
+                {
+                    write_bits::<N, B>(u128::from(self), sizes, buffer)
+                }
+
+                #[inline(always)]
+                fn size_hint(&self) -> Option<Sizes> {
+                    Some(Sizes::with_stack(bytes_for(N)))
+                }
+            }
+
+            impl<const N: usize> SerializeRef<Bits<N>> for $ty {
+                #[inline(always)]
+                fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+                where
+                    B: Buffer,
+                {
+                    write_bits::<N, B>(u128::from(*self), sizes, buffer)
+                }
+
+                #[inline(always)]
+                fn size_hint(&self) -> Option<Sizes> {
+                    Some(Sizes::with_stack(bytes_for(N)))
+                }
+            }
+
+            impl<const N: usize> Deserialize<'_, Bits<N>> for $ty {
+                #[inline(always)]
+                fn deserialize(de: Deserializer) -> Result<Self, DeserializeError> {
+                    let value = read_bits::<N>(de)?;
+                    $ty::try_from(value).map_err(|_| DeserializeError::IntegerOverflow)
+                }
+
+                #[inline(always)]
+                fn deserialize_in_place(&mut self, de: Deserializer) -> Result<(), DeserializeError> {
+                    *self = <$ty as Deserialize<'_, Bits<N>>>::deserialize(de)?;
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+impl_bits_int!(u8 u16 u32 u64 u128);
+
+impl<const N: usize> Serialize<Bits<N>> for [bool; N] {
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_bits::<N, B>(pack_flags(&self), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(bytes_for(N)))
+    }
+}
+
+impl<const N: usize> SerializeRef<Bits<N>> for [bool; N] {
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_bits::<N, B>(pack_flags(self), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(bytes_for(N)))
+    }
+}
+
+impl<const N: usize> Deserialize<'_, Bits<N>> for [bool; N] {
+    #[inline(always)]
+    fn deserialize(de: Deserializer) -> Result<Self, DeserializeError> {
+        let value = read_bits::<N>(de)?;
+        Ok(core::array::from_fn(|idx| value >> idx & 1 == 1))
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer) -> Result<(), DeserializeError> {
+        *self = <[bool; N] as Deserialize<'_, Bits<N>>>::deserialize(de)?;
+        Ok(())
+    }
+}
+
+/// Value that occupies fixed number of bits
+/// in types with `#[alkahest(bits)]` attribute.
+///
+/// Implemented for `bool`, unsigned integers, `Option` of bit fields
+/// and types with `#[alkahest(bits)]` attribute.
+///
+/// Type with `#[alkahest(bits)]` attribute is a formula for itself.
+/// Its fields are packed one after another starting from the lowest bit,
+/// and the value occupies `(BITS + 7) / 8` bytes.
+/// Enum stores variant index in as few bits as needed for all variants,
+/// followed by the fields of the variant.
+/// `Option` takes one bit for the tag followed by the value bits.
+/// Integer fields occupy all their bits unless narrowed
+/// with `#[alkahest(bits = N)]` field attribute.
+///
+/// Up to 128 bits are supported.
+///
+/// # Panics
+///
+/// Serialization panics if value of narrowed field does not fit `N` bits.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "derive")]
+/// # fn main() {
+/// use alkahest::*;
+///
+/// #[alkahest(bits)]
+/// #[derive(Debug, PartialEq)]
+/// enum Direction {
+///     Up,
+///     Down,
+///     Left,
+///     Right,
+/// }
+///
+/// #[alkahest(bits)]
+/// #[derive(Debug, PartialEq)]
+/// struct Input {
+///     jump: bool,
+///     crouch: bool,
+///     #[alkahest(bits = 3)]
+///     weapon: u8,
+///     direction: Option<Direction>,
+/// }
+///
+/// assert_eq!(<Input as BitField>::BITS, 8);
+///
+/// let input = Input {
+///     jump: true,
+///     crouch: false,
+///     weapon: 5,
+///     direction: Some(Direction::Left),
+/// };
+///
+/// let mut buffer = [0u8; 16];
+/// let (size, root) = serialize::<Input, _>(&input, &mut buffer).unwrap();
+/// assert_eq!(size, 1);
+/// assert_eq!(buffer[0], 0b10_1_101_0_1);
+///
+/// let value = deserialize_with_size::<Input, Input>(&buffer[..size], root).unwrap();
+/// assert_eq!(value, input);
+/// # }
+/// # #[cfg(not(feature = "derive"))]
+/// # fn main() {}
+/// ```
+pub trait BitField: Sized {
+    /// Number of bits the value occupies.
+    const BITS: usize;
+
+    /// Returns value bits.
+    /// Only lower `BITS` bits may be set.
+    fn to_bits(&self) -> u128;
+
+    /// Restores value from lower `BITS` bits.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if bits do not represent a valid value.
+    fn from_bits(bits: u128) -> Result<Self, DeserializeError>;
+}
+
+impl BitField for bool {
+    const BITS: usize = 1;
+
+    #[inline(always)]
+    fn to_bits(&self) -> u128 {
+        u128::from(*self)
+    }
+
+    #[inline(always)]
+    fn from_bits(bits: u128) -> Result<Self, DeserializeError> {
+        Ok(bits != 0)
+    }
+}
+
+macro_rules! impl_bit_field_int {
+    ($($ty:ident)*) => {
+        $(
+            impl BitField for $ty {
+                const BITS: usize = $ty::BITS as usize;
+
+                #[inline(always)]
+                fn to_bits(&self) -> u128 {
+                    u128::from(*self)
+                }
+
+                #[inline(always)]
+                fn from_bits(bits: u128) -> Result<Self, DeserializeError> {
+                    $ty::try_from(bits).map_err(|_| DeserializeError::IntegerOverflow)
+                }
+            }
+        )*
+    };
+}
+
+impl_bit_field_int!(u8 u16 u32 u64 u128);
+
+impl<T> BitField for Option<T>
+where
+    T: BitField,
+{
+    const BITS: usize = 1 + T::BITS;
+
+    #[inline(always)]
+    fn to_bits(&self) -> u128 {
+        match self {
+            None => 0,
+            Some(value) => 1 | value.to_bits() << 1,
+        }
+    }
+
+    #[inline(always)]
+    fn from_bits(bits: u128) -> Result<Self, DeserializeError> {
+        match bits & 1 {
+            0 if bits != 0 => Err(DeserializeError::Incompatible),
+            0 => Ok(None),
+            _ => Ok(Some(T::from_bits(bits >> 1)?)),
+        }
+    }
+}
+
+/// Places `value` of `width` bits at `shift` bit.
+///
+/// # Panics
+///
+/// Panics if value does not fit `width` bits.
+#[must_use]
+#[inline(always)]
+pub fn pack_bits(value: u128, shift: usize, width: usize) -> u128 {
+    if width < 128 && value >> width != 0 {
+        panic!("Value {} does not fit {} bits", value, width);
+    }
+    if width == 0 {
+        0
+    } else {
+        value << shift
+    }
+}
+
+/// Returns `width` bits starting at `shift` bit.
+#[must_use]
+#[inline(always)]
+pub fn unpack_bits(bits: u128, shift: usize, width: usize) -> u128 {
+    match width {
+        0 => 0,
+        128 => bits,
+        _ => bits >> shift & ((1 << width) - 1),
+    }
+}
+
+/// Checks that bits starting at `used` bit are unset.
+///
+/// # Errors
+///
+/// Returns `DeserializeError::Incompatible` if any of them is set.
+#[inline(always)]
+pub fn check_unused_bits(bits: u128, used: usize) -> Result<(), DeserializeError> {
+    if used < 128 && bits >> used != 0 {
+        return Err(DeserializeError::Incompatible);
+    }
+    Ok(())
+}
+
+/// Writes bit field into `(T::BITS + 7) / 8` bytes.
+///
+/// # Errors
+///
+/// Returns error if buffer write fails.
+#[inline(always)]
+pub fn write_bit_field<T, B>(value: &T, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+where
+    T: BitField,
+    B: Buffer,
+{
+    let bits = value.to_bits();
+    debug_assert!(check_unused_bits(bits, T::BITS).is_ok());
+    write_bytes(&bits.to_le_bytes()[..bytes_for(T::BITS)], sizes, buffer)
+}
+
+/// Reads bit field from `(T::BITS + 7) / 8` bytes.
+///
+/// # Errors
+///
+/// Returns error if bits above `T::BITS` are set
+/// or bits do not represent a valid value.
+#[inline(always)]
+pub fn read_bit_field<T>(mut de: Deserializer) -> Result<T, DeserializeError>
+where
+    T: BitField,
+{
+    let mut bytes = [0; 16];
+    bytes[..bytes_for(T::BITS)].copy_from_slice(de.read_bytes(bytes_for(T::BITS))?);
+    let bits = u128::from_le_bytes(bytes);
+    check_unused_bits(bits, T::BITS)?;
+    T::from_bits(bits)
+}
+
+/// Formula for sequences of flags packed into bits.
+///
+/// Eight flags occupy single byte.
+/// One more byte stores number of unused bits in the last byte.
+///
+/// Serializable from slices, arrays and vectors of `bool`.
+/// Deserializable into `Vec<bool>`.
+///
+/// # Examples
+///
+/// ```
+/// # use alkahest::*;
+///
+/// let flags = [true; 20];
+/// let mut buffer = [0u8; 16];
+///
+/// let (size, root) = serialize::<BitSet, _>(&flags[..], &mut buffer).unwrap();
+/// assert_eq!(size, 4);
+///
+/// let value = deserialize_with_size::<BitSet, Vec<bool>>(&buffer[..size], root).unwrap();
+/// assert_eq!(value, flags);
+/// ```
+pub struct BitSet;
+
+impl Formula for BitSet {
+    const MAX_STACK_SIZE: Option<usize> = None;
+    const EXACT_SIZE: bool = false;
+    const HEAPLESS: bool = true;
+}
+
+impl BareFormula for BitSet {}
+
+#[inline(always)]
+fn bitset_size(flags: &[bool]) -> Sizes {
+    Sizes::with_stack(1 + flags.len().div_ceil(8))
+}
+
+#[inline]
+fn write_bitset<B>(flags: &[bool], sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+where
+    B: Buffer,
+{
+    #[allow(clippy::cast_possible_truncation)]
+    let unused = ((8 - flags.len() % 8) % 8) as u8;
+    write_bytes(&[unused], sizes, buffer.reborrow())?;
+
+    for chunk in flags.chunks(8) {
+        #[allow(clippy::cast_possible_truncation)]
+        let byte = pack_flags(chunk) as u8;
+        write_bytes(&[byte], sizes, buffer.reborrow())?;
+    }
+    Ok(())
+}
+
+impl SerializeRef<BitSet> for [bool] {
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_bitset(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(bitset_size(self))
+    }
+}
+
+impl<const N: usize> Serialize<BitSet> for [bool; N] {
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_bitset(&self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(bitset_size(self))
+    }
+}
+
+impl<const N: usize> SerializeRef<BitSet> for [bool; N] {
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_bitset(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(bitset_size(self))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Serialize<BitSet> for alloc::vec::Vec<bool> {
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_bitset(&self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(bitset_size(self))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl SerializeRef<BitSet> for alloc::vec::Vec<bool> {
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_bitset(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(bitset_size(self))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Deserialize<'_, BitSet> for alloc::vec::Vec<bool> {
+    #[inline]
+    fn deserialize(de: Deserializer) -> Result<Self, DeserializeError> {
+        let mut flags = alloc::vec::Vec::new();
+        <Self as Deserialize<'_, BitSet>>::deserialize_in_place(&mut flags, de)?;
+        Ok(flags)
+    }
+
+    #[inline]
+    fn deserialize_in_place(&mut self, mut de: Deserializer) -> Result<(), DeserializeError> {
+        let unused = usize::from(de.read_byte()?);
+        let len = de.stack();
+
+        if unused >= 8 || (len == 0 && unused != 0) {
+            return Err(DeserializeError::WrongLength);
+        }
+
+        self.clear();
+        self.reserve(len * 8 - unused);
+        while de.stack() > 0 {
+            let byte = de.read_byte()?;
+            self.extend((0..8).map(|bit| byte >> bit & 1 == 1));
+        }
+        self.truncate(len * 8 - unused);
+        Ok(())
+    }
+}
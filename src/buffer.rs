@@ -37,6 +37,13 @@ pub trait Buffer {
 
     /// Reserves heap space and returns a buffer over it.
     /// Returned buffer is always of `FixedBuffer` type.
+    /// It ends at the end of reserved space,
+    /// so reserved bytes are the last `len` bytes of it.
+    ///
+    /// Buffers that keep the whole heap in memory return slice
+    /// that starts at heap offset 0.
+    /// Buffers that do not may return slice that starts
+    /// at any offset up to `heap`.
     ///
     /// If buffer cannot reserve heap space, it may return either
     /// `Err` or `Ok([])`.
@@ -54,6 +61,87 @@ pub trait Buffer {
         stack: usize,
         len: usize,
     ) -> Result<&mut [u8], Self::Error>;
+
+    /// Reserves heap space and returns a slice over reserved bytes only.
+    /// Returned slice covers exactly `len` bytes of the heap
+    /// starting at `heap` offset.
+    ///
+    /// Buffers that do not keep the whole heap in memory,
+    /// like ones that pass written heap to `std::io::Write`,
+    /// implement this method to avoid copying,
+    /// while [`Buffer::reserve_heap`] falls back to a slower path for them.
+    /// Default implementation calls [`Buffer::reserve_heap`]
+    /// and skips bytes before reserved space.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Buffer::reserve_heap`].
+    #[inline(always)]
+    fn reserve_heap_at(
+        &mut self,
+        heap: usize,
+        stack: usize,
+        len: usize,
+    ) -> Result<&mut [u8], Self::Error> {
+        match self.reserve_heap(heap, stack, len)? {
+            [] => Ok(&mut []),
+            reserved => {
+                let at = reserved.len() - len;
+                Ok(&mut reserved[at..])
+            }
+        }
+    }
+
+    /// Returns `true` if value of `len` bytes should be written
+    /// directly into reserved heap space when its size is known.
+    /// Otherwise value is written to the stack and then moved to the heap.
+    ///
+    /// Value is written with [`Buffer::reserve_heap`],
+    /// so streaming buffers return `false` for large values.
+    #[inline(always)]
+    fn prefer_reserve(&self, len: usize) -> bool {
+        let _ = len;
+        true
+    }
+
+    /// Called when size or address does not fit [`Width`](crate::Width)
+    /// of the reference being written.
+    ///
+    /// Buffers that do not keep written bytes, like ones used to
+    /// measure serialized size, return `Ok` to continue serialization.
+    ///
+    /// # Errors
+    ///
+    /// Fallible buffers should return `Err`.
+    ///
+    /// # Panics
+    ///
+    /// Default implementation panics, since infallible buffers
+    /// cannot report the error otherwise.
+    #[cold]
+    #[inline(never)]
+    fn width_overflow(&mut self) -> Result<(), Self::Error> {
+        panic!("Size or address does not fit reference width")
+    }
+
+    /// Writes shared bytes to the stack.
+    ///
+    /// Buffers may keep the reference to the bytes instead of copying them.
+    /// By default bytes are written with [`Buffer::write_stack`].
+    ///
+    /// # Errors
+    ///
+    /// If buffer cannot write bytes, it should return `Err`.
+    #[cfg(feature = "bytes")]
+    #[inline(always)]
+    fn write_shared(
+        &mut self,
+        heap: usize,
+        stack: usize,
+        bytes: &::bytes::Bytes,
+    ) -> Result<(), Self::Error> {
+        self.write_stack(heap, stack, bytes)
+    }
 }
 
 /// No-op buffer that does not write anything.
@@ -97,6 +185,11 @@ impl Buffer for DryBuffer {
     ) -> Result<&mut [u8], Infallible> {
         Ok(&mut [])
     }
+
+    #[inline(always)]
+    fn width_overflow(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
 }
 
 /// Error that may occur during serialization,
@@ -191,6 +284,92 @@ impl<'a> Buffer for CheckedFixedBuffer<'a> {
         let end = heap + len;
         Ok(&mut self.buf[..end])
     }
+
+    /// Addresses that do not fit reference width
+    /// are past addressable part of the buffer.
+    #[inline(always)]
+    fn width_overflow(&mut self) -> Result<(), BufferExhausted> {
+        Err(BufferExhausted)
+    }
+}
+
+/// Error of [`ReservedBuffer`].
+pub(crate) enum ReservedError {
+    /// Value does not fit reserved space.
+    /// Means that size hint of the value is wrong.
+    Exhausted,
+
+    /// Size or address does not fit reference width.
+    WidthOverflow,
+}
+
+/// Buffer over heap reserved for a value with known size.
+/// Reports width overflow separately from exhaustion,
+/// so that broken size hints are not mistaken for it.
+pub(crate) struct ReservedBuffer<'a> {
+    buf: &'a mut [u8],
+
+    /// Heap offset of the first byte in `buf`.
+    base: usize,
+}
+
+impl<'a> ReservedBuffer<'a> {
+    #[inline(always)]
+    pub(crate) fn new(buf: &'a mut [u8], base: usize) -> Self {
+        ReservedBuffer { buf, base }
+    }
+}
+
+impl<'a> Buffer for ReservedBuffer<'a> {
+    type Error = ReservedError;
+    type Reborrow<'b> = ReservedBuffer<'b> where 'a: 'b;
+
+    #[inline(always)]
+    fn reborrow(&mut self) -> Self::Reborrow<'_> {
+        ReservedBuffer {
+            buf: self.buf,
+            base: self.base,
+        }
+    }
+
+    #[inline(always)]
+    fn write_stack(&mut self, heap: usize, stack: usize, bytes: &[u8]) -> Result<(), ReservedError> {
+        CheckedFixedBuffer::new(self.buf)
+            .write_stack(heap - self.base, stack, bytes)
+            .map_err(|BufferExhausted| ReservedError::Exhausted)
+    }
+
+    #[inline(always)]
+    fn pad_stack(&mut self, heap: usize, stack: usize, len: usize) -> Result<(), ReservedError> {
+        CheckedFixedBuffer::new(self.buf)
+            .pad_stack(heap - self.base, stack, len)
+            .map_err(|BufferExhausted| ReservedError::Exhausted)
+    }
+
+    #[inline(always)]
+    fn move_to_heap(&mut self, heap: usize, stack: usize, len: usize) {
+        CheckedFixedBuffer::new(self.buf).move_to_heap(heap - self.base, stack, len);
+    }
+
+    #[inline(always)]
+    fn reserve_heap(
+        &mut self,
+        heap: usize,
+        stack: usize,
+        len: usize,
+    ) -> Result<&mut [u8], ReservedError> {
+        let heap = heap - self.base;
+        debug_assert!(heap + stack <= self.buf.len());
+        if self.buf.len() - heap - stack < len {
+            return Err(ReservedError::Exhausted);
+        }
+        Ok(&mut self.buf[..heap + len])
+    }
+
+    #[cold]
+    fn width_overflow(&mut self) -> Result<(), ReservedError> {
+        Err(ReservedError::WidthOverflow)
+    }
 }
 
 impl<'a> Buffer for &'a mut [u8] {
@@ -336,40 +515,143 @@ impl<'a> Buffer for MaybeFixedBuffer<'a> {
     }
 }
 
-/// Extensible buffer that writes to a vector.
-/// If buffer is too small to fit serialized data it extends the vector.
-/// Never returns an error, cannot fail to serialize data except for OOM error.
+/// Byte container that extensible buffers grow.
+///
+/// Stack is kept at the end of the container and heap at `offset`,
+/// so growing moves stack to the new end.
 #[cfg(feature = "alloc")]
-pub struct VecBuffer<'a> {
-    buf: &'a mut Vec<u8>,
+trait Growable: core::ops::DerefMut<Target = [u8]> {
+    /// Resizes container to `len` bytes, filling new bytes with zeros.
+    fn resize_zeroed(&mut self, len: usize);
 }
 
 #[cfg(feature = "alloc")]
-impl<'a> VecBuffer<'a> {
-    /// Creates a new buffer that writes to the given vector.
-    pub fn new(buf: &'a mut Vec<u8>) -> Self {
-        VecBuffer { buf }
+impl Growable for Vec<u8> {
+    #[inline(always)]
+    fn resize_zeroed(&mut self, len: usize) {
+        self.resize(len, 0);
     }
 }
 
+#[cfg(feature = "bytes")]
+impl Growable for ::bytes::BytesMut {
+    #[inline(always)]
+    fn resize_zeroed(&mut self, len: usize) {
+        self.resize(len, 0);
+    }
+}
+
+/// Shared implementation of [`Buffer`] methods
+/// for extensible buffers over [`Growable`] containers.
 #[cfg(feature = "alloc")]
-impl VecBuffer<'_> {
+mod grow {
+    use super::Growable;
+
     #[cold]
-    fn do_reserve(&mut self, heap: usize, stack: usize, additional: usize) {
-        let old_len = self.buf.len();
-        self.buf.resize(heap + stack + additional, 0);
-        let new_len = self.buf.len();
-        self.buf
-            .copy_within(old_len - stack..old_len, new_len - stack);
+    fn do_reserve<V>(buf: &mut V, offset: usize, heap: usize, stack: usize, additional: usize)
+    where
+        V: Growable,
+    {
+        let old_len = buf.len();
+        buf.resize_zeroed(offset + heap + stack + additional);
+        let new_len = buf.len();
+        buf.copy_within(old_len - stack..old_len, new_len - stack);
     }
+
     /// Ensures that at least `additional` bytes
     /// can be written between first `heap` and last `stack` bytes.
-    fn reserve(&mut self, heap: usize, stack: usize, additional: usize) {
-        let free = self.buf.len() - heap - stack;
+    #[inline(always)]
+    fn reserve<V>(buf: &mut V, offset: usize, heap: usize, stack: usize, additional: usize)
+    where
+        V: Growable,
+    {
+        debug_assert!(offset + heap + stack <= buf.len());
+        let free = buf.len() - offset - heap - stack;
         if free < additional {
-            self.do_reserve(heap, stack, additional);
+            do_reserve(buf, offset, heap, stack, additional);
+        }
+    }
+
+    #[inline(always)]
+    pub(super) fn write_stack<V>(
+        buf: &mut V,
+        offset: usize,
+        heap: usize,
+        stack: usize,
+        bytes: &[u8],
+    ) where
+        V: Growable,
+    {
+        reserve(buf, offset, heap, stack, bytes.len());
+        let at = buf.len() - stack - bytes.len();
+        buf[at..][..bytes.len()].copy_from_slice(bytes);
+    }
+
+    #[inline(always)]
+    pub(super) fn pad_stack<V>(buf: &mut V, offset: usize, heap: usize, stack: usize, len: usize)
+    where
+        V: Growable,
+    {
+        reserve(buf, offset, heap, stack, len);
+
+        #[cfg(test)]
+        {
+            let at = buf.len() - stack - len;
+            buf[at..][..len].fill(0);
         }
     }
+
+    #[inline(always)]
+    pub(super) fn move_to_heap<V>(buf: &mut V, offset: usize, heap: usize, stack: usize, len: usize)
+    where
+        V: Growable,
+    {
+        debug_assert!(offset + heap + stack <= buf.len());
+        debug_assert!(stack >= len);
+        let at = buf.len() - stack;
+        buf.copy_within(at..at + len, offset + heap);
+    }
+
+    #[inline(always)]
+    pub(super) fn reserve_heap<V>(
+        buf: &mut V,
+        offset: usize,
+        heap: usize,
+        stack: usize,
+        len: usize,
+    ) -> &mut [u8]
+    where
+        V: Growable,
+    {
+        reserve(buf, offset, heap, stack, len);
+        &mut buf[offset..][..heap + len]
+    }
+}
+
+/// Extensible buffer that writes to a vector.
+/// If buffer is too small to fit serialized data it extends the vector.
+/// Never returns an error, cannot fail to serialize data except for OOM error.
+#[cfg(feature = "alloc")]
+pub struct VecBuffer<'a> {
+    buf: &'a mut Vec<u8>,
+
+    /// Bytes of the vector before this offset are not touched.
+    offset: usize,
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> VecBuffer<'a> {
+    /// Creates a new buffer that writes to the given vector.
+    pub fn new(buf: &'a mut Vec<u8>) -> Self {
+        VecBuffer { buf, offset: 0 }
+    }
+
+    /// Creates a new buffer that writes to the given vector
+    /// after its current content.
+    pub(crate) fn append(buf: &'a mut Vec<u8>) -> Self {
+        let offset = buf.len();
+        VecBuffer { buf, offset }
+    }
 }
 
 #[cfg(feature = "alloc")]
@@ -379,37 +661,86 @@ impl<'a> Buffer for VecBuffer<'a> {
 
     #[inline(always)]
     fn reborrow(&mut self) -> Self::Reborrow<'_> {
-        VecBuffer { buf: self.buf }
+        VecBuffer {
+            buf: self.buf,
+            offset: self.offset,
+        }
     }
 
     #[inline(always)]
     fn write_stack(&mut self, heap: usize, stack: usize, bytes: &[u8]) -> Result<(), Infallible> {
-        debug_assert!(heap + stack <= self.buf.len());
-        self.reserve(heap, stack, bytes.len());
-        let at = self.buf.len() - stack - bytes.len();
-        self.buf[at..][..bytes.len()].copy_from_slice(bytes);
+        grow::write_stack(self.buf, self.offset, heap, stack, bytes);
         Ok(())
     }
 
     #[inline(always)]
     fn pad_stack(&mut self, heap: usize, stack: usize, len: usize) -> Result<(), Infallible> {
-        debug_assert!(heap + stack <= self.buf.len());
-        self.reserve(heap, stack, len);
+        grow::pad_stack(self.buf, self.offset, heap, stack, len);
+        Ok(())
+    }
 
-        #[cfg(test)]
-        {
-            let at = self.buf.len() - stack - len;
-            self.buf[at..][..len].fill(0);
-        }
+    #[inline(always)]
+    fn move_to_heap(&mut self, heap: usize, stack: usize, len: usize) {
+        grow::move_to_heap(self.buf, self.offset, heap, stack, len);
+    }
+
+    #[inline(always)]
+    fn reserve_heap(
+        &mut self,
+        heap: usize,
+        stack: usize,
+        len: usize,
+    ) -> Result<&mut [u8], Infallible> {
+        Ok(grow::reserve_heap(self.buf, self.offset, heap, stack, len))
+    }
+}
+
+/// Extensible buffer that writes to a [`BytesMut`](::bytes::BytesMut).
+/// If buffer is too small to fit serialized data it extends the `BytesMut`.
+/// Never returns an error, cannot fail to serialize data except for OOM error.
+///
+/// Grows the same way as [`VecBuffer`].
+/// Written bytes can be frozen into [`bytes::Bytes`](::bytes::Bytes)
+/// and read with [`read_packet_shared`](crate::read_packet_shared)
+/// to deserialize [`Bytes`](crate::Bytes) fields without copying.
+#[cfg(feature = "bytes")]
+pub struct BytesMutBuffer<'a> {
+    buf: &'a mut ::bytes::BytesMut,
+}
+
+#[cfg(feature = "bytes")]
+impl<'a> BytesMutBuffer<'a> {
+    /// Creates a new buffer that writes to the given `BytesMut`.
+    pub fn new(buf: &'a mut ::bytes::BytesMut) -> Self {
+        BytesMutBuffer { buf }
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl<'a> Buffer for BytesMutBuffer<'a> {
+    type Error = Infallible;
+    type Reborrow<'b> = BytesMutBuffer<'b> where 'a: 'b;
+
+    #[inline(always)]
+    fn reborrow(&mut self) -> Self::Reborrow<'_> {
+        BytesMutBuffer { buf: self.buf }
+    }
+
+    #[inline(always)]
+    fn write_stack(&mut self, heap: usize, stack: usize, bytes: &[u8]) -> Result<(), Infallible> {
+        grow::write_stack(self.buf, 0, heap, stack, bytes);
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn pad_stack(&mut self, heap: usize, stack: usize, len: usize) -> Result<(), Infallible> {
+        grow::pad_stack(self.buf, 0, heap, stack, len);
         Ok(())
     }
 
     #[inline(always)]
     fn move_to_heap(&mut self, heap: usize, stack: usize, len: usize) {
-        debug_assert!(heap + stack <= self.buf.len());
-        debug_assert!(stack >= len);
-        let at = self.buf.len() - stack;
-        self.buf.copy_within(at..at + len, heap);
+        grow::move_to_heap(self.buf, 0, heap, stack, len);
     }
 
     #[inline(always)]
@@ -419,8 +750,211 @@ impl<'a> Buffer for VecBuffer<'a> {
         stack: usize,
         len: usize,
     ) -> Result<&mut [u8], Infallible> {
-        debug_assert!(heap + stack <= self.buf.len());
-        self.reserve(heap, stack, len);
-        Ok(&mut self.buf[..heap + len])
+        Ok(grow::reserve_heap(self.buf, 0, heap, stack, len))
+    }
+}
+
+/// Size of written heap that is kept in memory before
+/// [`WriteBuffer`] passes it to the writer.
+/// Also values larger than this are not written
+/// into reserved heap space directly.
+#[cfg(feature = "std")]
+const WRITE_CHUNK: usize = 8192;
+
+/// Memory used by [`WriteBuffer`].
+///
+/// Heap bytes are append-only, so they are passed to the writer
+/// once enough of them is accumulated.
+/// Stack is kept in memory until it is moved to the heap.
+#[cfg(feature = "std")]
+pub(crate) struct WriteState {
+    /// Heap bytes that are not yet passed to the writer.
+    heap: Vec<u8>,
+
+    /// Stack bytes are placed at the end of this vector.
+    stack: Vec<u8>,
+
+    /// Number of heap bytes passed to the writer.
+    written: usize,
+}
+
+#[cfg(feature = "std")]
+impl WriteState {
+    /// Creates new state assuming that `written` bytes
+    /// are already passed to the writer.
+    pub(crate) fn new(written: usize) -> Self {
+        WriteState {
+            heap: Vec::new(),
+            stack: Vec::new(),
+            written,
+        }
+    }
+
+    /// Passes remaining heap bytes to the writer.
+    pub(crate) fn finish<W>(&mut self, writer: &mut W) -> std::io::Result<()>
+    where
+        W: std::io::Write + ?Sized,
+    {
+        writer.write_all(&self.heap)?;
+        self.written += self.heap.len();
+        self.heap.clear();
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn flush<W>(&mut self, writer: &mut W) -> std::io::Result<()>
+    where
+        W: std::io::Write + ?Sized,
+    {
+        if self.heap.len() >= WRITE_CHUNK {
+            self.finish(writer)?;
+        }
+        Ok(())
+    }
+
+    #[cold]
+    fn do_reserve_stack(&mut self, stack: usize, additional: usize) {
+        let old_len = self.stack.len();
+        let new_len = (stack + additional).max(old_len * 2);
+        self.stack.resize(new_len, 0);
+        self.stack
+            .copy_within(old_len - stack..old_len, new_len - stack);
+    }
+
+    /// Ensures that at least `additional` bytes
+    /// can be written before last `stack` bytes.
+    #[inline(always)]
+    fn reserve_stack(&mut self, stack: usize, additional: usize) {
+        if self.stack.len() - stack < additional {
+            self.do_reserve_stack(stack, additional);
+        }
+    }
+}
+
+/// Buffer that passes serialized data to [`std::io::Write`].
+///
+/// Heap is written to the writer in chunks as it grows.
+/// Only stack of the values being serialized is kept in memory.
+#[cfg(feature = "std")]
+pub(crate) struct WriteBuffer<'a, W: ?Sized> {
+    writer: &'a mut W,
+    state: &'a mut WriteState,
+}
+
+#[cfg(feature = "std")]
+impl<'a, W> WriteBuffer<'a, W>
+where
+    W: std::io::Write + ?Sized,
+{
+    /// Creates a new buffer that writes to the given writer.
+    pub(crate) fn new(writer: &'a mut W, state: &'a mut WriteState) -> Self {
+        WriteBuffer { writer, state }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, W> Buffer for WriteBuffer<'a, W>
+where
+    W: std::io::Write + ?Sized,
+{
+    type Error = std::io::Error;
+    type Reborrow<'b> = WriteBuffer<'b, W> where 'a: 'b;
+
+    #[inline(always)]
+    fn reborrow(&mut self) -> Self::Reborrow<'_> {
+        WriteBuffer {
+            writer: self.writer,
+            state: self.state,
+        }
+    }
+
+    #[inline(always)]
+    fn write_stack(
+        &mut self,
+        heap: usize,
+        stack: usize,
+        bytes: &[u8],
+    ) -> Result<(), std::io::Error> {
+        debug_assert_eq!(heap, self.state.written + self.state.heap.len());
+        self.state.flush(self.writer)?;
+        self.state.reserve_stack(stack, bytes.len());
+        let at = self.state.stack.len() - stack - bytes.len();
+        self.state.stack[at..][..bytes.len()].copy_from_slice(bytes);
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn pad_stack(&mut self, heap: usize, stack: usize, len: usize) -> Result<(), std::io::Error> {
+        debug_assert_eq!(heap, self.state.written + self.state.heap.len());
+        self.state.flush(self.writer)?;
+        self.state.reserve_stack(stack, len);
+        let at = self.state.stack.len() - stack - len;
+        self.state.stack[at..][..len].fill(0);
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn move_to_heap(&mut self, heap: usize, stack: usize, len: usize) {
+        debug_assert!(stack >= len);
+        debug_assert_eq!(heap, self.state.written + self.state.heap.len());
+        let start = self.state.stack.len() - stack;
+        self.state
+            .heap
+            .extend_from_slice(&self.state.stack[start..][..len]);
+    }
+
+    /// Heap passed to the writer is not kept in memory,
+    /// so returned slice starts at the first heap byte not yet written.
+    #[inline(always)]
+    fn reserve_heap(
+        &mut self,
+        heap: usize,
+        _stack: usize,
+        len: usize,
+    ) -> Result<&mut [u8], std::io::Error> {
+        self.state.flush(self.writer)?;
+        assert!(
+            heap >= self.state.written,
+            "Heap before offset {} is already written",
+            self.state.written
+        );
+        let at = heap - self.state.written;
+        if self.state.heap.len() < at + len {
+            self.state.heap.resize(at + len, 0);
+        }
+        Ok(&mut self.state.heap[..at + len])
+    }
+
+    #[inline(always)]
+    fn reserve_heap_at(
+        &mut self,
+        heap: usize,
+        _stack: usize,
+        len: usize,
+    ) -> Result<&mut [u8], std::io::Error> {
+        self.state.flush(self.writer)?;
+        assert!(
+            heap >= self.state.written,
+            "Heap before offset {} is already written",
+            self.state.written
+        );
+        let at = heap - self.state.written;
+        if self.state.heap.len() < at + len {
+            self.state.heap.resize(at + len, 0);
+        }
+        Ok(&mut self.state.heap[at..][..len])
+    }
+
+    #[inline(always)]
+    fn prefer_reserve(&self, len: usize) -> bool {
+        len <= WRITE_CHUNK
+    }
+
+    #[cold]
+    fn width_overflow(&mut self) -> Result<(), std::io::Error> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "size or address does not fit reference width",
+        ))
     }
 }
@@ -5,6 +5,9 @@ use crate::{
     serialize::{write_bytes, SerializeRef, Sizes},
 };
 
+#[cfg(feature = "bytes")]
+use crate::serialize::{write_shared_bytes, Serialize};
+
 /// A formula for a raw byte slices.
 /// Serializable from anything that implements `AsRef<[u8]>`.
 pub struct Bytes;
@@ -44,3 +47,75 @@ impl<'de, 'fe: 'de> Deserialize<'fe, Bytes> for &'de [u8] {
         Ok(())
     }
 }
+
+#[cfg(feature = "bytes")]
+impl Serialize<Bytes> for ::bytes::Bytes {
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_shared_bytes(&self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(self.len()))
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl SerializeRef<Bytes> for ::bytes::Bytes {
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_shared_bytes(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(self.len()))
+    }
+}
+
+/// Shares buffer of the deserializer if it has one,
+/// that is when read with [`read_packet_shared`](crate::read_packet_shared)
+/// or [`Deserializer::new_shared`].
+/// Otherwise copies bytes, e.g. with [`read_packet`](crate::read_packet)
+/// and [`deserialize`](crate::deserialize).
+#[cfg(feature = "bytes")]
+impl<'de> Deserialize<'de, Bytes> for ::bytes::Bytes {
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        Ok(match de.origin() {
+            Some(origin) => origin.slice_ref(de.read_all_bytes()),
+            None => ::bytes::Bytes::copy_from_slice(de.read_all_bytes()),
+        })
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        *self = <Self as Deserialize<'de, Bytes>>::deserialize(de)?;
+        Ok(())
+    }
+}
+
+/// `[u8]` formula places bytes in reverse order,
+/// so they are always copied,
+/// including with [`read_packet_shared`](crate::read_packet_shared).
+/// Use [`Bytes`] formula to share buffer of the deserializer.
+#[cfg(feature = "bytes")]
+impl<'de> Deserialize<'de, [u8]> for ::bytes::Bytes {
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        Ok(de.read_all_bytes().iter().rev().copied().collect())
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        *self = <Self as Deserialize<'de, [u8]>>::deserialize(de)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,520 @@
+use crate::{
+    buffer::{BufferExhausted, CheckedFixedBuffer},
+    deserialize::{Deserialize, DeserializeError},
+    packet::{read_packet, read_packet_size, write_packet_into, PacketFormula},
+    serialize::Serialize,
+};
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+#[cfg(feature = "alloc")]
+use crate::{
+    buffer::{Buffer, VecBuffer},
+    packet::header_size,
+};
+
+/// Size of the checksum that follows checked packet.
+pub const CHECKSUM_SIZE: usize = 4;
+
+/// Reversed CRC32C (Castagnoli) polynomial.
+const POLY: u32 = 0x82F6_3B78;
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        #[allow(clippy::cast_possible_truncation)]
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ POLY
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+static CRC_TABLE: [u32; 256] = crc_table();
+
+/// Multiplies two polynomials modulo CRC polynomial.
+/// Polynomials are bit-reversed, so `1 << 31` is `x^0`.
+#[cfg(feature = "alloc")]
+const fn mul_mod_poly(a: u32, mut b: u32) -> u32 {
+    let mut product = 0;
+    let mut bit = 0;
+    while bit < 32 {
+        if a & (1 << (31 - bit)) != 0 {
+            product ^= b;
+        }
+        b = if b & 1 == 1 { (b >> 1) ^ POLY } else { b >> 1 };
+        bit += 1;
+    }
+    product
+}
+
+#[cfg(feature = "alloc")]
+const fn x_pow_table() -> [u32; 64] {
+    let mut table = [0; 64];
+    let mut x = 1 << 30;
+    let mut i = 0;
+    while i < 64 {
+        table[i] = x;
+        x = mul_mod_poly(x, x);
+        i += 1;
+    }
+    table
+}
+
+/// `x^(2^n)` modulo CRC polynomial.
+#[cfg(feature = "alloc")]
+static X_POW_TABLE: [u32; 64] = x_pow_table();
+
+/// Returns `x^(8 * len)` modulo CRC polynomial,
+/// which shifts checksum over `len` bytes.
+#[cfg(feature = "alloc")]
+fn shift_bytes(len: usize) -> u32 {
+    let mut len = len as u64;
+    let mut shift = 1 << 31;
+    let mut n = 3;
+    while len != 0 {
+        if len & 1 == 1 {
+            shift = mul_mod_poly(X_POW_TABLE[n % 64], shift);
+        }
+        len >>= 1;
+        n += 1;
+    }
+    shift
+}
+
+/// Returns CRC32C checksum of concatenated bytes
+/// given checksums of both parts and the length of the second part.
+#[cfg(feature = "alloc")]
+#[inline]
+pub(crate) fn crc32c_combine(first: u32, second: u32, second_len: usize) -> u32 {
+    mul_mod_poly(shift_bytes(second_len), first) ^ second
+}
+
+/// Incremental CRC32C checksum.
+#[derive(Clone, Copy)]
+pub(crate) struct Crc32c {
+    state: u32,
+}
+
+impl Crc32c {
+    #[inline(always)]
+    pub const fn new() -> Self {
+        Crc32c { state: !0 }
+    }
+
+    #[inline]
+    pub fn update(&mut self, bytes: &[u8]) {
+        let mut crc = self.state;
+        for &byte in bytes {
+            crc = CRC_TABLE[usize::from((crc as u8) ^ byte)] ^ (crc >> 8);
+        }
+        self.state = crc;
+    }
+
+    /// Updates checksum with the bytes in reverse order.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub fn update_rev(&mut self, bytes: &[u8]) {
+        let mut crc = self.state;
+        for &byte in bytes.iter().rev() {
+            crc = CRC_TABLE[usize::from((crc as u8) ^ byte)] ^ (crc >> 8);
+        }
+        self.state = crc;
+    }
+
+    #[inline(always)]
+    pub const fn finish(self) -> u32 {
+        !self.state
+    }
+}
+
+/// Returns CRC32C checksum of the bytes.
+#[must_use]
+#[inline]
+pub fn crc32c(bytes: &[u8]) -> u32 {
+    let mut crc = Crc32c::new();
+    crc.update(bytes);
+    crc.finish()
+}
+
+/// Values larger than this are not written into reserved heap directly,
+/// so that space reserved by [`ChecksumBuffer`] stays small.
+#[cfg(feature = "alloc")]
+const CHECKSUM_CHUNK: usize = 4096;
+
+/// Zeros written instead of stack padding,
+/// so that padding bytes are known to the checksum.
+#[cfg(feature = "alloc")]
+const ZEROS: [u8; 64] = [0; 64];
+
+/// Heap space reserved by [`ChecksumBuffer`].
+#[cfg(feature = "alloc")]
+struct Pending {
+    heap: usize,
+    stack: usize,
+    len: usize,
+}
+
+/// Checksum of heap bytes that are written so far.
+#[cfg(feature = "alloc")]
+struct ChecksumState {
+    crc: Crc32c,
+
+    /// Copy of stack bytes in reverse order,
+    /// so that the last byte of the stack is the first one here.
+    /// Bytes after the current stack size are stale.
+    stack: Vec<u8>,
+
+    /// Bytes of reserved heap space are written here
+    /// and passed to the wrapped buffer on the next call.
+    reserved: Vec<u8>,
+
+    /// Reserved heap space that is not passed to the wrapped buffer yet.
+    pending: Option<Pending>,
+}
+
+#[cfg(feature = "alloc")]
+impl ChecksumState {
+    #[inline(always)]
+    fn new() -> Self {
+        ChecksumState {
+            crc: Crc32c::new(),
+            stack: Vec::new(),
+            reserved: Vec::new(),
+            pending: None,
+        }
+    }
+
+    /// Prepends the packet header written last to the checksum.
+    #[inline]
+    fn finish<F>(self, packet: &[u8]) -> u32
+    where
+        F: PacketFormula + ?Sized,
+    {
+        debug_assert!(self.pending.is_none());
+        let header = const { header_size::<F>() };
+        crc32c_combine(
+            crc32c(&packet[..header]),
+            self.crc.finish(),
+            packet.len() - header,
+        )
+    }
+}
+
+/// Buffer that updates checksum with heap bytes
+/// as they are written into the wrapped buffer.
+///
+/// Heap is append-only, so bytes are added to the checksum in order:
+/// stack bytes when they are moved to the heap
+/// and reserved heap space when it is passed to the wrapped buffer.
+/// Stack bytes are copied to know them when they are moved.
+/// The packet header is written last and is added by [`ChecksumState::finish`].
+#[cfg(feature = "alloc")]
+struct ChecksumBuffer<'a, B> {
+    buffer: B,
+    state: &'a mut ChecksumState,
+}
+
+#[cfg(feature = "alloc")]
+impl<B> ChecksumBuffer<'_, B>
+where
+    B: Buffer,
+{
+    /// Passes reserved heap space to the wrapped buffer
+    /// and adds it to the checksum.
+    #[inline(always)]
+    fn commit(&mut self) {
+        let Some(pending) = self.state.pending.take() else {
+            return;
+        };
+        let bytes = &self.state.reserved[..pending.len];
+        self.state.crc.update(bytes);
+
+        // Space is reserved in the wrapped buffer already, so this cannot fail.
+        if let Ok(reserved) = self
+            .buffer
+            .reserve_heap_at(pending.heap, pending.stack, pending.len)
+        {
+            if !reserved.is_empty() {
+                reserved.copy_from_slice(bytes);
+            }
+        }
+    }
+
+    /// Reserves heap space for bytes that are added to the checksum.
+    /// Packet header is reserved in the wrapped buffer directly.
+    #[inline(always)]
+    fn reserve(&mut self, heap: usize, stack: usize, len: usize) -> Result<&mut [u8], B::Error> {
+        self.commit();
+        if heap == 0 {
+            return self.buffer.reserve_heap_at(heap, stack, len);
+        }
+        if self.buffer.reserve_heap_at(heap, stack, len)?.is_empty() {
+            return Ok(&mut []);
+        }
+
+        self.state.reserved.clear();
+        self.state.reserved.resize(len, 0);
+        self.state.pending = Some(Pending { heap, stack, len });
+        Ok(&mut self.state.reserved[..])
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, B> Buffer for ChecksumBuffer<'a, B>
+where
+    B: Buffer,
+{
+    type Error = B::Error;
+    type Reborrow<'b>
+        = ChecksumBuffer<'b, B::Reborrow<'b>>
+    where
+        Self: 'b;
+
+    #[inline(always)]
+    fn reborrow(&mut self) -> Self::Reborrow<'_> {
+        ChecksumBuffer {
+            buffer: self.buffer.reborrow(),
+            state: self.state,
+        }
+    }
+
+    #[inline(always)]
+    fn write_stack(&mut self, heap: usize, stack: usize, bytes: &[u8]) -> Result<(), B::Error> {
+        self.commit();
+        self.buffer.write_stack(heap, stack, bytes)?;
+
+        // Bytes below the top of the stack may be overwritten,
+        // e.g. size of unsized field is written after the field.
+        let end = stack + bytes.len();
+        if self.state.stack.len() < end {
+            self.state.stack.resize(end, 0);
+        }
+        let copy = &mut self.state.stack[stack..end];
+        for (copy, &byte) in copy.iter_mut().zip(bytes.iter().rev()) {
+            *copy = byte;
+        }
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn pad_stack(&mut self, heap: usize, mut stack: usize, mut len: usize) -> Result<(), B::Error> {
+        while len > 0 {
+            let chunk = len.min(ZEROS.len());
+            self.write_stack(heap, stack, &ZEROS[..chunk])?;
+            stack += chunk;
+            len -= chunk;
+        }
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn move_to_heap(&mut self, heap: usize, stack: usize, len: usize) {
+        self.commit();
+        self.buffer.move_to_heap(heap, stack, len);
+        // Stack copy is reversed, so moved bytes are too.
+        self.state
+            .crc
+            .update_rev(&self.state.stack[stack - len..stack]);
+    }
+
+    #[inline(always)]
+    fn reserve_heap(
+        &mut self,
+        heap: usize,
+        stack: usize,
+        len: usize,
+    ) -> Result<&mut [u8], B::Error> {
+        self.reserve(heap, stack, len)
+    }
+
+    #[inline(always)]
+    fn reserve_heap_at(
+        &mut self,
+        heap: usize,
+        stack: usize,
+        len: usize,
+    ) -> Result<&mut [u8], B::Error> {
+        self.reserve(heap, stack, len)
+    }
+
+    #[inline(always)]
+    fn prefer_reserve(&self, len: usize) -> bool {
+        len <= CHECKSUM_CHUNK && self.buffer.prefer_reserve(len)
+    }
+
+    #[inline(always)]
+    fn width_overflow(&mut self) -> Result<(), B::Error> {
+        self.commit();
+        self.buffer.width_overflow()
+    }
+}
+
+/// Writes packet with the value into bytes slice,
+/// followed by CRC32C checksum of the packet.
+/// Returns the number of bytes written, including checksum.
+/// Fails if the buffer is too small.
+///
+/// With `alloc` feature checksum is updated as packet is written.
+/// Otherwise it is computed over the written packet.
+///
+/// # Errors
+///
+/// Returns [`BufferExhausted`] if the buffer is too small.
+#[inline]
+pub fn write_packet_checked<F, T>(value: T, output: &mut [u8]) -> Result<usize, BufferExhausted>
+where
+    F: PacketFormula + ?Sized,
+    T: Serialize<F::Formula>,
+{
+    let Some(room) = output.len().checked_sub(CHECKSUM_SIZE) else {
+        return Err(BufferExhausted);
+    };
+
+    #[cfg(feature = "alloc")]
+    let checksum = {
+        let mut state = ChecksumState::new();
+        let buffer = ChecksumBuffer {
+            buffer: CheckedFixedBuffer::new(&mut output[..room]),
+            state: &mut state,
+        };
+        let size = write_packet_into::<F, T, _>(value, buffer)?;
+        (size, state.finish::<F>(&output[..size]))
+    };
+
+    #[cfg(not(feature = "alloc"))]
+    let checksum = {
+        let buffer = CheckedFixedBuffer::new(&mut output[..room]);
+        let size = write_packet_into::<F, T, _>(value, buffer)?;
+        (size, crc32c(&output[..size]))
+    };
+
+    let (size, checksum) = checksum;
+    output[size..][..CHECKSUM_SIZE].copy_from_slice(&checksum.to_le_bytes());
+    Ok(size + CHECKSUM_SIZE)
+}
+
+/// Writes packet with the value into byte vector,
+/// followed by CRC32C checksum of the packet.
+/// Returns the number of bytes written, including checksum.
+///
+/// Grows the vector if needed.
+/// Checksum is updated as packet is written.
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn write_packet_checked_to_vec<F, T>(value: T, output: &mut alloc::vec::Vec<u8>) -> usize
+where
+    F: PacketFormula + ?Sized,
+    T: Serialize<F::Formula>,
+{
+    let mut state = ChecksumState::new();
+    let buffer = ChecksumBuffer {
+        buffer: VecBuffer::new(output),
+        state: &mut state,
+    };
+    let size = match write_packet_into::<F, T, _>(value, buffer) {
+        Ok(size) => size,
+        Err(never) => match never {},
+    };
+    let checksum = state.finish::<F>(&output[..size]);
+    output.truncate(size);
+    output.extend_from_slice(&checksum.to_le_bytes());
+    size + CHECKSUM_SIZE
+}
+
+/// Writes packet with the value into [`std::io::Write`],
+/// followed by CRC32C checksum of the packet.
+/// Returns the number of bytes written, including checksum.
+///
+/// Checksum is updated as bytes are passed to the writer.
+///
+/// # Errors
+///
+/// Returns error if writer fails.
+#[cfg(feature = "std")]
+#[inline]
+pub fn write_packet_checked_to_writer<F, T, W>(value: T, writer: &mut W) -> std::io::Result<usize>
+where
+    F: PacketFormula + ?Sized,
+    T: Serialize<F::Formula>,
+    W: std::io::Write + ?Sized,
+{
+    let mut checked = ChecksumWriter {
+        writer: &mut *writer,
+        crc: Crc32c::new(),
+    };
+    let size = crate::packet::write_packet_to_writer::<F, T, _>(value, &mut checked)?;
+    let checksum = checked.crc.finish();
+    writer.write_all(&checksum.to_le_bytes())?;
+    Ok(size + CHECKSUM_SIZE)
+}
+
+/// Writer that updates checksum with written bytes.
+#[cfg(feature = "std")]
+struct ChecksumWriter<'a, W: ?Sized> {
+    writer: &'a mut W,
+    crc: Crc32c,
+}
+
+#[cfg(feature = "std")]
+impl<W> std::io::Write for ChecksumWriter<'_, W>
+where
+    W: std::io::Write + ?Sized,
+{
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.writer.write(buf)?;
+        self.crc.update(&buf[..written]);
+        Ok(written)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Reads packet with value from the input
+/// and verifies CRC32C checksum that follows it.
+/// Returns deserialized value and number of bytes consumed,
+/// including checksum.
+///
+/// # Errors
+///
+/// Returns `DeserializeError::ChecksumMismatch` if checksum does not match.
+/// Returns `DeserializeError` if deserialization fails.
+#[inline]
+pub fn read_packet_checked<'de, F, T>(input: &'de [u8]) -> Result<(T, usize), DeserializeError>
+where
+    F: PacketFormula + ?Sized,
+    T: Deserialize<'de, F::Formula>,
+{
+    let size = read_packet_size::<F>(input).ok_or(DeserializeError::OutOfBounds)?;
+    let end = size
+        .checked_add(CHECKSUM_SIZE)
+        .ok_or(DeserializeError::OutOfBounds)?;
+    if input.len() < end {
+        return Err(DeserializeError::OutOfBounds);
+    }
+
+    let (packet, tail) = input.split_at(size);
+    let mut checksum = [0; CHECKSUM_SIZE];
+    checksum.copy_from_slice(&tail[..CHECKSUM_SIZE]);
+    if u32::from_le_bytes(checksum) != crc32c(packet) {
+        return Err(DeserializeError::ChecksumMismatch);
+    }
+
+    let (value, _) = read_packet::<F, T>(packet)?;
+    Ok((value, end))
+}
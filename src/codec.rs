@@ -0,0 +1,185 @@
+use core::{fmt, marker::PhantomData};
+use std::io;
+
+use ::bytes::BytesMut;
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::{
+    buffer::BytesMutBuffer,
+    deserialize::Deserialize,
+    packet::{header_size, read_packet, read_packet_size, write_packet_into, PacketFormula},
+    serialize::{fixed_width, Serialize},
+};
+
+/// Codec for [`tokio_util::codec`] that frames values as packets.
+///
+/// Encodes any value that implements [`Serialize<F>`]
+/// and decodes values of type `T`.
+/// Each frame is a packet produced by [`write_packet_into`]
+/// and is recognized with [`read_packet_size`].
+///
+/// Frames larger than the limit are rejected on both sides
+/// with [`io::ErrorKind::InvalidData`] error.
+pub struct AlkahestCodec<F: ?Sized, T> {
+    max_frame_size: usize,
+    marker: PhantomData<fn(&F) -> T>,
+}
+
+impl<F, T> AlkahestCodec<F, T>
+where
+    F: PacketFormula + ?Sized,
+{
+    /// Default limit for the frame size. 8 MiB.
+    pub const DEFAULT_MAX_FRAME_SIZE: usize = 8 * 1024 * 1024;
+
+    /// Creates a new codec with default frame size limit.
+    #[must_use]
+    #[inline]
+    pub fn new() -> Self {
+        AlkahestCodec {
+            max_frame_size: Self::DEFAULT_MAX_FRAME_SIZE,
+            marker: PhantomData,
+        }
+    }
+
+    /// Sets maximum size of a frame.
+    #[must_use]
+    #[inline]
+    pub fn with_max_frame_size(mut self, max_frame_size: usize) -> Self {
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    /// Returns maximum size of a frame.
+    #[must_use]
+    #[inline]
+    pub fn max_frame_size(&self) -> usize {
+        self.max_frame_size
+    }
+
+    fn frame_too_large(&self, size: usize) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            FrameTooLarge {
+                size,
+                max: self.max_frame_size,
+            },
+        )
+    }
+}
+
+impl<F, T> Default for AlkahestCodec<F, T>
+where
+    F: PacketFormula + ?Sized,
+{
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F, T> Clone for AlkahestCodec<F, T>
+where
+    F: ?Sized,
+{
+    #[inline]
+    fn clone(&self) -> Self {
+        AlkahestCodec {
+            max_frame_size: self.max_frame_size,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<F, T> fmt::Debug for AlkahestCodec<F, T>
+where
+    F: ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AlkahestCodec")
+            .field("max_frame_size", &self.max_frame_size)
+            .finish()
+    }
+}
+
+impl<F, T, V> Encoder<V> for AlkahestCodec<F, T>
+where
+    F: PacketFormula + ?Sized,
+    V: Serialize<F::Formula>,
+{
+    type Error = io::Error;
+
+    fn encode(&mut self, item: V, dst: &mut BytesMut) -> io::Result<()> {
+        // Packet is written at the end of `dst`.
+        let mut frame = dst.split_off(dst.len());
+        let size = match write_packet_into::<F, V, _>(item, BytesMutBuffer::new(&mut frame)) {
+            Ok(size) => size,
+            Err(never) => match never {},
+        };
+
+        if size > self.max_frame_size {
+            return Err(self.frame_too_large(size));
+        }
+
+        frame.truncate(size);
+        dst.unsplit(frame);
+        Ok(())
+    }
+}
+
+impl<F, T> Decoder for AlkahestCodec<F, T>
+where
+    F: PacketFormula + ?Sized,
+    T: for<'de> Deserialize<'de, F::Formula>,
+{
+    type Item = T;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<T>> {
+        let Some(size) = read_packet_size::<F>(src) else {
+            src.reserve(const { fixed_width::<F::Width>() } - src.len());
+            return Ok(None);
+        };
+
+        if size < const { header_size::<F>() } {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "packet size is smaller than reference",
+            ));
+        }
+
+        if size > self.max_frame_size {
+            return Err(self.frame_too_large(size));
+        }
+
+        if src.len() < size {
+            src.reserve(size - src.len());
+            return Ok(None);
+        }
+
+        let frame = src.split_to(size);
+        match read_packet::<F, T>(&frame) {
+            Ok((value, _)) => Ok(Some(value)),
+            Err(err) => Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+        }
+    }
+}
+
+/// Error for frames that exceed size limit.
+#[derive(Debug)]
+struct FrameTooLarge {
+    size: usize,
+    max: usize,
+}
+
+impl fmt::Display for FrameTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "frame size {} exceeds the limit of {} bytes",
+            self.size, self.max
+        )
+    }
+}
+
+impl std::error::Error for FrameTooLarge {}
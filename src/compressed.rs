@@ -0,0 +1,195 @@
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use crate::{
+    buffer::Buffer,
+    bytes::Bytes,
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::{reference_size, Formula},
+    packet::{read_packet, read_packet_in_place, write_packet_to_vec},
+    reference::Ref,
+    serialize::{Serialize, Sizes},
+};
+
+/// Default limit of decompressed size for [`Compressed`] formula.
+pub const MAX_DECOMPRESSED_SIZE: usize = 1 << 24;
+
+/// Compression algorithm for [`Compressed`] formula.
+pub trait Compression {
+    /// Compresses the bytes.
+    fn compress(input: &[u8]) -> Vec<u8>;
+
+    /// Decompresses the bytes.
+    /// Fails without decompressing more than `max_size` bytes
+    /// if decompressed data is larger.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError::WrongLength` if decompressed data
+    /// is larger than `max_size`.
+    /// Returns `DeserializeError::Incompatible` if input is not valid compressed data.
+    fn decompress(input: &[u8], max_size: usize) -> Result<Vec<u8>, DeserializeError>;
+}
+
+/// A formula wrapper that stores value of formula `F`
+/// compressed with algorithm `C`.
+///
+/// Value is serialized into scratch buffer with formula `F`,
+/// compressed and stored in dynamic payload like `Ref<Bytes>`.
+/// Deserialization decompresses into owned buffer,
+/// so only types that do not borrow from the input can be deserialized.
+///
+/// Other fields of the enclosing formula stay accessible without decompression.
+///
+/// Decompressed size is limited by `MAX_SIZE`,
+/// so that small malicious input cannot exhaust memory.
+/// Larger data fails to deserialize with `DeserializeError::WrongLength`.
+/// The limit applies only to deserialization, values of any size
+/// are serialized and can be read with a formula that has larger `MAX_SIZE`.
+pub struct Compressed<F: ?Sized, C, const MAX_SIZE: usize = MAX_DECOMPRESSED_SIZE> {
+    marker: PhantomData<fn(&F, C) -> &F>,
+}
+
+impl<F, C, const MAX_SIZE: usize> Formula for Compressed<F, C, MAX_SIZE>
+where
+    F: Formula + ?Sized,
+    C: Compression,
+{
+    const MAX_STACK_SIZE: Option<usize> = Some(reference_size::<Bytes>());
+    const EXACT_SIZE: bool = true;
+    const HEAPLESS: bool = false;
+}
+
+impl<F, C, T, const MAX_SIZE: usize> Serialize<Compressed<F, C, MAX_SIZE>> for T
+where
+    F: Formula + ?Sized,
+    C: Compression,
+    T: Serialize<F>,
+{
+    #[inline]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        let mut scratch = Vec::new();
+        let size = write_packet_to_vec::<F, T>(self, &mut scratch);
+        let compressed = C::compress(&scratch[..size]);
+        <&[u8] as Serialize<Ref<Bytes>>>::serialize(&compressed, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        None
+    }
+}
+
+impl<'de, F, C, T, const MAX_SIZE: usize> Deserialize<'de, Compressed<F, C, MAX_SIZE>> for T
+where
+    F: Formula + ?Sized,
+    C: Compression,
+    T: for<'a> Deserialize<'a, F>,
+{
+    #[inline]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        let compressed = <&[u8] as Deserialize<'de, Ref<Bytes>>>::deserialize(de)?;
+        let decompressed = C::decompress(compressed, MAX_SIZE)?;
+        let (value, _) = read_packet::<F, T>(&decompressed)?;
+        Ok(value)
+    }
+
+    #[inline]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        let compressed = <&[u8] as Deserialize<'de, Ref<Bytes>>>::deserialize(de)?;
+        let decompressed = C::decompress(compressed, MAX_SIZE)?;
+        read_packet_in_place::<F, T>(self, &decompressed)?;
+        Ok(())
+    }
+}
+
+/// LZ4 block compression with [`lz4_flex`] crate.
+#[cfg(feature = "lz4")]
+pub struct Lz4;
+
+#[cfg(feature = "lz4")]
+impl Compression for Lz4 {
+    #[inline]
+    fn compress(input: &[u8]) -> Vec<u8> {
+        lz4_flex::block::compress_prepend_size(input)
+    }
+
+    #[inline]
+    fn decompress(input: &[u8], max_size: usize) -> Result<Vec<u8>, DeserializeError> {
+        // Check prepended size before it is used to allocate output.
+        let (size, _) = lz4_flex::block::uncompressed_size(input)
+            .map_err(|_| DeserializeError::Incompatible)?;
+        if size > max_size {
+            return Err(DeserializeError::WrongLength);
+        }
+        lz4_flex::block::decompress_size_prepended(input)
+            .map_err(|_| DeserializeError::Incompatible)
+    }
+}
+
+/// Zstandard compression with [`zstd`] crate.
+#[cfg(feature = "zstd")]
+pub struct Zstd;
+
+#[cfg(feature = "zstd")]
+impl Compression for Zstd {
+    /// # Panics
+    ///
+    /// Compression of in-memory buffer with default level fails
+    /// only if zstd cannot allocate its context, which is treated
+    /// as allocation failure.
+    #[inline]
+    fn compress(input: &[u8]) -> Vec<u8> {
+        match zstd::bulk::compress(input, zstd::DEFAULT_COMPRESSION_LEVEL) {
+            Ok(compressed) => compressed,
+            Err(err) => panic!("Zstd compression error: {}", err),
+        }
+    }
+
+    #[inline]
+    fn decompress(input: &[u8], max_size: usize) -> Result<Vec<u8>, DeserializeError> {
+        use std::io::Read;
+
+        let decoder = zstd::stream::read::Decoder::with_buffer(input)
+            .map_err(|_| DeserializeError::Incompatible)?;
+
+        // Read one byte past the limit to detect larger data.
+        let mut output = Vec::new();
+        decoder
+            .take(max_size as u64 + 1)
+            .read_to_end(&mut output)
+            .map_err(|_| DeserializeError::Incompatible)?;
+
+        if output.len() > max_size {
+            return Err(DeserializeError::WrongLength);
+        }
+        Ok(output)
+    }
+}
+
+/// Deflate compression with [`miniz_oxide`] crate.
+#[cfg(feature = "deflate")]
+pub struct Deflate;
+
+#[cfg(feature = "deflate")]
+impl Compression for Deflate {
+    #[inline]
+    fn compress(input: &[u8]) -> Vec<u8> {
+        miniz_oxide::deflate::compress_to_vec(input, 6)
+    }
+
+    #[inline]
+    fn decompress(input: &[u8], max_size: usize) -> Result<Vec<u8>, DeserializeError> {
+        use miniz_oxide::inflate::TINFLStatus;
+
+        miniz_oxide::inflate::decompress_to_vec_with_limit(input, max_size).map_err(|err| match err
+            .status
+        {
+            TINFLStatus::HasMoreOutput => DeserializeError::WrongLength,
+            _ => DeserializeError::Incompatible,
+        })
+    }
+}
@@ -1,8 +1,10 @@
-use core::{iter::FusedIterator, marker::PhantomData, str::Utf8Error};
+use core::{fmt, iter::FusedIterator, marker::PhantomData, str::Utf8Error};
 
 use crate::{
     formula::{reference_size, unwrap_size, Formula},
+    serialize::fixed_width,
     size::{FixedIsizeType, FixedUsize, FixedUsizeType, SIZE_STACK},
+    width::Width,
 };
 
 #[inline(always)]
@@ -13,6 +15,7 @@ pub(crate) const fn cold_err<T>(e: DeserializeError) -> Result<T, DeserializeErr
 
 /// Error that can occur during deserialization.
 #[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
 pub enum DeserializeError {
     /// Indicates that input buffer is smaller than
     /// expected value length.
@@ -45,6 +48,55 @@ pub enum DeserializeError {
 
     /// Data is incompatible with the type to be deserialized.
     Incompatible,
+
+    /// Checksum of the packet does not match its content.
+    ChecksumMismatch,
+
+    /// Input is not aligned enough to borrow values from it.
+    Misaligned,
+
+    /// Arena collection is deserialized without arena.
+    ///
+    /// Use `deserialize_in_arena` or `read_packet_in_arena`
+    /// to deserialize collections from `bumpalo`.
+    MissingArena,
+}
+
+impl fmt::Display for DeserializeError {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeserializeError::OutOfBounds => write!(f, "input is too short"),
+            DeserializeError::WrongAddress => write!(f, "invalid address"),
+            DeserializeError::WrongLength => write!(f, "invalid value length"),
+            DeserializeError::InvalidUsize(value) => {
+                write!(f, "size {} does not fit `usize`", value)
+            }
+            DeserializeError::InvalidIsize(value) => {
+                write!(f, "size {} does not fit `isize`", value)
+            }
+            DeserializeError::WrongVariant(variant) => {
+                write!(f, "invalid enum variant {}", variant)
+            }
+            DeserializeError::NonUtf8(err) => write!(f, "string is not UTF-8: {}", err),
+            DeserializeError::IntegerOverflow => write!(f, "integer overflow"),
+            DeserializeError::Incompatible => write!(f, "incompatible data"),
+            DeserializeError::ChecksumMismatch => write!(f, "checksum mismatch"),
+            DeserializeError::Misaligned => write!(f, "input is misaligned"),
+            DeserializeError::MissingArena => write!(f, "arena is not provided"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DeserializeError {
+    #[inline]
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DeserializeError::NonUtf8(err) => Some(err),
+            _ => None,
+        }
+    }
 }
 
 /// Trait for types that can be deserialized
@@ -87,6 +139,14 @@ pub struct Deserializer<'de> {
     /// Input buffer sub-slice usable for deserialization.
     input: &'de [u8],
     stack: usize,
+
+    /// Shared buffer that contains the input.
+    #[cfg(feature = "bytes")]
+    origin: Option<&'de ::bytes::Bytes>,
+
+    /// Arena for deserialized values.
+    #[cfg(feature = "bumpalo")]
+    arena: Option<&'de bumpalo::Bump>,
 }
 
 impl<'de> Deserializer<'de> {
@@ -108,7 +168,77 @@ impl<'de> Deserializer<'de> {
     #[inline(always)]
     pub const fn new_unchecked(stack: usize, input: &'de [u8]) -> Self {
         debug_assert!(stack <= input.len());
-        Deserializer { input, stack }
+        Deserializer {
+            input,
+            stack,
+            #[cfg(feature = "bytes")]
+            origin: None,
+            #[cfg(feature = "bumpalo")]
+            arena: None,
+        }
+    }
+
+    /// Creates new deserializer from shared input buffer.
+    ///
+    /// Values deserialized into [`bytes::Bytes`](::bytes::Bytes)
+    /// with [`Bytes`](crate::Bytes) formula share the input buffer
+    /// instead of copying.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError::OutOfBounds` if
+    /// `stack` is greater than `input.len()`.
+    #[cfg(feature = "bytes")]
+    #[inline(always)]
+    pub fn new_shared(stack: usize, input: &'de ::bytes::Bytes) -> Result<Self, DeserializeError> {
+        Ok(Self::new(stack, input)?.with_origin(input))
+    }
+
+    #[cfg(feature = "bytes")]
+    #[inline(always)]
+    pub(crate) fn with_origin(mut self, origin: &'de ::bytes::Bytes) -> Self {
+        self.origin = Some(origin);
+        self
+    }
+
+    /// Returns shared buffer that contains the input, if any.
+    #[cfg(feature = "bytes")]
+    #[must_use]
+    #[inline(always)]
+    pub fn origin(&self) -> Option<&'de ::bytes::Bytes> {
+        self.origin
+    }
+
+    #[cfg(feature = "bumpalo")]
+    #[inline(always)]
+    pub(crate) fn with_arena(mut self, arena: &'de bumpalo::Bump) -> Self {
+        self.arena = Some(arena);
+        self
+    }
+
+    /// Returns arena for deserialized values, if any.
+    #[cfg(feature = "bumpalo")]
+    #[must_use]
+    #[inline(always)]
+    pub fn arena(&self) -> Option<&'de bumpalo::Bump> {
+        self.arena
+    }
+
+    /// Creates deserializer for part of the input.
+    /// Keeps shared buffer and arena of this deserializer.
+    #[inline(always)]
+    fn child(&self, stack: usize, input: &'de [u8]) -> Self {
+        #[allow(unused_mut)]
+        let mut child = Deserializer::new_unchecked(stack, input);
+        #[cfg(feature = "bytes")]
+        {
+            child.origin = self.origin;
+        }
+        #[cfg(feature = "bumpalo")]
+        {
+            child.arena = self.arena;
+        }
+        child
     }
 
     #[inline(always)]
@@ -118,7 +248,7 @@ impl<'de> Deserializer<'de> {
             return cold_err(DeserializeError::WrongLength);
         }
 
-        let sub = Deserializer::new_unchecked(stack, self.input);
+        let sub = self.child(stack, self.input);
 
         self.stack -= stack;
         let end = self.input.len() - stack;
@@ -126,6 +256,19 @@ impl<'de> Deserializer<'de> {
         Ok(sub)
     }
 
+    /// Returns number of bytes remaining on the stack.
+    #[inline(always)]
+    pub(crate) fn stack(&self) -> usize {
+        self.stack
+    }
+
+    /// Returns the input buffer up to the end of the stack.
+    #[cfg(feature = "rayon")]
+    #[inline(always)]
+    pub(crate) fn input(&self) -> &'de [u8] {
+        self.input
+    }
+
     /// Reads specified number of bytes from the input buffer.
     /// Returns slice of bytes.
     /// Advances the input buffer.
@@ -208,15 +351,65 @@ impl<'de> Deserializer<'de> {
     where
         F: Formula + ?Sized,
         T: Deserialize<'de, F>,
+    {
+        self.read_value_with::<F, FixedUsize, T>(last)
+    }
+
+    /// Reads and deserializes field written with
+    /// [`write_field_with`](crate::advanced::write_field_with).
+    /// Size of unsized field that is not last is read with specified [`Width`].
+    /// Advances the input buffer.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if deserialization fails.
+    #[inline(always)]
+    pub fn read_value_with<F, W, T>(&mut self, last: bool) -> Result<T, DeserializeError>
+    where
+        F: Formula + ?Sized,
+        W: Width,
+        T: Deserialize<'de, F>,
+    {
+        <T as Deserialize<'de, F>>::deserialize(self.sub_value_with::<F, W>(last)?)
+    }
+
+    /// Reads field from the input buffer and returns deserializer for it.
+    /// Size of unsized field that is not last is read with specified [`Width`].
+    /// Advances the input buffer.
+    #[inline(always)]
+    pub(crate) fn sub_value_with<F, W>(&mut self, last: bool) -> Result<Self, DeserializeError>
+    where
+        F: Formula + ?Sized,
+        W: Width,
     {
         let stack = match (F::MAX_STACK_SIZE, F::EXACT_SIZE, last) {
-            (None, _, false) => self.read_value::<FixedUsize, usize>(false)?,
+            (None, _, false) => self.read_prefix::<W>()?,
             (None, _, true) => self.stack,
             (Some(max_stack), false, true) => max_stack.min(self.stack),
             (Some(max_stack), _, _) => max_stack,
         };
 
-        <T as Deserialize<'de, F>>::deserialize(self.sub(stack)?)
+        self.sub(stack)
+    }
+
+    /// Reads size prefix of unsized field.
+    #[inline(always)]
+    fn read_prefix<W>(&mut self) -> Result<usize, DeserializeError>
+    where
+        W: Width,
+    {
+        let _ = const { fixed_width::<W>() };
+        W::read(self)
+    }
+
+    /// Returns deserializer for elements `start..end` of a slice
+    /// with element formula of `element_size` bytes.
+    #[inline(always)]
+    pub(crate) fn sized_elements(&self, element_size: usize, start: usize, end: usize) -> Self {
+        debug_assert!(start <= end);
+        debug_assert!(end * element_size <= self.stack);
+        let input = &self.input[..self.input.len() - start * element_size];
+        self.child((end - start) * element_size, input)
     }
 
     /// Reads and deserializes field from the back of input buffer.
@@ -241,7 +434,7 @@ impl<'de> Deserializer<'de> {
         let input_back = &self.input[..self.input.len() - self.stack + stack];
         self.stack -= stack;
 
-        let sub = Deserializer::new_unchecked(stack, input_back);
+        let sub = self.child(stack, input_back);
         <T as Deserialize<'de, F>>::deserialize(sub)
     }
 
@@ -255,11 +448,32 @@ impl<'de> Deserializer<'de> {
     where
         F: Formula + ?Sized,
         T: Deserialize<'de, F> + ?Sized,
+    {
+        self.read_in_place_with::<F, FixedUsize, T>(place, last)
+    }
+
+    /// Reads and deserializes field written with
+    /// [`write_field_with`](crate::advanced::write_field_with) in-place.
+    /// Size of unsized field that is not last is read with specified [`Width`].
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if deserialization fails.
+    #[inline(always)]
+    pub fn read_in_place_with<F, W, T>(
+        &mut self,
+        place: &mut T,
+        last: bool,
+    ) -> Result<(), DeserializeError>
+    where
+        F: Formula + ?Sized,
+        W: Width,
+        T: Deserialize<'de, F> + ?Sized,
     {
         let stack = match (last, F::MAX_STACK_SIZE) {
             (true, _) => self.stack,
             (false, Some(max_stack)) => max_stack,
-            (false, None) => self.read_value::<FixedUsize, usize>(false)?,
+            (false, None) => self.read_prefix::<W>()?,
         };
 
         <T as Deserialize<'de, F>>::deserialize_in_place(place, self.sub(stack)?)
@@ -290,7 +504,46 @@ impl<'de> Deserializer<'de> {
 
         let input = &head[..address];
 
-        Deserializer::new(size, input)
+        if size > input.len() {
+            return cold_err(DeserializeError::OutOfBounds);
+        }
+        Ok(self.child(size, input))
+    }
+
+    /// Reads reference with specified [`Width`] from the input buffer
+    /// and returns deserializer for the referenced value.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if reference is invalid.
+    #[inline(always)]
+    pub fn deref_with<F, W>(mut self) -> Result<Deserializer<'de>, DeserializeError>
+    where
+        F: Formula + ?Sized,
+        W: Width,
+    {
+        let size = if F::EXACT_SIZE {
+            None
+        } else {
+            Some(W::read(&mut self)?)
+        };
+        let address = W::read(&mut self)?;
+
+        let head = self.input;
+        if address > head.len() {
+            return Err(DeserializeError::WrongAddress);
+        }
+
+        let input = &head[..address];
+        let size = match size {
+            None => unwrap_size(F::MAX_STACK_SIZE).min(head.len()),
+            Some(size) => size,
+        };
+
+        if size > input.len() {
+            return cold_err(DeserializeError::OutOfBounds);
+        }
+        Ok(self.child(size, input))
     }
 
     /// Converts deserializer into iterator over deserialized values with
@@ -395,9 +648,20 @@ impl<'de> Deserializer<'de> {
 
     /// Skips specified number of values with specified formula.
     #[inline(always)]
-    fn skip_values<F>(&mut self, n: usize) -> Result<(), DeserializeError>
+    pub(crate) fn skip_values<F>(&mut self, n: usize) -> Result<(), DeserializeError>
     where
         F: Formula + ?Sized,
+    {
+        self.skip_values_with::<F, FixedUsize>(n)
+    }
+
+    /// Skips specified number of values with specified formula
+    /// and sizes of unsized values written with specified [`Width`].
+    #[inline(always)]
+    pub(crate) fn skip_values_with<F, W>(&mut self, n: usize) -> Result<(), DeserializeError>
+    where
+        F: Formula + ?Sized,
+        W: Width,
     {
         if n == 0 {
             return Ok(());
@@ -406,7 +670,7 @@ impl<'de> Deserializer<'de> {
         match F::MAX_STACK_SIZE {
             None => {
                 for _ in 0..n {
-                    let skip_bytes = self.read_value::<FixedUsize, usize>(false)?;
+                    let skip_bytes = self.read_prefix::<W>()?;
                     self.read_bytes(skip_bytes)?;
                 }
             }
@@ -491,7 +755,7 @@ where
 
     #[inline(always)]
     fn next(&mut self) -> Option<Result<T, DeserializeError>> {
-        if self.is_empty() {
+        if DeIter::is_empty(self) {
             return None;
         }
         let item = self.de.read_value::<F, T>(false);
@@ -533,7 +797,7 @@ where
                 if self.de.stack < SIZE_STACK {
                     break;
                 }
-                let sub = Deserializer::new_unchecked(SIZE_STACK, self.de.input);
+                let sub = self.de.child(SIZE_STACK, self.de.input);
                 self.de.input = &self.de.input[..self.de.input.len() - SIZE_STACK];
 
                 let stack = match <usize as Deserialize<'de, FixedUsize>>::deserialize(sub) {
@@ -543,7 +807,7 @@ where
                         return f(init, cold_err(err));
                     }
                 };
-                let sub = Deserializer::new_unchecked(stack, self.de.input);
+                let sub = self.de.child(stack, self.de.input);
                 self.de.input = &self.de.input[..self.de.input.len() - stack];
                 self.de.stack -= SIZE_STACK * stack;
 
@@ -551,7 +815,7 @@ where
                 init = f(init, result);
             },
             Some(0) => {
-                let sub = Deserializer::new_unchecked(0, self.de.input);
+                let sub = self.de.child(0, self.de.input);
                 for _ in 0..self.upper {
                     let result = <T as Deserialize<'de, F>>::deserialize(sub.clone());
                     init = f(init, result);
@@ -560,7 +824,7 @@ where
             Some(stack) => {
                 assert_eq!(self.de.stack / stack, self.upper);
                 for _ in 0..self.upper {
-                    let sub = Deserializer::new_unchecked(stack, self.de.input);
+                    let sub = self.de.child(stack, self.de.input);
                     self.de.input = &self.de.input[..self.de.input.len() - stack];
 
                     let result = <T as Deserialize<'de, F>>::deserialize(sub);
@@ -572,6 +836,26 @@ where
     }
 }
 
+impl<'de, F, T> DeIter<'de, F, T>
+where
+    F: Formula + ?Sized,
+    T: Deserialize<'de, F>,
+{
+    /// Converts iterator into [`SizedDeIter`] that is double-ended and exact-sized.
+    ///
+    /// Attempt to use with unsized element formula will result in compile error.
+    #[inline(always)]
+    pub fn into_sized(self) -> SizedDeIter<'de, F, T> {
+        const { assert!(F::MAX_STACK_SIZE.is_some(), "Element formula must be sized") }
+
+        DeIter {
+            de: self.de,
+            upper: self.upper,
+            marker: PhantomData,
+        }
+    }
+}
+
 impl<'de, F, T> DeIter<'de, F, T, IterSized>
 where
     F: Formula + ?Sized,
@@ -615,7 +899,7 @@ where
     {
         match Self::ELEMENT_SIZE {
             0 => {
-                let sub = Deserializer::new_unchecked(0, self.de.input);
+                let sub = self.de.child(0, self.de.input);
                 for _ in 0..self.upper {
                     let result = <T as Deserialize<'de, F>>::deserialize(sub.clone());
                     init = f(init, result);
@@ -626,7 +910,7 @@ where
                 let mut end = self.de.input.len() - stack * self.upper;
                 for _ in 0..self.upper {
                     end += stack;
-                    let sub = Deserializer::new_unchecked(stack, &self.de.input[..end]);
+                    let sub = self.de.child(stack, &self.de.input[..end]);
 
                     let result = <T as Deserialize<'de, F>>::deserialize(sub);
                     init = f(init, result);
@@ -680,6 +964,44 @@ where
     Ok(value)
 }
 
+/// Deserializes value from byte array of the formula's exact size.
+/// Returns deserialized value.
+///
+/// Counterpart of [`serialize_to_array`](crate::serialize::serialize_to_array).
+/// Input size is checked at compile time, so no bounds checks are needed.
+///
+/// # Errors
+///
+/// Returns `DeserializeError` if the bytes are not a valid value,
+/// for example integer that does not fit the target type.
+///
+/// # Panics
+///
+/// Using formula that is not exact-sized and heap-less,
+/// or `N` that does not match its size, is a compile-time error.
+#[inline(always)]
+pub fn deserialize_array<'de, F, T, const N: usize>(
+    input: &'de [u8; N],
+) -> Result<T, DeserializeError>
+where
+    F: Formula + ?Sized,
+    T: Deserialize<'de, F>,
+{
+    const {
+        assert!(
+            F::EXACT_SIZE && F::HEAPLESS,
+            "Formula must be exact-sized and heap-less"
+        );
+        assert!(
+            matches!(F::MAX_STACK_SIZE, Some(size) if size == N),
+            "Array size must match formula size"
+        );
+    }
+
+    let de = Deserializer::new_unchecked(N, input);
+    <T as Deserialize<'de, F>>::deserialize(de)
+}
+
 /// Deserializes value from the input.
 /// The value must occupy the whole input slice.
 /// Returns deserialized value.
@@ -0,0 +1,149 @@
+use core::{marker::PhantomData, mem::size_of};
+
+use crate::{
+    buffer::Buffer,
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::{BareFormula, Formula},
+    serialize::{write_bytes, Serialize, SerializeRef, Sizes},
+};
+
+/// Formula wrapper for primitives stored in big-endian byte order.
+///
+/// Use to describe existing wire formats with big-endian fields.
+/// Same types that can be serialized with primitive formula `F`
+/// can be serialized with `Be<F>`.
+///
+/// # Examples
+///
+/// ```
+/// # use alkahest::*;
+///
+/// let mut buffer = [0u8; 4];
+/// serialize::<Be<u32>, u32>(0x0102_0304, &mut buffer).unwrap();
+/// assert_eq!(buffer, [1, 2, 3, 4]);
+/// ```
+pub struct Be<F: ?Sized> {
+    marker: PhantomData<fn(&F) -> &F>,
+}
+
+/// Formula wrapper for primitives stored in little-endian byte order.
+///
+/// Same as primitive formula `F` itself.
+/// Exists to make byte order explicit in formulas that mix both.
+pub struct Le<F: ?Sized> {
+    marker: PhantomData<fn(&F) -> &F>,
+}
+
+macro_rules! impl_endian {
+    ($($ty:ident [$($from:ident)*])*) => {
+        $(
+            impl_endian!(@ Be to_be_bytes from_be_bytes $ty [$($from)*]);
+            impl_endian!(@ Le to_le_bytes from_le_bytes $ty [$($from)*]);
+        )*
+    };
+
+    (@ $wrapper:ident $to_bytes:ident $from_bytes:ident $ty:ident [$($from:ident)*]) => {
+        impl Formula for $wrapper<$ty> {
+            const MAX_STACK_SIZE: Option<usize> = Some(size_of::<$ty>());
+            const EXACT_SIZE: bool = true;
+            const HEAPLESS: bool = true;
+        }
+
+        impl BareFormula for $wrapper<$ty> {}
+
+        impl Serialize<$wrapper<$ty>> for $ty {
+            #[inline(always)]
+            fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+            where
+                B: Buffer,
+            {
+                write_bytes(&self.$to_bytes(), sizes, buffer)
+            }
+
+            #[inline(always)]
+            fn size_hint(&self) -> Option<Sizes> {
+                Some(Sizes::with_stack(size_of::<$ty>()))
+            }
+        }
+
+        impl SerializeRef<$wrapper<$ty>> for $ty {
+            #[inline(always)]
+            fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+            where
+                B: Buffer,
+            {
+                write_bytes(&self.$to_bytes(), sizes, buffer)
+            }
+
+            #[inline(always)]
+            fn size_hint(&self) -> Option<Sizes> {
+                Some(Sizes::with_stack(size_of::<$ty>()))
+            }
+        }
+
+        $(
+            impl Serialize<$wrapper<$ty>> for $from {
+                #[inline(always)]
+                fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+                where
+                    B: Buffer,
+                {
+                    write_bytes(&$ty::from(self).$to_bytes(), sizes, buffer)
+                }
+
+                #[inline(always)]
+                fn size_hint(&self) -> Option<Sizes> {
+                    Some(Sizes::with_stack(size_of::<$ty>()))
+                }
+            }
+
+            impl SerializeRef<$wrapper<$ty>> for $from {
+                #[inline(always)]
+                fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+                where
+                    B: Buffer,
+                {
+                    write_bytes(&$ty::from(*self).$to_bytes(), sizes, buffer)
+                }
+
+                #[inline(always)]
+                fn size_hint(&self) -> Option<Sizes> {
+                    Some(Sizes::with_stack(size_of::<$ty>()))
+                }
+            }
+        )*
+
+        impl<T> Deserialize<'_, $wrapper<$ty>> for T
+        where
+            T: From<$ty>,
+        {
+            #[inline(always)]
+            fn deserialize(mut de: Deserializer) -> Result<Self, DeserializeError> {
+                let input = de.read_byte_array::<{ size_of::<$ty>() }>()?;
+                Ok(From::from(<$ty>::$from_bytes(input)))
+            }
+
+            #[inline(always)]
+            fn deserialize_in_place(&mut self, mut de: Deserializer) -> Result<(), DeserializeError> {
+                let input = de.read_byte_array::<{ size_of::<$ty>() }>()?;
+                *self = From::from(<$ty>::$from_bytes(input));
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_endian! {
+    u8 []
+    u16 [u8]
+    u32 [u8 u16]
+    u64 [u8 u16 u32]
+    u128 [u8 u16 u32 u64]
+    i8 []
+    i16 [i8]
+    i32 [i8 i16]
+    i64 [i8 i16 i32]
+    i128 [i8 i16 i32 i64]
+    f32 []
+    f64 [f32]
+}
@@ -0,0 +1,301 @@
+use core::marker::PhantomData;
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use crate::{
+    buffer::{Buffer, DryBuffer},
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::{BareFormula, Formula},
+    lazy::Lazy,
+    serialize::{write_field, Serialize, Sizes},
+    size::{FixedUsize, SIZE_STACK},
+};
+
+/// A formula for slices with offset table.
+///
+/// `[F]` formula prefixes unsized elements with their size,
+/// so element at index can be found only by reading all elements before it.
+/// This formula writes number of elements and table of their offsets
+/// before the elements, so [`Lazy<Indexed<F>>`](Lazy) deserializes
+/// any element in constant time, even for unsized element formulas
+/// like `As<str>`.
+///
+/// Table takes `FixedUsize` per element and unsized elements
+/// are not prefixed with their size.
+/// Serialization visits each element twice,
+/// first to measure offsets and then to write it,
+/// so it is implemented for slices, arrays and vectors of references.
+///
+/// Layout differs from `[F]`, changing formula of a field
+/// between the two changes its wire format.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut buffer = [0u8; 1024];
+///
+/// let names = ["alice", "bob", "carol"];
+/// let (size, root) = serialize::<Indexed<As<str>>, _>(&names, &mut buffer).unwrap();
+/// let lazy = deserialize_with_size::<Indexed<As<str>>, Lazy<Indexed<As<str>>>>(
+///     &buffer[..size],
+///     root,
+/// )
+/// .unwrap();
+///
+/// assert_eq!(lazy.len().unwrap(), 3);
+/// assert_eq!(lazy.get_elem::<&str>(2).unwrap().unwrap(), "carol");
+/// assert!(lazy.get_elem::<&str>(3).is_none());
+/// ```
+pub struct Indexed<F: ?Sized> {
+    marker: PhantomData<fn(&F) -> &F>,
+}
+
+impl<F> Formula for Indexed<F>
+where
+    F: Formula + ?Sized,
+{
+    const MAX_STACK_SIZE: Option<usize> = None;
+    const EXACT_SIZE: bool = false;
+    const HEAPLESS: bool = F::HEAPLESS;
+}
+
+impl<F> BareFormula for Indexed<F> where F: Formula + ?Sized {}
+
+/// Unsized elements are not prefixed with their size,
+/// table of offsets bounds them.
+#[inline(always)]
+const fn element_is_last<F>() -> bool
+where
+    F: Formula + ?Sized,
+{
+    F::MAX_STACK_SIZE.is_none()
+}
+
+/// Writes number of elements, table of their offsets and the elements.
+///
+/// Offset of an element is the stack size of elements before it and itself.
+/// First element is written first, so it is read first from the end of the stack.
+#[inline]
+fn write_indexed<F, I, B>(iter: I, sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+where
+    F: Formula + ?Sized,
+    I: ExactSizeIterator + Clone,
+    I::Item: Serialize<F>,
+    B: Buffer,
+{
+    let len = iter.len();
+    write_field::<FixedUsize, _, _>(len, sizes, buffer.reborrow(), true)?;
+
+    // Elements are measured where they will be written after the table,
+    // since size of references may depend on their address.
+    let mut measured = Sizes {
+        heap: sizes.heap,
+        stack: sizes.stack + len * SIZE_STACK,
+    };
+    let start = measured.stack;
+    for elem in iter.clone() {
+        match write_field::<F, _, _>(elem, &mut measured, DryBuffer, element_is_last::<F>()) {
+            Ok(()) => {}
+            Err(never) => match never {},
+        }
+        write_field::<FixedUsize, _, _>(measured.stack - start, sizes, buffer.reborrow(), true)?;
+    }
+
+    for elem in iter {
+        write_field::<F, _, _>(elem, sizes, buffer.reborrow(), element_is_last::<F>())?;
+    }
+
+    debug_assert_eq!(*sizes, measured);
+    Ok(())
+}
+
+impl<'ser, F, T> Serialize<Indexed<F>> for &'ser [T]
+where
+    F: Formula + ?Sized,
+    &'ser T: Serialize<F>,
+{
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_indexed::<F, _, _>(self.iter(), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        None
+    }
+}
+
+impl<'ser, F, T, const N: usize> Serialize<Indexed<F>> for &'ser [T; N]
+where
+    F: Formula + ?Sized,
+    &'ser T: Serialize<F>,
+{
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_indexed::<F, _, _>(self.iter(), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        None
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'ser, F, T> Serialize<Indexed<F>> for &'ser Vec<T>
+where
+    F: Formula + ?Sized,
+    &'ser T: Serialize<F>,
+{
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_indexed::<F, _, _>(self.iter(), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        None
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<F, T> Serialize<Indexed<F>> for Vec<T>
+where
+    F: Formula + ?Sized,
+    for<'ser> &'ser T: Serialize<F>,
+{
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_indexed::<F, _, _>(self.iter(), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        None
+    }
+}
+
+/// Number of elements and deserializer over the table and the elements.
+#[inline(always)]
+fn read_header(mut de: Deserializer) -> Result<(usize, Deserializer), DeserializeError> {
+    let len = de.read_value::<FixedUsize, usize>(true)?;
+    match len.checked_mul(SIZE_STACK) {
+        Some(table) if table <= de.stack() => Ok((len, de)),
+        _ => Err(DeserializeError::WrongLength),
+    }
+}
+
+/// Deserializer of element at `idx` that is less than `len`.
+#[inline(always)]
+fn element<'de>(
+    de: &Deserializer<'de>,
+    len: usize,
+    idx: usize,
+) -> Result<Deserializer<'de>, DeserializeError> {
+    debug_assert!(idx < len);
+
+    let offset = |idx: usize| {
+        de.sized_elements(SIZE_STACK, idx, idx + 1)
+            .read_value::<FixedUsize, usize>(true)
+    };
+
+    let start = match idx {
+        0 => 0,
+        idx => offset(idx - 1)?,
+    };
+    let end = offset(idx)?;
+
+    let table = len * SIZE_STACK;
+    if start > end || end > de.stack() - table {
+        return Err(DeserializeError::WrongLength);
+    }
+
+    let elements = de.sized_elements(1, table, de.stack());
+    Ok(elements.sized_elements(1, start, end))
+}
+
+impl<'de, F> Lazy<'de, Indexed<F>>
+where
+    F: Formula + ?Sized,
+{
+    /// Returns number of elements in the slice.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if number of elements cannot be read.
+    #[inline(always)]
+    pub fn len(&self) -> Result<usize, DeserializeError> {
+        let (len, _) = read_header(self.deserializer())?;
+        Ok(len)
+    }
+
+    /// Returns `true` if the slice has no elements.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Lazy::len`].
+    #[inline(always)]
+    pub fn is_empty(&self) -> Result<bool, DeserializeError> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Deserialize element at specified index.
+    /// Returns `None` if index is out of bounds.
+    ///
+    /// Reads two offsets from the table and the element,
+    /// other elements are not read.
+    #[inline]
+    pub fn get_elem<T>(&self, idx: usize) -> Option<Result<T, DeserializeError>>
+    where
+        T: Deserialize<'de, F>,
+    {
+        let (len, de) = match read_header(self.deserializer()) {
+            Ok(header) => header,
+            Err(err) => return Some(Err(err)),
+        };
+        if idx >= len {
+            return None;
+        }
+        Some(element(&de, len, idx).and_then(<T as Deserialize<'de, F>>::deserialize))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'de, F, T> Deserialize<'de, Indexed<F>> for Vec<T>
+where
+    F: Formula + ?Sized,
+    T: Deserialize<'de, F>,
+{
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        let mut vec = Vec::new();
+        <Self as Deserialize<'de, Indexed<F>>>::deserialize_in_place(&mut vec, de)?;
+        Ok(vec)
+    }
+
+    #[inline]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        let (len, de) = read_header(de)?;
+        self.clear();
+        self.reserve(len);
+        for idx in 0..len {
+            let elem = element(&de, len, idx)?;
+            self.push(<T as Deserialize<'de, F>>::deserialize(elem)?);
+        }
+        Ok(())
+    }
+}
@@ -1,12 +1,15 @@
 use core::{
     any::type_name,
+    cmp::Ordering,
     fmt::{self, Debug},
     marker::PhantomData,
+    ops::{Bound, RangeBounds},
 };
 
 use crate::{
     deserialize::{DeIter, Deserialize, DeserializeError, Deserializer, SizedDeIter},
     formula::{unwrap_size, BareFormula, Formula},
+    size::FixedUsize,
 };
 
 /// Wrapper for lazy deserialization.
@@ -30,7 +33,25 @@ where
 
 impl<'de, F> Lazy<'de, F>
 where
-    F: BareFormula + ?Sized,
+    F: ?Sized,
+{
+    #[inline(always)]
+    pub(crate) fn new(de: Deserializer<'de>) -> Self {
+        Lazy {
+            de,
+            marker: PhantomData,
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn deserializer(&self) -> Deserializer<'de> {
+        self.de.clone()
+    }
+}
+
+impl<'de, F> Lazy<'de, F>
+where
+    F: Formula + ?Sized,
 {
     /// Deserialize the lazy value.
     ///
@@ -106,6 +127,21 @@ where
     /// assert!(iter.next().is_none());
     /// ```
     ///
+    /// Iterator is double-ended and exact-sized.
+    ///
+    /// ```
+    /// # use alkahest::*;
+    /// let mut buffer = [0u8; 1024];
+    ///
+    /// let (size, root) = serialize::<[Ref<str>], _>(["qwe", "rty", "uio"], &mut buffer).unwrap();
+    /// let seq = deserialize_with_size::<[Ref<str>], Lazy<[Ref<str>]>>(&buffer[..size], root).unwrap();
+    /// let mut iter = seq.sized_iter::<&str>();
+    /// assert_eq!(iter.len(), 3);
+    /// assert_eq!(iter.next_back().unwrap().unwrap(), "uio");
+    /// assert_eq!(iter.next().unwrap().unwrap(), "qwe");
+    /// assert_eq!(iter.len(), 1);
+    /// ```
+    ///
     /// `sized_iter` cannot be used to deserialize slice of unsized formulas.
     /// Attempt to use unsized formula will result in compile error.
     ///
@@ -158,6 +194,37 @@ where
     /// assert_eq!(iter.next().unwrap().unwrap(), "rty");
     /// assert!(iter.next().is_none());
     /// ```
+    ///
+    /// For sized formulas [`DeIter::into_sized`] converts iterator
+    /// into double-ended and exact-sized one, same as [`Lazy::sized_iter`] returns.
+    ///
+    /// ```
+    /// # use alkahest::*;
+    /// let mut buffer = [0u8; 1024];
+    ///
+    /// let (size, root) = serialize::<[Ref<str>], _>(["qwe", "rty", "uio"], &mut buffer).unwrap();
+    /// let seq = deserialize_with_size::<[Ref<str>], Lazy<[Ref<str>]>>(&buffer[..size], root).unwrap();
+    /// let mut iter = seq.iter::<&str>().into_sized();
+    /// assert_eq!(iter.len(), 3);
+    /// assert_eq!(iter.next_back().unwrap().unwrap(), "uio");
+    /// assert_eq!(iter.next().unwrap().unwrap(), "qwe");
+    /// assert_eq!(iter.len(), 1);
+    /// ```
+    ///
+    /// Unsized elements are prefixed with their size,
+    /// so they can be iterated only forward and their number is not known.
+    /// Attempt to convert iterator over unsized formulas will result in compile error.
+    /// Use `[Ref<F>]` formula to store unsized elements with fixed stride.
+    ///
+    /// ```compile_fail
+    /// # use alkahest::*;
+    /// let mut buffer = [0u8; 1024];
+    ///
+    /// let (size, root) = serialize::<[As<str>], _>(["qwe", "rty"], &mut buffer).unwrap();
+    /// let seq = deserialize_with_size::<[As<str>], Lazy<[As<str>]>>(&buffer[..size], root).unwrap();
+    /// let mut iter = seq.iter::<&str>().into_sized();
+    /// assert_eq!(iter.next_back().unwrap().unwrap(), "rty");
+    /// ```
     #[inline(always)]
     pub fn iter<T>(&self) -> DeIter<'de, F, T>
     where
@@ -165,6 +232,220 @@ where
     {
         self.de.clone().into_unsized_iter()
     }
+
+    /// Returns number of elements in the slice.
+    ///
+    /// `len` and other random-access methods cannot be used
+    /// with slice of unsized formulas.
+    /// Attempt to use unsized formula will result in compile error.
+    /// Slices of unsized formulas have no offset table.
+    /// To get random access, store unsized elements with [`Indexed<F>`](crate::Indexed)
+    /// formula that writes offset of each element,
+    /// or with `[Ref<F>]` formula, each element of it is a reference of fixed size.
+    /// Note that changing the formula changes the wire format.
+    ///
+    /// ```compile_fail
+    /// # use alkahest::*;
+    /// let mut buffer = [0u8; 1024];
+    ///
+    /// let (size, root) = serialize::<[As<str>], _>(["qwe", "rty"], &mut buffer).unwrap();
+    /// let lazy = deserialize_with_size::<[As<str>], Lazy<[As<str>]>>(&buffer[..size], root).unwrap();
+    /// assert_eq!(lazy.len().unwrap(), 2);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Slice of zero-sized elements stores the number of elements.
+    /// Returns `DeserializeError` if it cannot be read.
+    #[inline(always)]
+    pub fn len(&self) -> Result<usize, DeserializeError> {
+        match const { unwrap_size(F::MAX_STACK_SIZE) } {
+            0 => self.de.clone().read_value::<FixedUsize, usize>(true),
+            element_size => Ok(self.de.stack() / element_size),
+        }
+    }
+
+    /// Returns `true` if the slice has no elements.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Lazy::len`].
+    #[inline(always)]
+    pub fn is_empty(&self) -> Result<bool, DeserializeError> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Deserialize element at specified index.
+    /// Returns `None` if index is out of bounds.
+    ///
+    /// Named `get_elem` because [`Lazy::get`] deserializes the whole value
+    /// for any formula, including slices.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use alkahest::*;
+    /// let mut buffer = [0u8; 1024];
+    ///
+    /// let scores = [(100u32, "alice"), (90, "bob"), (80, "carol")];
+    /// let (size, root) = serialize::<[(u32, Ref<str>)], _>(scores, &mut buffer).unwrap();
+    /// let lazy = deserialize_with_size::<[(u32, Ref<str>)], Lazy<[(u32, Ref<str>)]>>(&buffer[..size], root).unwrap();
+    ///
+    /// assert_eq!(lazy.len().unwrap(), 3);
+    /// assert_eq!(lazy.get_elem::<(u32, &str)>(1).unwrap().unwrap(), (90, "bob"));
+    /// assert!(lazy.get_elem::<(u32, &str)>(3).is_none());
+    /// ```
+    #[inline(always)]
+    pub fn get_elem<T>(&self, idx: usize) -> Option<Result<T, DeserializeError>>
+    where
+        T: Deserialize<'de, F>,
+    {
+        match self.len() {
+            Err(err) => Some(Err(err)),
+            Ok(len) if idx >= len => None,
+            Ok(_) => Some(self.elem(idx)),
+        }
+    }
+
+    #[inline(always)]
+    fn elem<T>(&self, idx: usize) -> Result<T, DeserializeError>
+    where
+        T: Deserialize<'de, F>,
+    {
+        let element_size = const { unwrap_size(F::MAX_STACK_SIZE) };
+        let de = self.de.sized_elements(element_size, idx, idx + 1);
+        <T as Deserialize<'de, F>>::deserialize(de)
+    }
+
+    /// Returns lazy slice of elements in specified range.
+    ///
+    /// Using zero-sized element formula is a compile-time error.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError::OutOfBounds` if range is out of bounds
+    /// of the slice.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use alkahest::*;
+    /// let mut buffer = [0u8; 1024];
+    ///
+    /// let (size, root) = serialize::<[u32], _>(0..100u32, &mut buffer).unwrap();
+    /// let lazy = deserialize_with_size::<[u32], Lazy<[u32]>>(&buffer[..size], root).unwrap();
+    ///
+    /// let page = lazy.slice(20..30).unwrap();
+    /// assert_eq!(page.len().unwrap(), 10);
+    /// assert_eq!(page.get::<Vec<u32>>().unwrap(), (20..30).collect::<Vec<u32>>());
+    /// assert!(lazy.slice(90..110).is_err());
+    /// ```
+    #[inline]
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> Result<Self, DeserializeError> {
+        let element_size = const {
+            let element_size = unwrap_size(F::MAX_STACK_SIZE);
+            assert!(
+                element_size != 0,
+                "Slicing `Lazy` of zero-sized elements is not supported"
+            );
+            element_size
+        };
+
+        let len = self.de.stack() / element_size;
+        let start = match range.start_bound() {
+            Bound::Included(&start) => Some(start),
+            Bound::Excluded(&start) => start.checked_add(1),
+            Bound::Unbounded => Some(0),
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end.checked_add(1),
+            Bound::Excluded(&end) => Some(end),
+            Bound::Unbounded => Some(len),
+        };
+        match (start, end) {
+            (Some(start), Some(end)) if start <= end && end <= len => {
+                Ok(Lazy::new(self.de.sized_elements(element_size, start, end)))
+            }
+            _ => Err(DeserializeError::OutOfBounds),
+        }
+    }
+
+    /// Binary searches sorted slice with a comparator function.
+    /// Deserializes only visited elements.
+    ///
+    /// Returns `Ok(idx)` of matching element or `Err(idx)`
+    /// where matching element could be inserted, like [`slice::binary_search_by`].
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if deserialization of visited element fails.
+    #[inline]
+    pub fn binary_search_by<T, Fun>(
+        &self,
+        mut f: Fun,
+    ) -> Result<Result<usize, usize>, DeserializeError>
+    where
+        T: Deserialize<'de, F>,
+        Fun: FnMut(&T) -> Ordering,
+    {
+        let mut low = 0;
+        let mut high = self.len()?;
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match f(&self.elem::<T>(mid)?) {
+                Ordering::Less => low = mid + 1,
+                Ordering::Greater => high = mid,
+                Ordering::Equal => return Ok(Ok(mid)),
+            }
+        }
+        Ok(Err(low))
+    }
+
+    /// Binary searches sorted slice with a key extraction function.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if deserialization of visited element fails.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use alkahest::*;
+    /// let mut buffer = [0u8; 1024];
+    ///
+    /// let scores = [(100u32, "alice"), (90, "bob"), (80, "carol")];
+    /// let (size, root) = serialize::<[(u32, Ref<str>)], _>(scores, &mut buffer).unwrap();
+    /// let lazy = deserialize_with_size::<[(u32, Ref<str>)], Lazy<[(u32, Ref<str>)]>>(&buffer[..size], root).unwrap();
+    ///
+    /// let found = lazy.binary_search_by_key(&core::cmp::Reverse(85), |&(score, _): &(u32, &str)| core::cmp::Reverse(score));
+    /// assert_eq!(found.unwrap(), Err(2));
+    /// ```
+    #[inline]
+    pub fn binary_search_by_key<T, K, Fun>(
+        &self,
+        key: &K,
+        mut f: Fun,
+    ) -> Result<Result<usize, usize>, DeserializeError>
+    where
+        T: Deserialize<'de, F>,
+        K: Ord,
+        Fun: FnMut(&T) -> K,
+    {
+        self.binary_search_by(|elem: &T| f(elem).cmp(key))
+    }
+
+    /// Binary searches sorted slice for a value.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if deserialization of visited element fails.
+    #[inline]
+    pub fn binary_search<T>(&self, value: &T) -> Result<Result<usize, usize>, DeserializeError>
+    where
+        T: Deserialize<'de, F> + Ord,
+    {
+        self.binary_search_by(|elem: &T| elem.cmp(value))
+    }
 }
 
 impl<'de, 'fe: 'de, F> Deserialize<'fe, F> for Lazy<'de, F>
@@ -173,10 +454,7 @@ where
 {
     #[inline(always)]
     fn deserialize(de: Deserializer<'fe>) -> Result<Self, DeserializeError> {
-        Ok(Lazy {
-            de,
-            marker: PhantomData,
-        })
+        Ok(Lazy::new(de))
     }
 
     #[inline(always)]
@@ -6,7 +6,8 @@
 //! For those use cases, see `advanced` module.
 
 #![cfg_attr(not(feature = "std"), no_std)]
-#![forbid(unsafe_code)]
+#![cfg_attr(not(feature = "mmap"), forbid(unsafe_code))]
+#![cfg_attr(feature = "mmap", deny(unsafe_code))]
 #![deny(missing_docs)]
 
 #[cfg(test)]
@@ -15,12 +16,18 @@ extern crate self as alkahest;
 #[cfg(feature = "alloc")]
 extern crate alloc;
 
+mod archive;
 mod array;
 mod r#as;
+mod batch;
+mod bits;
 mod buffer;
 mod bytes;
+mod checksum;
 mod deserialize;
+mod endian;
 mod formula;
+mod indexed;
 mod iter;
 mod lazy;
 mod option;
@@ -34,6 +41,8 @@ mod slice;
 mod str;
 mod tuple;
 mod vlq;
+mod width;
+mod zigzag;
 
 #[cfg(test)]
 mod tests;
@@ -50,33 +59,76 @@ mod string;
 #[cfg(feature = "bincoded")]
 mod bincoded;
 
+#[cfg(feature = "pod")]
+mod pod;
+
+#[cfg(feature = "pod")]
+mod aligned;
+
+#[cfg(feature = "tokio")]
+mod codec;
+
+#[cfg(feature = "bumpalo")]
+mod arena;
+
+#[cfg(all(feature = "bytes", feature = "std"))]
+mod vectored;
+
+#[cfg(feature = "mmap")]
+mod mmap;
+
+#[cfg(feature = "rayon")]
+mod par;
+
+#[cfg(any(feature = "lz4", feature = "zstd", feature = "deflate"))]
+mod compressed;
+
 pub use crate::{
+    archive::{Archive, ARCHIVE_HEADER_SIZE, ARCHIVE_MAGIC, ARCHIVE_VERSION},
+    batch::PacketIter,
+    bits::{BitField, BitSet, Bits},
     buffer::BufferExhausted,
     bytes::Bytes,
+    checksum::{read_packet_checked, write_packet_checked, CHECKSUM_SIZE},
     deserialize::{
-        deserialize, deserialize_in_place, deserialize_in_place_with_size, deserialize_with_size,
-        DeIter, Deserialize, DeserializeError,
+        deserialize, deserialize_array, deserialize_in_place, deserialize_in_place_with_size,
+        deserialize_with_size, DeIter, Deserialize, DeserializeError,
     },
+    endian::{Be, Le},
     formula::Formula,
+    indexed::Indexed,
     iter::SerIter,
     lazy::Lazy,
     packet::{
         packet_size, read_packet, read_packet_in_place, read_packet_size, write_packet,
-        write_packet_into, write_packet_unchecked,
+        write_packet_into, write_packet_unchecked, Packet, PacketFormula,
     },
     r#as::As,
     reference::Ref,
     serialize::{
-        serialize, serialize_or_size, serialize_unchecked, serialized_size, BufferSizeRequired,
-        Serialize, SerializeRef,
+        serialize, serialize_or_size, serialize_to_array, serialize_unchecked, serialized_size,
+        BufferSizeRequired, Serialize, SerializeRef,
     },
     size::{FixedIsize, FixedUsize},
     skip::Skip,
-    vlq::Vlq,
+    vlq::{Vlq, VlqString, VlqVec},
+    width::Width,
+    zigzag::{Delta, ZigZag},
 };
 
 #[cfg(feature = "alloc")]
-pub use crate::{packet::write_packet_to_vec, serialize::serialize_to_vec};
+pub use crate::{
+    archive::write_archive_to_vec, batch::PacketBatch, checksum::write_packet_checked_to_vec,
+    packet::write_packet_to_vec, serialize::serialize_to_vec,
+};
+
+#[cfg(feature = "std")]
+pub use crate::{
+    archive::write_archive,
+    checksum::write_packet_checked_to_writer,
+    packet::{write_packet_to_writer, PacketReader},
+    serialize::serialize_to_writer,
+};
 
 #[cfg(feature = "derive")]
 pub use alkahest_proc::{alkahest, Deserialize, Formula, Serialize, SerializeRef};
@@ -84,24 +136,66 @@ pub use alkahest_proc::{alkahest, Deserialize, Formula, Serialize, SerializeRef}
 #[cfg(feature = "bincoded")]
 pub use bincoded::{Bincode, Bincoded};
 
+#[cfg(feature = "pod")]
+pub use crate::{
+    aligned::Aligned,
+    pod::{PodSlice, SwapBytes},
+};
+
+#[cfg(feature = "bytes")]
+pub use crate::packet::read_packet_shared;
+
+#[cfg(all(feature = "bytes", feature = "std"))]
+pub use crate::vectored::{write_packet_vectored, VectoredPacket};
+
+#[cfg(feature = "tokio")]
+pub use codec::AlkahestCodec;
+
+#[cfg(feature = "bumpalo")]
+pub use arena::{
+    deserialize_in_arena, deserialize_with_arena, read_packet_in_arena, read_packet_with_arena,
+};
+
+#[cfg(feature = "mmap")]
+pub use mmap::MmapArchive;
+
+#[cfg(any(feature = "lz4", feature = "zstd", feature = "deflate"))]
+pub use compressed::{Compressed, Compression, MAX_DECOMPRESSED_SIZE};
+
+#[cfg(feature = "lz4")]
+pub use compressed::Lz4;
+
+#[cfg(feature = "zstd")]
+pub use compressed::Zstd;
+
+#[cfg(feature = "deflate")]
+pub use compressed::Deflate;
+
+#[cfg(feature = "rayon")]
+pub use par::{serialize_par, serialize_par_to_vec};
+
 /// This module contains types and functions for manual implementations of
 /// `Serialize` and `Deserialize` traits.
 pub mod advanced {
     pub use crate::{
         buffer::{Buffer, CheckedFixedBuffer, MaybeFixedBuffer},
+        checksum::crc32c,
         deserialize::Deserializer,
         formula::{reference_size, BareFormula},
         iter::{default_iter_fast_sizes, deserialize_extend_iter, deserialize_from_iter},
         serialize::{
             field_size_hint, formula_fast_sizes, slice_writer, write_array, write_bytes,
-            write_exact_size_field, write_field, write_ref, write_reference, write_slice, Sizes,
-            SliceWriter,
+            write_exact_size_field, write_field, write_field_with, write_ref, write_reference,
+            write_reference_with, write_slice, Sizes, SliceWriter,
         },
         size::{FixedIsize, FixedIsizeType},
     };
 
     #[cfg(feature = "alloc")]
     pub use crate::buffer::VecBuffer;
+
+    #[cfg(feature = "bytes")]
+    pub use crate::{buffer::BytesMutBuffer, serialize::write_shared_bytes};
 }
 
 /// Private module for macros to use.
@@ -110,31 +204,50 @@ pub mod advanced {
 pub mod private {
     pub use {
         bool,
-        core::{convert::Into, debug_assert_eq, option::Option, result::Result},
-        u32, u8, usize,
+        core::{assert, convert::Into, mem::size_of, option::Option, result::Result},
+        str, u128, u32, u8, usize,
     };
 
     pub use crate::{
+        bits::{
+            bytes_for, check_unused_bits, pack_bits, read_bit_field, unpack_bits, write_bit_field,
+            BitField,
+        },
         buffer::Buffer,
         deserialize::{Deserialize, DeserializeError, Deserializer},
         formula::{max_size, sum_size, BareFormula, Formula},
+        lazy::Lazy,
         serialize::{
             formula_fast_sizes, write_exact_size_field, write_field, Serialize, SerializeRef, Sizes,
         },
+        size::FixedUsize,
+        width::Width,
     };
 
+    #[cfg(feature = "pod")]
+    pub use crate::pod::{read_pod, write_pod, SwapBytes};
+
+    #[cfg(feature = "pod")]
+    pub use bytemuck::Pod;
+
     use core::marker::PhantomData;
 
     pub const VARIANT_SIZE: usize = core::mem::size_of::<u32>();
     pub const VARIANT_SIZE_OPT: Option<usize> = Some(VARIANT_SIZE);
 
-    pub struct WithFormula<F: Formula + ?Sized> {
-        marker: PhantomData<fn(&F) -> &F>,
+    /// Width of size prefixes of unsized fields of derived formula.
+    pub trait FieldWidth {
+        type Width: Width;
+    }
+
+    pub struct WithFormula<F: Formula + ?Sized, W: Width = FixedUsize> {
+        marker: PhantomData<fn(&F, W) -> &F>,
     }
 
-    impl<F> WithFormula<F>
+    impl<F, W> WithFormula<F, W>
     where
         F: Formula + ?Sized,
+        W: Width,
     {
         #[inline(always)]
         pub fn write_field<T, B>(
@@ -148,7 +261,7 @@ pub mod private {
             B: Buffer,
             T: Serialize<F>,
         {
-            crate::serialize::write_field(value, sizes, buffer, last)
+            crate::serialize::write_field_with::<F, W, T, B>(value, sizes, buffer, last)
         }
 
         #[inline(always)]
@@ -161,7 +274,7 @@ pub mod private {
             F: Formula,
             T: Deserialize<'de, F>,
         {
-            de.read_value::<F, T>(last)
+            de.read_value_with::<F, W, T>(last)
         }
 
         #[inline(always)]
@@ -175,7 +288,7 @@ pub mod private {
             F: Formula,
             T: Deserialize<'de, F>,
         {
-            de.read_in_place::<F, T>(place, last)
+            de.read_in_place_with::<F, W, T>(place, last)
         }
 
         #[inline(always)]
@@ -187,11 +300,99 @@ pub mod private {
         }
     }
 
+    #[inline(always)]
+    pub fn lazy_deserializer<'de, F: ?Sized>(lazy: &Lazy<'de, F>) -> Deserializer<'de> {
+        lazy.deserializer()
+    }
+
+    #[inline(always)]
+    pub fn skip_field<F: Formula + ?Sized, W: Width>(
+        de: &mut Deserializer<'_>,
+    ) -> Result<(), DeserializeError> {
+        de.skip_values_with::<F, W>(1)
+    }
+
+    #[inline(always)]
+    pub fn read_lazy_field<'de, F: Formula + ?Sized, W: Width>(
+        de: &mut Deserializer<'de>,
+        last: bool,
+    ) -> Result<Lazy<'de, F>, DeserializeError> {
+        Ok(Lazy::new(de.sub_value_with::<F, W>(last)?))
+    }
+
+    const fn str_eq(a: &str, b: &str) -> bool {
+        let a = a.as_bytes();
+        let b = b.as_bytes();
+        if a.len() != b.len() {
+            return false;
+        }
+        let mut i = 0;
+        while i < a.len() {
+            if a[i] != b[i] {
+                return false;
+            }
+            i += 1;
+        }
+        true
+    }
+
+    const fn name_index(names: &[&str], name: &str) -> Option<usize> {
+        let mut i = 0;
+        while i < names.len() {
+            if str_eq(names[i], name) {
+                return Some(i);
+            }
+            i += 1;
+        }
+        None
+    }
+
+    /// Checks that field or variant is found in the formula.
+    #[must_use]
+    pub const fn has_name(names: &[&str], name: &str) -> bool {
+        name_index(names, name).is_some()
+    }
+
+    /// Checks that field is placed at specified index in the formula.
+    /// Fields not found in the formula are reported elsewhere.
+    #[must_use]
+    pub const fn field_order_matches(fields: &[&str], name: &str, idx: usize) -> bool {
+        match name_index(fields, name) {
+            Some(found) => found == idx,
+            None => true,
+        }
+    }
+
+    /// Returns fields of the formula's variant.
+    /// Variants not found in the formula are reported elsewhere.
+    #[must_use]
+    pub const fn variant_fields<'a>(
+        variants: &[&str],
+        fields: &'a [&'a [&'a str]],
+        name: &str,
+    ) -> Option<&'a [&'a str]> {
+        match name_index(variants, name) {
+            Some(idx) => Some(fields[idx]),
+            None => None,
+        }
+    }
+
+    /// Returns index of the variant in the formula.
+    /// Used in constant context, so panics become compile errors.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub const fn variant_index(variants: &[&str], name: &str) -> u32 {
+        match name_index(variants, name) {
+            Some(idx) => idx as u32,
+            None => panic!("Variant is not found in formula"),
+        }
+    }
+
     #[must_use]
     #[inline(always)]
-    pub fn with_formula<F: Formula + ?Sized, L: Formula + ?Sized>(
+    pub fn with_formula<F: FieldWidth + ?Sized, L: Formula + ?Sized>(
         _: impl FnOnce(&F) -> &L,
-    ) -> WithFormula<L> {
+    ) -> WithFormula<L, F::Width> {
         WithFormula {
             marker: PhantomData,
         }
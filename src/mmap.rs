@@ -0,0 +1,57 @@
+//! Entry point for archives in memory-mapped files.
+//!
+//! This is the only module of the crate that uses `unsafe` code.
+
+#![allow(unsafe_code)]
+
+use std::{fs::File, io};
+
+use memmap2::Mmap;
+
+use crate::{archive::Archive, formula::Formula};
+
+/// Archive over memory-mapped file.
+///
+/// Opened with [`Archive::open`].
+pub type MmapArchive<F> = Archive<Mmap, F>;
+
+impl<F> Archive<Mmap, F>
+where
+    F: Formula + ?Sized,
+{
+    /// Maps the file into memory and opens archive stored in it.
+    /// Validates the header and bounds of the root packet,
+    /// pages of the file are read only when accessed.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while the archive is alive,
+    /// neither by this process nor by any other.
+    /// Values deserialized from the archive borrow bytes of the mapping,
+    /// so a change of the file is observed through them,
+    /// and truncation makes access to the mapping fault.
+    /// See [`memmap2::Mmap::map`].
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the file cannot be mapped.
+    /// Invalid archive is reported with [`io::ErrorKind::InvalidData`] kind.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use alkahest::*;
+    /// let file = std::fs::File::open("values.alk")?;
+    ///
+    /// // SAFETY: The file is not modified while the archive is alive.
+    /// let archive = unsafe { Archive::<_, [u32]>::open(&file)? };
+    /// let values = archive.lazy();
+    /// # let _ = values;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub unsafe fn open(file: &File) -> io::Result<Self> {
+        // SAFETY: Caller upholds the contract of `Mmap::map`.
+        let map = unsafe { Mmap::map(file)? };
+        Archive::new(map).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
@@ -1,11 +1,141 @@
+use core::marker::PhantomData;
+
+#[cfg(feature = "std")]
+use crate::{
+    buffer::{WriteBuffer, WriteState},
+    lazy::Lazy,
+};
+
 use crate::{
     buffer::{Buffer, BufferExhausted, CheckedFixedBuffer, DryBuffer, VecBuffer},
-    deserialize::{read_reference, Deserialize, DeserializeError, Deserializer},
-    formula::{reference_size, Formula},
-    serialize::{write_ref, write_reference, Serialize, Sizes},
-    size::{FixedUsize, SIZE_STACK},
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::{unwrap_size, Formula},
+    serialize::{fixed_width, write_ref, write_reference_with, Serialize, Sizes},
+    size::FixedUsize,
+    width::Width,
 };
 
+/// Formula of a packet.
+///
+/// Packet starts with a header that contains address of the value
+/// and its size, unless the value formula has exact size.
+/// This trait selects formula of the value and [`Width`] of the header.
+///
+/// Implemented for every [`Formula`] with [`FixedUsize`] header,
+/// so packet functions accept value formulas directly.
+/// Use [`Packet`] to write header with another width.
+pub trait PacketFormula {
+    /// Formula of the value in the packet.
+    type Formula: Formula + ?Sized;
+
+    /// Width of the address and size in the packet header.
+    /// Must have fixed size.
+    type Width: Width;
+}
+
+impl<F> PacketFormula for F
+where
+    F: Formula + ?Sized,
+{
+    type Formula = F;
+    type Width = FixedUsize;
+}
+
+/// Packet formula with header of specified [`Width`].
+///
+/// Pass in place of the value formula to packet functions.
+/// `Packet<F>` writes the same bytes as `F`.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut buffer = [0u8; 64];
+/// let size = write_packet_unchecked::<Packet<str, u8>, _>("hello", &mut buffer);
+/// assert_eq!(size, 2 + 5);
+/// assert_eq!(read_packet_size::<Packet<str, u8>>(&buffer), Some(size));
+///
+/// let (value, _) = read_packet::<Packet<str, u8>, &str>(&buffer[..size]).unwrap();
+/// assert_eq!(value, "hello");
+/// ```
+pub struct Packet<F: ?Sized, W = FixedUsize> {
+    marker: PhantomData<fn(&F, W) -> &F>,
+}
+
+impl<F, W> PacketFormula for Packet<F, W>
+where
+    F: Formula + ?Sized,
+    W: Width,
+{
+    type Formula = F;
+    type Width = W;
+}
+
+/// Returns size of the packet header.
+///
+/// # Panics
+///
+/// Panics if width of the header does not have fixed size.
+/// Evaluated in constant context, so panics become compile errors.
+#[must_use]
+#[inline(always)]
+pub(crate) const fn header_size<P>() -> usize
+where
+    P: PacketFormula + ?Sized,
+{
+    let width = fixed_width::<P::Width>();
+    if <P::Formula as Formula>::EXACT_SIZE {
+        width
+    } else {
+        width * 2
+    }
+}
+
+/// Writes packet header into the start of the buffer.
+/// Calls [`Buffer::width_overflow`] if size or address
+/// does not fit the header width.
+#[inline(always)]
+fn write_header<P, B>(size: usize, address: usize, mut buffer: B) -> Result<(), B::Error>
+where
+    P: PacketFormula + ?Sized,
+    B: Buffer,
+{
+    let header_size = const { header_size::<P>() };
+    let fits = match buffer.reserve_heap_at(0, 0, header_size)? {
+        [] => true,
+        reserved => write_reference_with::<P::Formula, P::Width, _>(
+            size,
+            address,
+            &mut Sizes { heap: 0, stack: 0 },
+            CheckedFixedBuffer::new(reserved),
+        )
+        .is_ok(),
+    };
+    if !fits {
+        buffer.width_overflow()?;
+    }
+    Ok(())
+}
+
+/// Reads address and size of the value from packet header.
+/// `len` is the length of the input after the header.
+#[inline(always)]
+fn read_header<P>(header: &[u8], len: usize) -> Result<(usize, usize), DeserializeError>
+where
+    P: PacketFormula + ?Sized,
+{
+    let mut de = Deserializer::new(header.len(), header)?;
+    if <P::Formula as Formula>::EXACT_SIZE {
+        let address = <P::Width as Width>::read(&mut de)?;
+        let size = unwrap_size(<P::Formula as Formula>::MAX_STACK_SIZE).min(len);
+        Ok((address, size))
+    } else {
+        let size = <P::Width as Width>::read(&mut de)?;
+        let address = <P::Width as Width>::read(&mut de)?;
+        Ok((address, size))
+    }
+}
+
 /// Returns the number of bytes required to write packet with the value.
 /// Note that value is consumed.
 ///
@@ -15,10 +145,10 @@ use crate::{
 #[inline(always)]
 pub fn packet_size<F, T>(value: T) -> usize
 where
-    F: Formula + ?Sized,
-    T: Serialize<F>,
+    F: PacketFormula + ?Sized,
+    T: Serialize<F::Formula>,
 {
-    match write_packet_into(value, DryBuffer) {
+    match write_packet_into::<F, T, _>(value, DryBuffer) {
         Ok(size) => size,
         Err(never) => match never {},
     }
@@ -29,26 +159,20 @@ where
 #[inline(always)]
 pub fn write_packet_into<F, T, B>(value: T, mut buffer: B) -> Result<usize, B::Error>
 where
-    F: Formula + ?Sized,
-    T: Serialize<F>,
+    F: PacketFormula + ?Sized,
+    T: Serialize<F::Formula>,
     B: Buffer,
 {
-    let reference_size = reference_size::<F>();
-    buffer.reserve_heap(0, 0, reference_size)?;
+    let header_size = const { header_size::<F>() };
+    buffer.reserve_heap_at(0, 0, header_size)?;
 
     let mut sizes = Sizes {
-        heap: reference_size,
+        heap: header_size,
         stack: 0,
     };
 
-    let size = write_ref(value, &mut sizes, buffer.reborrow())?;
-
-    match buffer.reserve_heap(0, 0, reference_size)? {
-        [] => {}
-        reserved => {
-            write_reference::<F, _>(size, sizes.heap, 0, 0, reserved).unwrap();
-        }
-    }
+    let size = write_ref::<F::Formula, T, _>(value, &mut sizes, buffer.reborrow())?;
+    write_header::<F, _>(size, sizes.heap, buffer)?;
 
     Ok(sizes.heap)
 }
@@ -66,8 +190,8 @@ where
 #[inline(always)]
 pub fn write_packet<F, T, B>(value: T, output: &mut [u8]) -> Result<usize, BufferExhausted>
 where
-    F: Formula + ?Sized,
-    T: Serialize<F>,
+    F: PacketFormula + ?Sized,
+    T: Serialize<F::Formula>,
 {
     write_packet_into::<F, T, _>(value, CheckedFixedBuffer::new(output))
 }
@@ -80,8 +204,8 @@ where
 #[inline(always)]
 pub fn write_packet_unchecked<F, T>(value: T, output: &mut [u8]) -> usize
 where
-    F: Formula + ?Sized,
-    T: Serialize<F>,
+    F: PacketFormula + ?Sized,
+    T: Serialize<F::Formula>,
 {
     match write_packet_into::<F, T, _>(value, output) {
         Ok(size) => size,
@@ -100,8 +224,8 @@ where
 #[inline(always)]
 pub fn write_packet_to_vec<F, T>(value: T, output: &mut alloc::vec::Vec<u8>) -> usize
 where
-    F: Formula + ?Sized,
-    T: Serialize<F>,
+    F: PacketFormula + ?Sized,
+    T: Serialize<F::Formula>,
 {
     match write_packet_into::<F, T, _>(value, VecBuffer::new(output)) {
         Ok(size) => size,
@@ -109,6 +233,70 @@ where
     }
 }
 
+/// Writes packet with the value into [`std::io::Write`].
+/// Returns the number of bytes written.
+///
+/// Packet starts with reference to the value, so its size must be known
+/// before anything is written.
+/// If value provides size hint, serialized data is passed
+/// to the writer in chunks as it is produced.
+/// Otherwise whole packet is serialized into memory first.
+///
+/// # Errors
+///
+/// Returns error if writer fails.
+/// Returns error of `InvalidData` kind if serialized size does not match
+/// size hint of the value, since header is already passed to the writer.
+/// Bytes passed to the writer do not form a valid packet in this case.
+#[cfg(feature = "std")]
+pub fn write_packet_to_writer<F, T, W>(value: T, writer: &mut W) -> std::io::Result<usize>
+where
+    F: PacketFormula + ?Sized,
+    T: Serialize<F::Formula>,
+    W: std::io::Write + ?Sized,
+{
+    let Some(promised) = <T as Serialize<F::Formula>>::size_hint(&value) else {
+        let mut output = alloc::vec::Vec::new();
+        let size = write_packet_to_vec::<F, T>(value, &mut output);
+        writer.write_all(&output[..size])?;
+        return Ok(size);
+    };
+
+    let header_size = const { header_size::<F>() };
+
+    let mut header = alloc::vec![0; header_size];
+    let address = header_size + promised.total();
+    if write_reference_with::<F::Formula, F::Width, _>(
+        promised.stack,
+        address,
+        &mut Sizes { heap: 0, stack: 0 },
+        CheckedFixedBuffer::new(&mut header),
+    )
+    .is_err()
+    {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "size or address does not fit reference width",
+        ));
+    }
+    writer.write_all(&header)?;
+
+    let mut sizes = Sizes::with_heap(header_size);
+    let mut state = WriteState::new(header_size);
+    let size =
+        write_ref::<F::Formula, T, _>(value, &mut sizes, WriteBuffer::new(writer, &mut state))?;
+    state.finish(writer)?;
+
+    if size != promised.stack || sizes.heap != address {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "serialized size does not match size hint",
+        ));
+    }
+
+    Ok(sizes.heap)
+}
+
 /// Reads size of the packet with value from the input.
 /// Returns `None` if the input is too short to determine the size.
 ///
@@ -119,44 +307,36 @@ where
 #[inline(always)]
 pub fn read_packet_size<F>(input: &[u8]) -> Option<usize>
 where
-    F: Formula + ?Sized,
+    F: PacketFormula + ?Sized,
 {
-    match F::MAX_STACK_SIZE {
-        Some(0) => Some(0),
-        _ => {
-            if input.len() < SIZE_STACK {
-                None
-            } else {
-                let mut bytes = [0u8; SIZE_STACK];
-                bytes.copy_from_slice(&input[..SIZE_STACK]);
-                let address =
-                    FixedUsize::from_le_bytes(bytes).expect("Value size can't fit `usize`");
-                Some(address.into())
-            }
-        }
+    // Packet always starts with the header, even for zero-sized values.
+    // Address in the header is the size of the packet.
+    let width = const { fixed_width::<F::Width>() };
+    if input.len() < width {
+        None
+    } else {
+        let mut de = Deserializer::new_unchecked(width, &input[..width]);
+        let address = <F::Width as Width>::read(&mut de).expect("Value size can't fit `usize`");
+        Some(address)
     }
 }
 
-/// Reads packet with value from the input.
-/// Returns deserialized value and number of bytes consumed.
-///
-/// # Errors
-///
-/// Returns `DeserializeError` if deserialization fails.
-#[must_use]
+/// Returns deserializer for the value in the packet
+/// and number of bytes the packet occupies.
 #[inline(always)]
-pub fn read_packet<'de, F, T>(input: &'de [u8]) -> Result<(T, usize), DeserializeError>
+pub(crate) fn packet_deserializer<F>(
+    input: &[u8],
+) -> Result<(Deserializer<'_>, usize), DeserializeError>
 where
-    F: Formula + ?Sized,
-    T: Deserialize<'de, F>,
+    F: PacketFormula + ?Sized,
 {
-    let reference_size = reference_size::<F>();
+    let header_size = const { header_size::<F>() };
 
-    if input.len() < reference_size {
+    if input.len() < header_size {
         return Err(DeserializeError::OutOfBounds);
     }
 
-    let (address, size) = read_reference::<F>(input, input.len() - reference_size);
+    let (address, size) = read_header::<F>(&input[..header_size], input.len() - header_size)?;
 
     if size > address {
         return Err(DeserializeError::WrongAddress);
@@ -166,8 +346,53 @@ where
         return Err(DeserializeError::OutOfBounds);
     }
 
-    let de = Deserializer::new_unchecked(size, &input[..address]);
-    let value = <T as Deserialize<'de, F>>::deserialize(de)?;
+    Ok((
+        Deserializer::new_unchecked(size, &input[..address]),
+        address,
+    ))
+}
+
+/// Reads packet with value from the input.
+/// Returns deserialized value and number of bytes consumed.
+///
+/// # Errors
+///
+/// Returns `DeserializeError` if deserialization fails.
+#[allow(clippy::double_must_use)]
+#[must_use]
+#[inline(always)]
+pub fn read_packet<'de, F, T>(input: &'de [u8]) -> Result<(T, usize), DeserializeError>
+where
+    F: PacketFormula + ?Sized,
+    T: Deserialize<'de, F::Formula>,
+{
+    let (de, address) = packet_deserializer::<F>(input)?;
+    let value = <T as Deserialize<'de, F::Formula>>::deserialize(de)?;
+
+    Ok((value, address))
+}
+
+/// Reads packet with value from the shared input buffer.
+/// Returns deserialized value and number of bytes consumed.
+///
+/// Values deserialized into [`bytes::Bytes`](::bytes::Bytes)
+/// with [`Bytes`](crate::Bytes) formula share the input buffer
+/// instead of copying.
+///
+/// # Errors
+///
+/// Returns `DeserializeError` if deserialization fails.
+#[cfg(feature = "bytes")]
+#[inline(always)]
+pub fn read_packet_shared<'de, F, T>(
+    input: &'de ::bytes::Bytes,
+) -> Result<(T, usize), DeserializeError>
+where
+    F: PacketFormula + ?Sized,
+    T: Deserialize<'de, F::Formula>,
+{
+    let (de, address) = packet_deserializer::<F>(input)?;
+    let value = <T as Deserialize<'de, F::Formula>>::deserialize(de.with_origin(input))?;
 
     Ok((value, address))
 }
@@ -179,6 +404,7 @@ where
 /// # Errors
 ///
 /// Returns `DeserializeError` if deserialization fails.
+#[allow(clippy::double_must_use)]
 #[must_use]
 #[inline(always)]
 pub fn read_packet_in_place<'de, F, T>(
@@ -186,27 +412,175 @@ pub fn read_packet_in_place<'de, F, T>(
     input: &'de [u8],
 ) -> Result<usize, DeserializeError>
 where
-    F: Formula + ?Sized,
-    T: Deserialize<'de, F> + ?Sized,
+    F: PacketFormula + ?Sized,
+    T: Deserialize<'de, F::Formula> + ?Sized,
 {
-    let reference_size = reference_size::<F>();
+    let (de, address) = packet_deserializer::<F>(input)?;
+    <T as Deserialize<'de, F::Formula>>::deserialize_in_place(place, de)?;
 
-    if input.len() < reference_size {
-        return Err(DeserializeError::OutOfBounds);
+    Ok(address)
+}
+
+/// Reads packets from [`std::io::Read`].
+///
+/// Each packet is read into internal buffer that is reused for following packets.
+/// Values and [`Lazy`] produced by the reader borrow that buffer.
+///
+/// Partial reads and interrupted reads are retried.
+#[cfg(feature = "std")]
+pub struct PacketReader<R> {
+    reader: R,
+    buffer: alloc::vec::Vec<u8>,
+    max_size: usize,
+}
+
+#[cfg(feature = "std")]
+impl<R> PacketReader<R>
+where
+    R: std::io::Read,
+{
+    /// Default limit for the packet size. 8 MiB.
+    pub const DEFAULT_MAX_SIZE: usize = 8 * 1024 * 1024;
+
+    /// Creates a new packet reader with default packet size limit.
+    #[inline]
+    pub fn new(reader: R) -> Self {
+        PacketReader {
+            reader,
+            buffer: alloc::vec::Vec::new(),
+            max_size: Self::DEFAULT_MAX_SIZE,
+        }
     }
 
-    let (address, size) = read_reference::<F>(input, input.len() - reference_size);
+    /// Sets maximum size of a packet.
+    /// Reading larger packet fails without allocating memory for it.
+    #[must_use]
+    #[inline]
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
 
-    if size > address {
-        return Err(DeserializeError::WrongAddress);
+    /// Returns maximum size of a packet.
+    #[must_use]
+    #[inline]
+    pub fn max_size(&self) -> usize {
+        self.max_size
     }
 
-    if address > input.len() {
-        return Err(DeserializeError::OutOfBounds);
+    /// Returns reference to the underlying reader.
+    #[inline]
+    pub fn get_ref(&self) -> &R {
+        &self.reader
     }
 
-    let de = Deserializer::new_unchecked(size, &input[..address]);
-    <T as Deserialize<'de, F>>::deserialize_in_place(place, de)?;
+    /// Returns mutable reference to the underlying reader.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
 
-    Ok(address)
+    /// Returns the underlying reader.
+    #[inline]
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Reads next packet and returns its bytes.
+    /// Returns `None` if reader reached end of stream
+    /// before the packet starts.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if reader fails, stream ends in the middle of the packet
+    /// or packet size is invalid.
+    pub fn read_frame<F>(&mut self) -> std::io::Result<Option<&[u8]>>
+    where
+        F: PacketFormula + ?Sized,
+    {
+        use std::io::{Error, ErrorKind};
+
+        let width = const { fixed_width::<F::Width>() };
+        self.buffer.resize(width, 0);
+
+        let mut filled = 0;
+        while filled < width {
+            match self.reader.read(&mut self.buffer[filled..]) {
+                Ok(0) if filled == 0 => return Ok(None),
+                Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+                Ok(n) => filled += n,
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        let size = match read_packet_size::<F>(&self.buffer) {
+            Some(size) if size < const { header_size::<F>() } => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    DeserializeError::WrongAddress,
+                ))
+            }
+            Some(size) if size > self.max_size => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "packet size exceeds the limit",
+                ))
+            }
+            Some(size) => size,
+            None => unreachable!("Packet size is read"),
+        };
+
+        self.buffer.resize(size, 0);
+        self.reader.read_exact(&mut self.buffer[width..])?;
+
+        Ok(Some(&self.buffer))
+    }
+
+    /// Reads next packet and deserializes value from it.
+    /// Returns `None` if reader reached end of stream
+    /// before the packet starts.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if reader fails, stream ends in the middle of the packet
+    /// or deserialization fails.
+    /// Deserialization errors are reported with [`std::io::ErrorKind::InvalidData`] kind.
+    pub fn read<'a, F, T>(&'a mut self) -> std::io::Result<Option<T>>
+    where
+        F: PacketFormula + ?Sized,
+        T: Deserialize<'a, F::Formula>,
+    {
+        let Some(frame) = self.read_frame::<F>()? else {
+            return Ok(None);
+        };
+
+        match read_packet::<F, T>(frame) {
+            Ok((value, _)) => Ok(Some(value)),
+            Err(err) => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, err)),
+        }
+    }
+
+    /// Reads next packet and returns lazy value from it.
+    /// Returns `None` if reader reached end of stream
+    /// before the packet starts.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if reader fails, stream ends in the middle of the packet
+    /// or packet header is invalid.
+    /// Invalid header is reported with [`std::io::ErrorKind::InvalidData`] kind.
+    pub fn read_lazy<F>(&mut self) -> std::io::Result<Option<Lazy<'_, F::Formula>>>
+    where
+        F: PacketFormula + ?Sized,
+    {
+        let Some(frame) = self.read_frame::<F>()? else {
+            return Ok(None);
+        };
+
+        match packet_deserializer::<F>(frame) {
+            Ok((de, _)) => Ok(Some(Lazy::new(de))),
+            Err(err) => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, err)),
+        }
+    }
 }
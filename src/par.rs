@@ -0,0 +1,177 @@
+//!
+//! This module provides parallel serialization and deserialization
+//! of slices with sized element formulas using [`rayon`].
+//!
+
+use alloc::vec::Vec;
+
+use rayon::{
+    iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator},
+    slice::ParallelSliceMut,
+};
+
+use crate::{
+    buffer::BufferExhausted,
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::{unwrap_size, Formula},
+    lazy::Lazy,
+    serialize::{serialize, serialize_unchecked, Serialize},
+    size::{FixedUsize, SIZE_STACK},
+};
+
+/// Serialize elements of parallel iterator into bytes slice
+/// with `[F]` formula.
+/// Returns the number of bytes written and size of the root value,
+/// same as [`serialize`] with `[F]` formula would.
+///
+/// Element formula must be exact-sized and heap-less,
+/// so that offset of each element is known in advance.
+/// Elements are written into disjoint parts of the output concurrently.
+///
+/// # Errors
+///
+/// Returns [`BufferExhausted`] if the buffer is too small.
+///
+/// # Panics
+///
+/// Using formula that is not exact-sized and heap-less is a compile-time error.
+///
+/// # Examples
+///
+/// ```
+/// # use alkahest::*;
+/// use rayon::prelude::*;
+///
+/// let mut buffer = [0u8; 1024];
+///
+/// let (size, root) = serialize_par::<u32, _>((0..100u32).into_par_iter(), &mut buffer).unwrap();
+/// assert_eq!(size, 400);
+///
+/// let value = deserialize_with_size::<[u32], Vec<u32>>(&buffer[..size], root).unwrap();
+/// assert_eq!(value, (0..100).collect::<Vec<u32>>());
+/// ```
+#[inline]
+pub fn serialize_par<F, I>(iter: I, output: &mut [u8]) -> Result<(usize, usize), BufferExhausted>
+where
+    F: Formula,
+    I: IndexedParallelIterator,
+    I::Item: Serialize<F>,
+{
+    const {
+        assert!(
+            F::EXACT_SIZE && F::HEAPLESS && F::MAX_STACK_SIZE.is_some(),
+            "Element formula must be exact-sized and heap-less"
+        );
+    }
+
+    let count = iter.len();
+
+    let element_size = unwrap_size(F::MAX_STACK_SIZE);
+    if element_size == 0 {
+        // Slice of zero-sized elements stores only the count.
+        return serialize::<FixedUsize, usize>(count, output);
+    }
+
+    let size = element_size.checked_mul(count).ok_or(BufferExhausted)?;
+    if output.len() < size {
+        return Err(BufferExhausted);
+    }
+
+    // First element is placed at the end of the slice.
+    iter.zip(output[..size].par_chunks_exact_mut(element_size).rev())
+        .for_each(|(elem, chunk)| {
+            serialize_unchecked::<F, _>(elem, chunk);
+        });
+
+    Ok((size, size))
+}
+
+/// Serialize elements of parallel iterator into byte vector
+/// with `[F]` formula.
+/// Returns the number of bytes written and size of the root value.
+///
+/// Grows the vector if needed.
+///
+/// # Panics
+///
+/// Panics if size of the slice overflows `usize`.
+/// Using formula that is not exact-sized and heap-less is a compile-time error.
+#[inline]
+pub fn serialize_par_to_vec<F, I>(iter: I, output: &mut Vec<u8>) -> (usize, usize)
+where
+    F: Formula,
+    I: IndexedParallelIterator,
+    I::Item: Serialize<F>,
+{
+    let size = match unwrap_size(F::MAX_STACK_SIZE) {
+        0 => SIZE_STACK,
+        element_size => element_size
+            .checked_mul(iter.len())
+            .expect("Slice size overflows `usize`"),
+    };
+
+    if output.len() < size {
+        output.resize(size, 0);
+    }
+
+    match serialize_par::<F, I>(iter, output) {
+        Ok(sizes) => sizes,
+        Err(BufferExhausted) => unreachable!(),
+    }
+}
+
+impl<'de, F> Lazy<'de, [F]>
+where
+    F: Formula,
+{
+    /// Produce parallel iterator over lazy deserialized values.
+    ///
+    /// Elements are deserialized independently on rayon threads.
+    /// With `bumpalo` feature elements do not share arena of the deserializer.
+    ///
+    /// `par_iter` cannot be used to deserialize slice of unsized formulas.
+    /// Attempt to use unsized formula will result in compile error.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use alkahest::*;
+    /// use rayon::prelude::*;
+    ///
+    /// let mut buffer = [0u8; 1024];
+    ///
+    /// let (size, root) = serialize::<[u32], _>(0..100u32, &mut buffer).unwrap();
+    /// let lazy = deserialize_with_size::<[u32], Lazy<[u32]>>(&buffer[..size], root).unwrap();
+    /// let sum = lazy.par_iter::<u32>().map(Result::unwrap).sum::<u32>();
+    /// assert_eq!(sum, 4950);
+    /// ```
+    #[inline]
+    pub fn par_iter<T>(
+        &self,
+    ) -> impl IndexedParallelIterator<Item = Result<T, DeserializeError>> + 'de
+    where
+        T: Deserialize<'de, F> + Send,
+    {
+        let element_size = const { unwrap_size(F::MAX_STACK_SIZE) };
+
+        let de = self.deserializer();
+        let count = de.clone().into_sized_iter::<F, T>().len();
+        let input = de.input();
+
+        #[cfg(feature = "bytes")]
+        let origin = de.origin();
+
+        (0..count).into_par_iter().map(move |idx| {
+            let end = input.len() - idx * element_size;
+            let de = Deserializer::new_unchecked(element_size, &input[..end]);
+
+            #[cfg(feature = "bytes")]
+            let de = match origin {
+                Some(origin) => de.with_origin(origin),
+                None => de,
+            };
+
+            <T as Deserialize<'de, F>>::deserialize(de)
+        })
+    }
+}
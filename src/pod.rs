@@ -0,0 +1,261 @@
+use core::{
+    marker::PhantomData,
+    mem::{size_of, size_of_val},
+};
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use bytemuck::Pod;
+
+use crate::{
+    buffer::Buffer,
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::{BareFormula, Formula},
+    serialize::{write_bytes, Serialize, SerializeRef, Sizes},
+};
+
+/// Plain-old-data type which byte order can be reversed.
+///
+/// Pod formulas store values in little-endian byte order.
+/// On little-endian targets values are copied as is,
+/// on big-endian targets bytes of each primitive field are swapped.
+///
+/// Implemented for primitive integers and floats, arrays of them
+/// and structures with `#[alkahest(pod)]` attribute.
+pub trait SwapBytes: Pod {
+    /// Reverses byte order of each primitive field.
+    fn swap_bytes(&mut self);
+}
+
+macro_rules! impl_swap_bytes {
+    ($($ty:ident)*) => {
+        $(
+            impl SwapBytes for $ty {
+                #[inline(always)]
+                fn swap_bytes(&mut self) {
+                    *self = $ty::swap_bytes(*self);
+                }
+            }
+        )*
+    };
+}
+
+impl_swap_bytes!(u8 u16 u32 u64 u128 i8 i16 i32 i64 i128);
+
+impl SwapBytes for f32 {
+    #[inline(always)]
+    fn swap_bytes(&mut self) {
+        *self = f32::from_bits(self.to_bits().swap_bytes());
+    }
+}
+
+impl SwapBytes for f64 {
+    #[inline(always)]
+    fn swap_bytes(&mut self) {
+        *self = f64::from_bits(self.to_bits().swap_bytes());
+    }
+}
+
+impl<T, const N: usize> SwapBytes for [T; N]
+where
+    T: SwapBytes,
+    [T; N]: Pod,
+{
+    #[inline(always)]
+    fn swap_bytes(&mut self) {
+        for elem in self {
+            elem.swap_bytes();
+        }
+    }
+}
+
+/// Converts value between native and little-endian byte order.
+#[inline(always)]
+fn swap_le<T>(value: &mut T)
+where
+    T: SwapBytes,
+{
+    if cfg!(target_endian = "big") {
+        value.swap_bytes();
+    }
+}
+
+/// A formula for slices of plain-old-data values.
+///
+/// Elements are serialized as a single block of bytes
+/// equal to their in-memory representation on little-endian targets.
+/// This makes serialization and deserialization a bulk byte copy.
+/// On big-endian targets elements are byte-swapped one by one.
+///
+/// `[T]` formula does not take this path for pod elements,
+/// since generic `[F]` implementations cannot be specialized for them.
+/// It writes elements one by one, and each element is placed
+/// before previously written one, so serialized elements
+/// end up in reverse order.
+/// To get bulk copy, formula of the field must change to `PodSlice<T>`.
+/// This changes wire format of the field: data serialized with `[T]`
+/// cannot be read with `PodSlice<T>` and vice versa,
+/// so stored data must be re-serialized.
+///
+/// Element type must implement [`SwapBytes`].
+/// Structures can opt-in with `#[alkahest(pod)]` attribute
+/// to use the same representation when serialized alone.
+///
+/// Using zero-sized element type is a compile error.
+pub struct PodSlice<T> {
+    marker: PhantomData<fn(&T) -> &T>,
+}
+
+impl<T> Formula for PodSlice<T>
+where
+    T: SwapBytes,
+{
+    const MAX_STACK_SIZE: Option<usize> = None;
+    const EXACT_SIZE: bool = false;
+    const HEAPLESS: bool = true;
+}
+
+impl<T> BareFormula for PodSlice<T> where T: SwapBytes {}
+
+impl<T> SerializeRef<PodSlice<T>> for [T]
+where
+    T: SwapBytes,
+{
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_pod_slice(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(size_of_val(self)))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> SerializeRef<PodSlice<T>> for Vec<T>
+where
+    T: SwapBytes,
+{
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_pod_slice(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(size_of_val(&self[..])))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> Serialize<PodSlice<T>> for Vec<T>
+where
+    T: SwapBytes,
+{
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_pod_slice(&self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(size_of_val(&self[..])))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'de, T> Deserialize<'de, PodSlice<T>> for Vec<T>
+where
+    T: SwapBytes,
+{
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        let mut vec = Vec::new();
+        extend_pod(&mut vec, de.read_all_bytes())?;
+        Ok(vec)
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        self.clear();
+        extend_pod(self, de.read_all_bytes())
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[inline(always)]
+pub(crate) fn extend_pod<T>(vec: &mut Vec<T>, bytes: &[u8]) -> Result<(), DeserializeError>
+where
+    T: SwapBytes,
+{
+    const { assert!(size_of::<T>() != 0, "Zero-sized elements are not supported") }
+
+    if bytes.len() % size_of::<T>() != 0 {
+        return Err(DeserializeError::WrongLength);
+    }
+
+    let old_len = vec.len();
+    vec.resize(old_len + bytes.len() / size_of::<T>(), T::zeroed());
+    bytemuck::cast_slice_mut::<T, u8>(&mut vec[old_len..]).copy_from_slice(bytes);
+    vec[old_len..].iter_mut().for_each(swap_le);
+    Ok(())
+}
+
+/// Writes plain-old-data values as a single block of bytes.
+#[inline(always)]
+pub(crate) fn write_pod_slice<T, B>(
+    values: &[T],
+    sizes: &mut Sizes,
+    mut buffer: B,
+) -> Result<(), B::Error>
+where
+    T: SwapBytes,
+    B: Buffer,
+{
+    const { assert!(size_of::<T>() != 0, "Zero-sized elements are not supported") }
+
+    if cfg!(target_endian = "little") {
+        return write_bytes(bytemuck::cast_slice(values), sizes, buffer);
+    }
+
+    // Later writes are placed before earlier ones.
+    for value in values.iter().rev() {
+        write_pod(value, sizes, buffer.reborrow())?;
+    }
+    Ok(())
+}
+
+/// Writes plain-old-data value as its little-endian in-memory representation.
+#[inline(always)]
+pub fn write_pod<T, B>(value: &T, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+where
+    T: SwapBytes,
+    B: Buffer,
+{
+    let mut value = *value;
+    swap_le(&mut value);
+    write_bytes(bytemuck::bytes_of(&value), sizes, buffer)
+}
+
+/// Reads plain-old-data value from its little-endian in-memory representation.
+#[inline(always)]
+pub fn read_pod<T>(mut de: Deserializer) -> Result<T, DeserializeError>
+where
+    T: SwapBytes,
+{
+    let bytes = de.read_bytes(size_of::<T>())?;
+    let mut value = bytemuck::pod_read_unaligned(bytes);
+    swap_le(&mut value);
+    Ok(value)
+}
@@ -7,8 +7,10 @@ use core::marker::PhantomData;
 use crate::{
     buffer::Buffer,
     deserialize::{Deserialize, DeserializeError, Deserializer},
-    formula::{reference_size, BareFormula, Formula},
-    serialize::{field_size_hint, write_ref, write_reference, Serialize, Sizes},
+    formula::{BareFormula, Formula},
+    serialize::{field_size_hint, write_ref, write_reference_with, Serialize, Sizes},
+    size::FixedUsize,
+    width::Width,
 };
 
 /// `Ref` is a formula wrapper.
@@ -18,22 +20,65 @@ use crate::{
 /// The `slice` type is unsized type that uses length metadata.
 /// Structures allows last field to be of unsized type. In this case
 /// metadata of the field inherited by the struct.
-pub struct Ref<F: ?Sized> {
-    marker: PhantomData<fn(&F) -> &F>,
+///
+/// Second parameter controls [`Width`] of the stored offset and metadata.
+/// It defaults to [`FixedUsize`] which is configured with global `fixed*` features.
+/// Only this reference is affected.
+/// `Vec<F>` and `String` formulas are laid out as `Ref<[F]>` and `Ref<str>`,
+/// so `Ref<[F], u8>` can be used in their place for the same values.
+/// Size prefixes of unsized fields and packet header use [`FixedUsize`].
+///
+/// Stored address is an offset from the start of the whole serialized buffer,
+/// not from the reference. Serialization fails if the address or size
+/// does not fit the width, which happens when value with narrow reference
+/// is placed after large data. See [`Buffer::width_overflow`].
+/// Use narrow widths only for messages that are known to be small.
+///
+/// # Examples
+///
+/// ```
+/// # use alkahest::*;
+///
+/// let mut buffer = [0u8; 1024];
+/// let (size, root) = serialize::<Ref<[u16], u8>, _>(vec![1u16, 2, 3], &mut buffer).unwrap();
+/// assert_eq!(root, 2);
+/// let value = deserialize_with_size::<Ref<[u16], u8>, Vec<u16>>(&buffer[..size], root).unwrap();
+/// assert_eq!(value, [1, 2, 3]);
+///
+/// let err = serialize::<Ref<[u16], u8>, _>(vec![0u16; 200], &mut buffer).unwrap_err();
+/// assert_eq!(err, BufferExhausted);
+/// ```
+pub struct Ref<F: ?Sized, W = FixedUsize> {
+    marker: PhantomData<fn(&F, W) -> &F>,
 }
 
-impl<F> Formula for Ref<F>
+/// Returns size of the reference with specified width.
+const fn reference_size_with<F, W>() -> Option<usize>
+where
+    F: Formula + ?Sized,
+    W: Width,
+{
+    match W::SIZE {
+        None => None,
+        Some(size) if F::EXACT_SIZE => Some(size),
+        Some(size) => Some(size * 2),
+    }
+}
+
+impl<F, W> Formula for Ref<F, W>
 where
     F: BareFormula + ?Sized,
+    W: Width,
 {
-    const MAX_STACK_SIZE: Option<usize> = Some(reference_size::<F>());
-    const EXACT_SIZE: bool = true;
+    const MAX_STACK_SIZE: Option<usize> = reference_size_with::<F, W>();
+    const EXACT_SIZE: bool = W::SIZE.is_some();
     const HEAPLESS: bool = matches!(F::MAX_STACK_SIZE, Some(0));
 }
 
-impl<F, T> Serialize<Ref<F>> for T
+impl<F, W, T> Serialize<Ref<F, W>> for T
 where
     F: BareFormula + ?Sized,
+    W: Width,
     T: Serialize<F>,
 {
     #[inline(always)]
@@ -42,23 +87,23 @@ where
         B: Buffer,
     {
         let size = write_ref::<F, T, _>(self, sizes, buffer.reborrow())?;
-        write_reference::<F, B>(size, sizes.heap, sizes.heap, sizes.stack, buffer)?;
-        sizes.stack += reference_size::<F>();
-        Ok(())
+        write_reference_with::<F, W, _>(size, sizes.heap, sizes, buffer)
     }
 
     #[inline(always)]
     fn size_hint(&self) -> Option<Sizes> {
+        let reference_size = reference_size_with::<F, W>()?;
         let mut sizes = field_size_hint::<F>(self, true)?;
         sizes.to_heap(0);
-        sizes.add_stack(reference_size::<F>());
+        sizes.add_stack(reference_size);
         Some(sizes)
     }
 }
 
-impl<'de, F, T> Deserialize<'de, Ref<F>> for T
+impl<'de, F, W, T> Deserialize<'de, Ref<F, W>> for T
 where
     F: BareFormula + ?Sized,
+    W: Width,
     T: Deserialize<'de, F> + ?Sized,
 {
     #[inline(always)]
@@ -66,13 +111,13 @@ where
     where
         T: Sized,
     {
-        let de = de.deref::<F>()?;
+        let de = de.deref_with::<F, W>()?;
         <T as Deserialize<F>>::deserialize(de)
     }
 
     #[inline(always)]
     fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
-        let de = de.deref::<F>()?;
+        let de = de.deref_with::<F, W>()?;
         <T as Deserialize<F>>::deserialize_in_place(self, de)
     }
 }
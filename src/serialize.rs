@@ -1,14 +1,21 @@
 use core::{fmt, marker::PhantomData, ops};
 
 use crate::{
-    buffer::{Buffer, BufferExhausted, CheckedFixedBuffer, DryBuffer, MaybeFixedBuffer},
+    buffer::{
+        Buffer, BufferExhausted, CheckedFixedBuffer, DryBuffer, MaybeFixedBuffer, ReservedBuffer,
+        ReservedError,
+    },
     formula::{unwrap_size, BareFormula, Formula},
     size::{FixedUsize, SIZE_STACK},
+    width::Width,
 };
 
 #[cfg(feature = "alloc")]
 use crate::buffer::VecBuffer;
 
+#[cfg(feature = "std")]
+use crate::buffer::{WriteBuffer, WriteState};
+
 /// Heap and stack sizes.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Sizes {
@@ -356,6 +363,60 @@ where
     }
 }
 
+/// Serialize value into byte array of the formula's exact size.
+/// Returns the array with serialized value.
+///
+/// Available for formulas with exact stack size `N` and no heap.
+/// Since the size is known at compile time, serialization cannot fail.
+///
+/// # Panics
+///
+/// Using formula that is not exact-sized and heap-less,
+/// or `N` that does not match its size, is a compile-time error.
+///
+/// # Examples
+///
+/// ```
+/// # use alkahest::*;
+///
+/// let frame: [u8; 6] = serialize_to_array::<(u16, Be<u32>), _, 6>((1u16, 2u32));
+/// assert_eq!(frame, [0, 0, 0, 2, 1, 0]);
+///
+/// let value = deserialize_array::<(u16, Be<u32>), (u16, u32), 6>(&frame).unwrap();
+/// assert_eq!(value, (1, 2));
+/// ```
+///
+/// Array size must match the formula.
+///
+/// ```compile_fail
+/// # use alkahest::*;
+///
+/// let frame: [u8; 8] = serialize_to_array::<u32, _, 8>(1u32);
+/// ```
+#[inline(always)]
+pub fn serialize_to_array<F, T, const N: usize>(value: T) -> [u8; N]
+where
+    F: Formula + ?Sized,
+    T: Serialize<F>,
+{
+    const {
+        assert!(
+            F::EXACT_SIZE && F::HEAPLESS,
+            "Formula must be exact-sized and heap-less"
+        );
+        assert!(
+            matches!(F::MAX_STACK_SIZE, Some(size) if size == N),
+            "Array size must match formula size"
+        );
+    }
+
+    let mut output = [0; N];
+    match serialize_into::<F, T, _>(value, &mut output[..]) {
+        Ok(_) => output,
+        Err(never) => match never {},
+    }
+}
+
 /// Error that may occur during serialization
 /// if buffer is too small to fit serialized data.
 ///
@@ -428,6 +489,30 @@ where
     }
 }
 
+/// Serialize value into [`std::io::Write`].
+/// Returns the number of bytes written and size of the root value.
+///
+/// Serialized data is passed to the writer in chunks as it is produced.
+/// Only stack part of the values being serialized is kept in memory,
+/// for example elements of a slice, but not strings they refer to.
+///
+/// # Errors
+///
+/// Returns error if writer fails.
+#[cfg(feature = "std")]
+#[inline(always)]
+pub fn serialize_to_writer<F, T, W>(value: T, writer: &mut W) -> std::io::Result<(usize, usize)>
+where
+    F: Formula + ?Sized,
+    T: Serialize<F>,
+    W: std::io::Write + ?Sized,
+{
+    let mut state = WriteState::new(0);
+    let sizes = serialize_into::<F, T, _>(value, WriteBuffer::new(writer, &mut state))?;
+    state.finish(writer)?;
+    Ok(sizes)
+}
+
 /// Returns the number of bytes required to serialize the value.
 /// Note that value is consumed.
 ///
@@ -504,6 +589,36 @@ where
     Ok(())
 }
 
+/// Writes reference with specified [`Width`] into the buffer.
+/// Advances stack by the size of written reference.
+///
+/// Use in [`Serialize::serialize`](Serialize::serialize) implementation
+/// after writing value to the heap.
+/// With [`FixedUsize`] width writes the same bytes as [`write_reference`].
+/// Read it back with [`Deserializer::deref_with`](crate::advanced::Deserializer::deref_with).
+///
+/// # Errors
+///
+/// Returns error if buffer write fails
+/// or size or address does not fit the width.
+#[inline(always)]
+pub fn write_reference_with<F, W, B>(
+    size: usize,
+    address: usize,
+    sizes: &mut Sizes,
+    mut buffer: B,
+) -> Result<(), B::Error>
+where
+    F: Formula + ?Sized,
+    W: Width,
+    B: Buffer,
+{
+    if !F::EXACT_SIZE {
+        W::write(size, sizes, buffer.reborrow())?;
+    }
+    W::write(address, sizes, buffer)
+}
+
 /// Writes field value into the buffer.
 ///
 /// Use in [`Serialize::serialize`](Serialize::serialize) implementation.
@@ -513,6 +628,32 @@ where
 /// Returns error if buffer write fails.
 #[inline(always)]
 pub fn write_field<F, T, B>(
+    value: T,
+    sizes: &mut Sizes,
+    buffer: B,
+    last: bool,
+) -> Result<(), B::Error>
+where
+    F: Formula + ?Sized,
+    T: Serialize<F>,
+    B: Buffer,
+{
+    write_field_with::<F, FixedUsize, T, B>(value, sizes, buffer, last)
+}
+
+/// Writes field value into the buffer.
+/// Size of unsized field that is not last is written with specified [`Width`].
+/// With [`FixedUsize`] width writes the same bytes as [`write_field`].
+/// Read it back with [`Deserializer::read_value_with`](crate::advanced::Deserializer::read_value_with).
+///
+/// Use in [`Serialize::serialize`](Serialize::serialize) implementation.
+///
+/// # Errors
+///
+/// Returns error if buffer write fails
+/// or size does not fit the width.
+#[inline(always)]
+pub fn write_field_with<F, W, T, B>(
     value: T,
     sizes: &mut Sizes,
     mut buffer: B,
@@ -520,12 +661,15 @@ pub fn write_field<F, T, B>(
 ) -> Result<(), B::Error>
 where
     F: Formula + ?Sized,
+    W: Width,
     T: Serialize<F>,
     B: Buffer,
 {
+    let prefix = const { fixed_width::<W>() };
+
     if !last && F::MAX_STACK_SIZE.is_none() {
-        buffer.write_stack(sizes.heap, sizes.stack, &[0; SIZE_STACK])?;
-        sizes.stack += SIZE_STACK;
+        buffer.pad_stack(sizes.heap, sizes.stack, prefix)?;
+        sizes.stack += prefix;
     }
 
     let old_stack = sizes.stack;
@@ -533,11 +677,12 @@ where
 
     match (F::MAX_STACK_SIZE, F::EXACT_SIZE, last) {
         (None, _, false) => {
-            let size = FixedUsize::truncate_unchecked(sizes.stack - old_stack);
-            let res = buffer.write_stack(sizes.heap, old_stack - SIZE_STACK, &size.to_le_bytes());
-            if res.is_err() {
-                unreachable!("Successfully written before");
+            // Size is written in place of the padding.
+            let mut prefix_sizes = Sizes {
+                heap: sizes.heap,
+                stack: old_stack - prefix,
             };
+            W::write(sizes.stack - old_stack, &mut prefix_sizes, buffer)?;
         }
         (None, _, true) => {}
         (Some(max_stack), false, false) => {
@@ -556,6 +701,19 @@ where
     Ok(())
 }
 
+/// Returns size of [`Width`] that is used for size prefixes and packet headers.
+/// Fails to compile if encoded size depends on the value.
+#[inline(always)]
+pub(crate) const fn fixed_width<W>() -> usize
+where
+    W: Width,
+{
+    match W::SIZE {
+        Some(size) => size,
+        None => panic!("Width of size prefixes and packet headers must have fixed size"),
+    }
+}
+
 /// Write a field with exact size into buffer.
 /// Requires that `F::EXACT_SIZE` is `true` and
 /// `F::MAX_STACK_SIZE` is `Some`.
@@ -601,6 +759,29 @@ where
     Ok(())
 }
 
+/// Writes shared bytes into the buffer.
+/// Buffer may keep reference to the bytes instead of copying them.
+///
+/// Use in [`Serialize::serialize`](Serialize::serialize) implementation.
+///
+/// # Errors
+///
+/// Returns error if buffer write fails.
+#[cfg(feature = "bytes")]
+#[inline(always)]
+pub fn write_shared_bytes<B>(
+    bytes: &::bytes::Bytes,
+    sizes: &mut Sizes,
+    mut buffer: B,
+) -> Result<(), B::Error>
+where
+    B: Buffer,
+{
+    buffer.write_shared(sizes.heap, sizes.stack, bytes)?;
+    sizes.stack += bytes.len();
+    Ok(())
+}
+
 #[cold]
 #[inline(always)]
 fn write_ref_slow<F, T, B>(value: T, sizes: &mut Sizes, mut buffer: B) -> Result<usize, B::Error>
@@ -624,6 +805,7 @@ where
 /// # Errors
 ///
 /// Returns error if buffer write fails.
+#[allow(clippy::double_must_use)]
 #[must_use]
 #[inline(always)]
 pub fn write_ref<F, T, B>(value: T, sizes: &mut Sizes, mut buffer: B) -> Result<usize, B::Error>
@@ -636,27 +818,42 @@ where
     let promised = <T as Serialize<F>>::size_hint(&value);
 
     let stack = match promised {
-        None => write_ref_slow(value, sizes, buffer.reborrow())?,
-        Some(promised) => match buffer.reserve_heap(sizes.heap, sizes.stack, promised.total())? {
-            [] => match write_ref_slow(value, sizes, DryBuffer) {
-                Ok(stack) => stack,
-                Err(never) => match never {},
-            },
-            reserved => {
-                let mut reserved_sizes = Sizes {
-                    heap: sizes.heap,
-                    stack: 0,
-                };
-                <T as Serialize<F>>::serialize(value, &mut reserved_sizes, reserved)
-                    .expect("Reserved enough space");
-
-                debug_assert_eq!(reserved_sizes.heap, sizes.heap + promised.heap);
-                debug_assert_eq!(reserved_sizes.stack, promised.stack);
-
-                sizes.heap = reserved_sizes.total();
-                reserved_sizes.stack
+        Some(promised) if buffer.prefer_reserve(promised.total()) => {
+            match buffer.reserve_heap(sizes.heap, sizes.stack, promised.total())? {
+                [] => match write_ref_slow(value, sizes, DryBuffer) {
+                    Ok(stack) => stack,
+                    Err(never) => match never {},
+                },
+                reserved => {
+                    let mut reserved_sizes = Sizes {
+                        heap: sizes.heap,
+                        stack: 0,
+                    };
+                    // Reserved slice may start after heap offset 0.
+                    let base = sizes.heap + promised.total() - reserved.len();
+                    let reserved = ReservedBuffer::new(reserved, base);
+                    match <T as Serialize<F>>::serialize(value, &mut reserved_sizes, reserved) {
+                        Ok(()) => {}
+                        Err(ReservedError::Exhausted) => panic!("Reserved enough space"),
+                        Err(ReservedError::WidthOverflow) => {
+                            buffer.width_overflow()?;
+                            // Buffer ignores the overflow, so skip the rest of the value.
+                            reserved_sizes = Sizes {
+                                heap: sizes.heap + promised.heap,
+                                stack: promised.stack,
+                            };
+                        }
+                    }
+
+                    debug_assert_eq!(reserved_sizes.heap, sizes.heap + promised.heap);
+                    debug_assert_eq!(reserved_sizes.stack, promised.stack);
+
+                    sizes.heap = reserved_sizes.total();
+                    reserved_sizes.stack
+                }
             }
-        },
+        }
+        _ => write_ref_slow(value, sizes, buffer.reborrow())?,
     };
 
     Ok(stack)
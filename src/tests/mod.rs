@@ -2,10 +2,9 @@
 mod net;
 
 #[cfg(feature = "alloc")]
-use alloc::{collections::VecDeque, vec, vec::Vec};
+use alloc::{collections::VecDeque, vec::Vec};
 
 use crate::{
-    buffer::BufferExhausted,
     bytes::Bytes,
     deserialize::{
         deserialize, deserialize_in_place_with_size, deserialize_with_size, Deserialize,
@@ -15,7 +14,7 @@ use crate::{
     r#as::As,
     reference::Ref,
     serialize::{serialize, serialize_or_size, serialized_size, Serialize},
-    vlq::Vlq,
+    vlq::{Vlq, VlqString, VlqVec},
 };
 
 fn test_type<'a, F, T, D>(value: &T, buffer: &'a mut [u8], eq: impl Fn(&T, &D) -> bool)
@@ -58,16 +57,12 @@ where
 
     let buffer2 = &mut buffer[size.0..];
 
-    match serialize::<F, _>(value, &mut []) {
-        Ok(_) => assert_eq!(size.0, 0),
-        Err(BufferExhausted) => {}
+    if serialize::<F, _>(value, &mut []).is_ok() {
+        assert_eq!(size.0, 0);
     }
 
-    if size.0 > 0 {
-        match serialize::<F, _>(value, &mut buffer2[..size.0 - 1]) {
-            Ok(_) => panic!("expected error"),
-            Err(BufferExhausted) => {}
-        }
+    if size.0 > 0 && serialize::<F, _>(value, &mut buffer2[..size.0 - 1]).is_ok() {
+        panic!("expected error");
     }
 
     let size2 = serialize::<F, _>(value, buffer2).expect("expected success");
@@ -233,6 +228,9 @@ fn test_enums() {
     let size = alkahest::serialize::<TestFormula, _>(data, &mut bytes).unwrap();
     let data = alkahest::deserialize::<TestFormula, TestData>(&bytes[..size.0]).unwrap();
     assert_eq!(data, TestData::Foo { a: 1 });
+
+    let data = alkahest::deserialize::<TestFormula, TestDataLazy>(&bytes[..size.0]).unwrap();
+    assert!(matches!(data, TestDataLazy::Foo { a: 1 }));
 }
 
 #[cfg(feature = "alloc")]
@@ -289,11 +287,12 @@ fn test_packet() {
         Server(ServerMessage),
     }
 
-    assert_eq!(
-        <GameMessage as Formula>::EXACT_SIZE,
-        false,
-        "Enum with non-EXACT_SIZE variants are not EXACT_SIZE"
-    );
+    const {
+        assert!(
+            !<GameMessage as Formula>::EXACT_SIZE,
+            "Enum with non-EXACT_SIZE variants are not EXACT_SIZE"
+        );
+    }
 
     #[derive(Debug, Clone)]
     #[alkahest(Formula, Serialize, Deserialize)]
@@ -302,11 +301,12 @@ fn test_packet() {
         Chat(String),
     }
 
-    assert_eq!(
-        <ClientMessage as Formula>::EXACT_SIZE,
-        false,
-        "Enums with differently sized variants are not EXACT_SIZE"
-    );
+    const {
+        assert!(
+            !<ClientMessage as Formula>::EXACT_SIZE,
+            "Enums with differently sized variants are not EXACT_SIZE"
+        );
+    }
 
     #[derive(Debug, Clone)]
     #[alkahest(Formula, Serialize, Deserialize)]
@@ -315,11 +315,12 @@ fn test_packet() {
         ClientChat { client_id: u64, message: String },
     }
 
-    assert_eq!(
-        <ServerMessage as Formula>::EXACT_SIZE,
-        false,
-        "Enums with differently sized variants are not EXACT_SIZE"
-    );
+    const {
+        assert!(
+            !<ServerMessage as Formula>::EXACT_SIZE,
+            "Enums with differently sized variants are not EXACT_SIZE"
+        );
+    }
 
     #[derive(Debug)]
     #[alkahest(Formula, Serialize, Deserialize)]
@@ -348,6 +349,780 @@ fn test_packet() {
     .unwrap();
 }
 
+#[cfg(all(feature = "std", feature = "derive"))]
+#[test]
+fn test_write_to_writer() {
+    use alkahest_proc::alkahest;
+    use alloc::{format, string::String, vec::Vec};
+    use std::io;
+
+    use crate::{
+        packet::{read_packet, write_packet_to_vec, write_packet_to_writer},
+        serialize::{serialize_to_vec, serialize_to_writer},
+        SerIter,
+    };
+
+    #[derive(Debug, PartialEq)]
+    #[alkahest(Formula, Serialize, SerializeRef, Deserialize)]
+    struct Record {
+        id: u32,
+        name: String,
+    }
+
+    /// Fails after accepting `limit` bytes.
+    struct Limited {
+        limit: usize,
+    }
+
+    impl io::Write for Limited {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.limit < buf.len() {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            self.limit -= buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let records: Vec<Record> = (0..1000)
+        .map(|id| Record {
+            id,
+            name: format!("record-{}", id),
+        })
+        .collect();
+
+    let mut expected = Vec::new();
+    let (size, root) = serialize_to_vec::<[Record], _>(&records, &mut expected);
+
+    let mut output = Vec::new();
+    let sizes = serialize_to_writer::<[Record], _, _>(&records, &mut output).unwrap();
+    assert_eq!(sizes, (size, root));
+    assert_eq!(output, expected[..size]);
+
+    let de = deserialize_with_size::<[Record], Vec<Record>>(&output, root).unwrap();
+    assert_eq!(de, records);
+
+    let mut expected = Vec::new();
+    let size = write_packet_to_vec::<[Record], _>(&records, &mut expected);
+
+    let mut output = Vec::new();
+    assert_eq!(
+        write_packet_to_writer::<[Record], _, _>(&records, &mut output).unwrap(),
+        size
+    );
+    assert_eq!(output, expected[..size]);
+
+    // Iterators do not provide size hint.
+    let mut output = Vec::new();
+    assert_eq!(
+        write_packet_to_writer::<[Record], _, _>(SerIter(records.iter()), &mut output).unwrap(),
+        size
+    );
+    assert_eq!(output, expected[..size]);
+
+    let (de, consumed) = read_packet::<[Record], Vec<Record>>(&output).unwrap();
+    assert_eq!(consumed, size);
+    assert_eq!(de, records);
+
+    let err = serialize_to_writer::<[Record], _, _>(&records, &mut Limited { limit: 100 });
+    assert_eq!(err.unwrap_err().kind(), io::ErrorKind::WriteZero);
+
+    /// Records size of each write call.
+    #[derive(Default)]
+    struct Chunks {
+        output: Vec<u8>,
+        writes: Vec<usize>,
+    }
+
+    impl io::Write for Chunks {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.extend_from_slice(buf);
+            self.writes.push(buf.len());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // Heap is passed to the writer as it grows,
+    // not accumulated until serialization is complete.
+    let mut chunks = Chunks::default();
+    let (size, _) = serialize_to_writer::<[Record], _, _>(&records, &mut chunks).unwrap();
+    assert_eq!(chunks.output.len(), size);
+    assert!(chunks.writes.len() > 1);
+    assert!(chunks.writes[0] < size / 2);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_nested_reserve_heap() {
+    use alloc::{vec, vec::Vec};
+
+    use crate::{
+        buffer::Buffer,
+        formula::reference_size,
+        serialize::{serialize_to_vec, serialize_to_writer, write_reference, Sizes},
+    };
+
+    /// Bytes written with `Buffer::reserve_heap`.
+    /// Same as `Ref<Bytes>`.
+    struct Blob;
+
+    impl Formula for Blob {
+        const MAX_STACK_SIZE: Option<usize> = Some(reference_size::<Bytes>());
+        const EXACT_SIZE: bool = true;
+        const HEAPLESS: bool = false;
+    }
+
+    struct Reserving<'a>(&'a [u8]);
+
+    impl Serialize<Blob> for Reserving<'_> {
+        fn serialize<B>(self, sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+        where
+            B: Buffer,
+        {
+            let len = self.0.len();
+            match buffer.reserve_heap(sizes.heap, sizes.stack, len)? {
+                [] => {}
+                reserved => {
+                    let at = reserved.len() - len;
+                    reserved[at..].copy_from_slice(self.0);
+                }
+            }
+            sizes.heap += len;
+            write_reference::<Bytes, _>(len, sizes.heap, sizes.heap, sizes.stack, buffer)?;
+            sizes.stack += reference_size::<Bytes>();
+            Ok(())
+        }
+
+        fn size_hint(&self) -> Option<Sizes> {
+            Some(Sizes {
+                heap: self.0.len(),
+                stack: reference_size::<Bytes>(),
+            })
+        }
+    }
+
+    let blobs: Vec<Vec<u8>> = (0..100u8).map(|i| vec![i; 200]).collect();
+    let value = || blobs.iter().map(|blob| Reserving(blob)).collect::<Vec<_>>();
+
+    // Blobs are written into `&mut [u8]` reserved for the vector.
+    let mut expected = Vec::new();
+    let (size, root) = serialize_to_vec::<Vec<Blob>, _>(value(), &mut expected);
+    let de = deserialize_with_size::<Vec<Ref<Bytes>>, Vec<&[u8]>>(&expected[..size], root).unwrap();
+    assert_eq!(de, blobs);
+
+    // Blobs reserve heap after part of it is passed to the writer.
+    let mut output = Vec::new();
+    let sizes = serialize_to_writer::<Vec<Blob>, _, _>(value(), &mut output).unwrap();
+    assert_eq!(sizes, (size, root));
+    assert_eq!(output, expected[..size]);
+
+    // Blobs reserve heap after shared bytes.
+    #[cfg(feature = "bytes")]
+    {
+        use crate::{
+            packet::{read_packet, write_packet_to_vec},
+            vectored::{write_packet_vectored, VectoredPacket},
+        };
+
+        let large = ::bytes::Bytes::from(vec![0xff; 5000]);
+
+        let mut expected = Vec::new();
+        let size = write_packet_to_vec::<(Ref<Bytes>, Vec<Blob>), _>(
+            (large.clone(), value()),
+            &mut expected,
+        );
+
+        let mut packet = VectoredPacket::new();
+        assert_eq!(
+            write_packet_vectored::<(Ref<Bytes>, Vec<Blob>), _>(
+                (large.clone(), value()),
+                &mut packet
+            ),
+            size
+        );
+        assert_eq!(packet.to_vec(), expected[..size]);
+
+        let ((head, de), _) =
+            read_packet::<(Ref<Bytes>, Vec<Ref<Bytes>>), (&[u8], Vec<&[u8]>)>(&expected).unwrap();
+        assert_eq!(head, &large[..]);
+        assert_eq!(de, blobs);
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_packet_reader() {
+    use alloc::{string::String, vec::Vec};
+    use std::io;
+
+    use crate::packet::{read_packet_size, write_packet_to_vec, PacketReader};
+
+    /// Yields at most `chunk` bytes per read and interrupts every other read.
+    struct Choppy<'a> {
+        input: &'a [u8],
+        chunk: usize,
+        interrupt: bool,
+    }
+
+    impl io::Read for Choppy<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.interrupt = !self.interrupt;
+            if self.interrupt {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+            let len = buf.len().min(self.chunk).min(self.input.len());
+            buf[..len].copy_from_slice(&self.input[..len]);
+            self.input = &self.input[len..];
+            Ok(len)
+        }
+    }
+
+    let mut stream = Vec::new();
+    for value in ["a", "bb", "", "long enough value to span many chunks"] {
+        let mut packet = Vec::new();
+        let size = write_packet_to_vec::<str, _>(value, &mut packet);
+        stream.extend_from_slice(&packet[..size]);
+    }
+
+    let mut reader = PacketReader::new(Choppy {
+        input: &stream,
+        chunk: 3,
+        interrupt: false,
+    });
+    assert_eq!(reader.read::<str, &str>().unwrap(), Some("a"));
+    assert_eq!(reader.read::<str, String>().unwrap().as_deref(), Some("bb"));
+    assert_eq!(reader.read::<str, &str>().unwrap(), Some(""));
+    let lazy = reader.read_lazy::<str>().unwrap().unwrap();
+    assert_eq!(
+        lazy.get::<&str>().unwrap(),
+        "long enough value to span many chunks"
+    );
+    assert!(reader.read::<str, &str>().unwrap().is_none());
+
+    // Stream ends in the middle of the packet.
+    let mut reader = PacketReader::new(&stream[..stream.len() - 1]);
+    for _ in 0..3 {
+        reader.read::<str, &str>().unwrap().unwrap();
+    }
+    let err = reader.read::<str, &str>().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+    // Packets larger than the limit are rejected.
+    let mut reader = PacketReader::new(&stream[..]).with_max_size(8);
+    let err = reader.read::<str, &str>().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    // Default limit applies to sizes that fit the header.
+    let header = [0xff; crate::size::SIZE_STACK];
+    let mut reader = PacketReader::new(&header[..]);
+    let err = reader.read::<str, &str>().unwrap_err();
+    if crate::size::SIZE_STACK >= 4 {
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    // Zero-sized values still have reference in the packet.
+    let mut packet = Vec::new();
+    let size = write_packet_to_vec::<(), _>((), &mut packet);
+    assert_eq!(read_packet_size::<()>(&packet[..size]), Some(size));
+
+    let mut reader = PacketReader::new(&packet[..size]);
+    assert_eq!(reader.read::<(), ()>().unwrap(), Some(()));
+    assert!(reader.read::<(), ()>().unwrap().is_none());
+}
+
+#[cfg(all(feature = "tokio", feature = "derive"))]
+#[tokio::test]
+async fn test_tokio_codec() {
+    use alkahest_proc::alkahest;
+    use alloc::{string::String, vec, vec::Vec};
+    use futures::{SinkExt, StreamExt};
+    use std::io;
+    use tokio::io::AsyncWriteExt;
+    use tokio_util::codec::{FramedRead, FramedWrite};
+
+    use crate::codec::AlkahestCodec;
+
+    #[alkahest(Formula)]
+    struct Message {
+        id: u32,
+        payload: [u8],
+    }
+
+    #[alkahest(Serialize<Message>)]
+    struct MessageWrite<'a> {
+        id: u32,
+        payload: &'a [u8],
+    }
+
+    #[derive(Debug, PartialEq)]
+    #[alkahest(Deserialize<'_, Message>)]
+    struct MessageRead {
+        id: u32,
+        payload: Vec<u8>,
+    }
+
+    // Small duplex buffer splits frames between reads.
+    let (client, server) = tokio::io::duplex(16);
+
+    let writer = tokio::spawn(async move {
+        let mut framed = FramedWrite::new(client, AlkahestCodec::<Message, ()>::new());
+        for id in 0..10u32 {
+            let payload = vec![id as u8; id as usize * 7];
+            framed
+                .send(MessageWrite {
+                    id,
+                    payload: &payload,
+                })
+                .await
+                .unwrap();
+        }
+    });
+
+    let mut framed = FramedRead::new(server, AlkahestCodec::<Message, MessageRead>::new());
+    for id in 0..10u32 {
+        let message = framed.next().await.unwrap().unwrap();
+        assert_eq!(
+            message,
+            MessageRead {
+                id,
+                payload: vec![id as u8; id as usize * 7],
+            }
+        );
+    }
+    assert!(framed.next().await.is_none());
+    writer.await.unwrap();
+
+    // Frames over the limit are rejected by both encoder and decoder.
+    let (client, server) = tokio::io::duplex(1024);
+    let mut framed = FramedWrite::new(client, AlkahestCodec::<str, ()>::new());
+    framed.send("long message").await.unwrap();
+    framed.get_mut().shutdown().await.unwrap();
+
+    let mut limited = FramedWrite::new(
+        Vec::new(),
+        AlkahestCodec::<str, ()>::new().with_max_frame_size(8),
+    );
+    let err = limited.send("long message").await.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    let mut framed = FramedRead::new(
+        server,
+        AlkahestCodec::<str, String>::new().with_max_frame_size(8),
+    );
+    let err = framed.next().await.unwrap().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[cfg(all(feature = "bytes", feature = "derive"))]
+#[test]
+fn test_shared_bytes() {
+    use alkahest_proc::alkahest;
+    use alloc::{vec, vec::Vec};
+    use bytes::BytesMut;
+
+    use crate::{
+        buffer::BytesMutBuffer,
+        packet::{read_packet, read_packet_shared, write_packet_into, write_packet_to_vec},
+    };
+
+    #[alkahest(Formula)]
+    struct Forward {
+        route: u32,
+        payload: Bytes,
+    }
+
+    #[alkahest(Serialize<Forward>)]
+    struct ForwardWrite<'a> {
+        route: u32,
+        payload: &'a [u8],
+    }
+
+    #[alkahest(Deserialize<'_, Forward>)]
+    struct ForwardRead {
+        route: u32,
+        payload: bytes::Bytes,
+    }
+
+    let payload = vec![42u8; 1000];
+
+    let mut output = BytesMut::new();
+    let size = write_packet_into::<Forward, _, _>(
+        ForwardWrite {
+            route: 7,
+            payload: &payload,
+        },
+        BytesMutBuffer::new(&mut output),
+    )
+    .unwrap();
+    output.truncate(size);
+    let input = output.freeze();
+
+    let (value, consumed) = read_packet_shared::<Forward, ForwardRead>(&input).unwrap();
+    assert_eq!(consumed, size);
+    assert_eq!(value.route, 7);
+    assert_eq!(value.payload, payload);
+
+    // Payload points into the input buffer.
+    let range = input.as_ptr_range();
+    assert!(range.contains(&value.payload.as_ptr()));
+
+    // Without shared input the payload is copied.
+    let (value, _) = read_packet::<Forward, ForwardRead>(&input).unwrap();
+    assert_eq!(value.payload, payload);
+    assert!(!range.contains(&value.payload.as_ptr()));
+
+    // `Vec<u8>` and `[u8]` formulas are copied in original order.
+    let mut bytes = Vec::new();
+    write_packet_to_vec::<Vec<u8>, _>([1u8, 2, 3], &mut bytes);
+    let input = bytes::Bytes::from(bytes);
+    let (value, _) = read_packet_shared::<Vec<u8>, bytes::Bytes>(&input).unwrap();
+    assert_eq!(value, [1u8, 2, 3][..]);
+}
+
+#[cfg(all(feature = "bytes", feature = "std", feature = "derive"))]
+#[test]
+fn test_vectored_packet() {
+    use alkahest_proc::alkahest;
+    use alloc::{vec, vec::Vec};
+    use std::io::Write;
+
+    use crate::{
+        packet::{read_packet, write_packet_to_vec},
+        vectored::{write_packet_vectored, VectoredPacket},
+    };
+
+    #[alkahest(Formula)]
+    enum Chunk {
+        Data { seq: u32, payload: Bytes },
+        Ack { seq: u32 },
+    }
+
+    #[alkahest(Formula)]
+    struct Message {
+        header: Bytes,
+        chunks: Vec<Chunk>,
+        trailer: Option<Bytes>,
+    }
+
+    #[alkahest(Serialize<Chunk>)]
+    enum ChunkWrite {
+        Data { seq: u32, payload: bytes::Bytes },
+        Ack { seq: u32 },
+    }
+
+    #[alkahest(Serialize<Message>)]
+    struct MessageWrite {
+        header: bytes::Bytes,
+        chunks: Vec<ChunkWrite>,
+        trailer: Option<bytes::Bytes>,
+    }
+
+    #[alkahest(Deserialize<'de, Chunk>)]
+    enum ChunkRead<'de> {
+        Data { seq: u32, payload: &'de [u8] },
+        Ack { seq: u32 },
+    }
+
+    #[alkahest(Deserialize<'de, Message>)]
+    struct MessageRead<'de> {
+        header: &'de [u8],
+        chunks: Vec<ChunkRead<'de>>,
+        trailer: Option<&'de [u8]>,
+    }
+
+    let large = bytes::Bytes::from(vec![1u8; 5000]);
+    let message = || MessageWrite {
+        header: bytes::Bytes::from_static(b"header"),
+        chunks: vec![
+            ChunkWrite::Data {
+                seq: 1,
+                payload: large.clone(),
+            },
+            ChunkWrite::Ack { seq: 1 },
+            ChunkWrite::Data {
+                seq: 2,
+                payload: bytes::Bytes::from(vec![2u8; 3000]),
+            },
+            ChunkWrite::Data {
+                seq: 3,
+                payload: bytes::Bytes::from_static(b"small"),
+            },
+        ],
+        trailer: Some(large.clone()),
+    };
+
+    let mut expected = Vec::new();
+    let size = write_packet_to_vec::<Message, _>(message(), &mut expected);
+
+    let mut packet = VectoredPacket::new();
+    assert_eq!(
+        write_packet_vectored::<Message, _>(message(), &mut packet),
+        size
+    );
+    assert_eq!(packet.len(), size);
+    assert_eq!(packet.to_vec(), expected[..size]);
+
+    // Large payloads are referenced, not copied.
+    let slices = packet.io_slices();
+    let shared = slices
+        .iter()
+        .filter(|slice| slice.as_ptr() == large.as_ptr())
+        .count();
+    assert_eq!(shared, 2);
+
+    let mut output = Vec::new();
+    let written = output.write_vectored(&slices).unwrap();
+    assert_eq!(written, size);
+
+    let (value, consumed) = read_packet::<Message, MessageRead>(&output).unwrap();
+    assert_eq!(consumed, size);
+    assert_eq!(value.header, b"header");
+    assert_eq!(value.trailer, Some(&large[..]));
+    assert_eq!(value.chunks.len(), 4);
+    match value.chunks[0] {
+        ChunkRead::Data { seq, payload } => {
+            assert_eq!(seq, 1);
+            assert_eq!(payload, &large[..]);
+        }
+        ChunkRead::Ack { .. } => panic!("Unexpected ack"),
+    }
+    match value.chunks[1] {
+        ChunkRead::Ack { seq } => assert_eq!(seq, 1),
+        ChunkRead::Data { .. } => panic!("Unexpected data"),
+    }
+
+    // Everything is copied with high threshold.
+    let mut packet = VectoredPacket::new().with_min_shared(10000);
+    write_packet_vectored::<Message, _>(message(), &mut packet);
+    assert_eq!(packet.io_slices().len(), 1);
+    assert_eq!(packet.to_vec(), expected[..size]);
+}
+
+#[cfg(all(feature = "bumpalo", feature = "derive"))]
+#[test]
+fn test_arena() {
+    use alkahest_proc::alkahest;
+    use alloc::{vec, vec::Vec};
+    use bumpalo::{collections, Bump};
+
+    use crate::{
+        arena::{
+            deserialize_in_arena, deserialize_with_arena, read_packet_in_arena,
+            read_packet_with_arena,
+        },
+        deserialize::DeserializeError,
+        packet::write_packet_to_vec,
+        serialize::serialize_to_vec,
+    };
+
+    #[alkahest(Formula)]
+    struct Entity {
+        name: alloc::string::String,
+        tags: Vec<u32>,
+        blob: Bytes,
+    }
+
+    #[alkahest(Serialize<Entity>)]
+    struct EntityWrite<'a> {
+        name: &'a str,
+        tags: Vec<u32>,
+        blob: &'a [u8],
+    }
+
+    #[alkahest(Deserialize<'a, Entity>)]
+    struct EntityRead<'a> {
+        name: &'a str,
+        tags: collections::Vec<'a, u32>,
+        blob: collections::Vec<'a, u8>,
+    }
+
+    let arena = Bump::new();
+
+    let entities: collections::Vec<EntityRead> = {
+        let mut input = Vec::new();
+        write_packet_to_vec::<[Entity], _>(
+            vec![
+                EntityWrite {
+                    name: "first",
+                    tags: vec![1, 2, 3],
+                    blob: b"blob",
+                },
+                EntityWrite {
+                    name: "second",
+                    tags: vec![],
+                    blob: b"",
+                },
+            ],
+            &mut input,
+        );
+
+        read_packet_in_arena::<[Entity], _>(&arena, &input)
+            .unwrap()
+            .0
+    };
+
+    // Input is dropped, but values stay in the arena.
+    assert_eq!(entities.len(), 2);
+    assert_eq!(entities[0].name, "first");
+    assert_eq!(entities[0].tags, [1, 2, 3]);
+    assert_eq!(entities[0].blob, b"blob");
+    assert_eq!(entities[1].name, "second");
+    assert!(entities[1].tags.is_empty());
+    assert!(entities[1].blob.is_empty());
+
+    let mut input = Vec::new();
+    let size = write_packet_to_vec::<str, _>("packet", &mut input);
+    input.extend_from_slice(b"trailing");
+
+    let (string, consumed) =
+        read_packet_in_arena::<str, collections::String>(&arena, &input).unwrap();
+    assert_eq!(consumed, size);
+    assert_eq!(string, "packet");
+
+    let string: &str = {
+        let mut input = Vec::new();
+        let (size, _) = serialize_to_vec::<str, _>("borrowed", &mut input);
+        deserialize_in_arena::<str, _>(&arena, &input[..size]).unwrap()
+    };
+    assert_eq!(string, "borrowed");
+
+    // Borrowed values point into the input, collections into the arena.
+    let mut input = Vec::new();
+    let size = write_packet_to_vec::<[Entity], _>(
+        vec![EntityWrite {
+            name: "shared",
+            tags: vec![4, 5],
+            blob: b"bytes",
+        }],
+        &mut input,
+    );
+    let (entities, consumed) =
+        read_packet_with_arena::<[Entity], collections::Vec<EntityRead>>(&arena, &input).unwrap();
+    assert_eq!(consumed, size);
+    assert_eq!(entities[0].name, "shared");
+    assert!(input.as_ptr_range().contains(&entities[0].name.as_ptr()));
+    assert_eq!(entities[0].tags, [4, 5]);
+    assert_eq!(entities[0].blob, b"bytes");
+
+    let mut input = Vec::new();
+    let (size, _) = serialize_to_vec::<str, _>("in place", &mut input);
+    let string = deserialize_with_arena::<str, &str>(&arena, &input[..size]).unwrap();
+    assert_eq!(string, "in place");
+    assert!(input.as_ptr_range().contains(&string.as_ptr()));
+
+    let mut input = Vec::new();
+    let (size, root) = serialize_to_vec::<[u32], _>([1u32, 2, 3], &mut input);
+    assert!(matches!(
+        deserialize_with_size::<[u32], collections::Vec<u32>>(&input[..size], root),
+        Err(DeserializeError::MissingArena)
+    ));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_packet_batch() {
+    use alloc::{format, string::String, vec::Vec};
+
+    use crate::{
+        batch::{PacketBatch, PacketIter},
+        deserialize::DeserializeError,
+        packet::read_packet,
+    };
+
+    let mut batch = PacketBatch::new();
+    for round in 0..2 {
+        for idx in 0..100 {
+            batch.push::<str, _>(format!("packet {} {}", round, idx).as_str());
+        }
+        assert_eq!(batch.len(), 100);
+        assert_eq!(
+            batch
+                .get(42)
+                .map(|packet| read_packet::<str, &str>(packet).unwrap().0),
+            Some(format!("packet {} 42", round).as_str())
+        );
+        assert!(batch.get(100).is_none());
+
+        let mut iter = PacketIter::<str>::new(batch.as_bytes());
+        for (idx, packet) in batch.iter().enumerate() {
+            assert_eq!(iter.next().unwrap().unwrap(), packet);
+            let (value, _) = read_packet::<str, String>(packet).unwrap();
+            assert_eq!(value, format!("packet {} {}", round, idx));
+        }
+        assert!(iter.next().is_none());
+        assert!(iter.remaining().is_empty());
+
+        // Memory is reused for next round.
+        let capacity = batch.as_bytes().as_ptr();
+        batch.clear();
+        assert!(batch.is_empty());
+        batch.push::<str, _>("reuse");
+        assert_eq!(batch.as_bytes().as_ptr(), capacity);
+        batch.clear();
+    }
+
+    // Incomplete packet at the end is left in the remaining input.
+    batch.push::<[u32], _>([1u32, 2, 3]);
+    batch.push::<[u32], _>([4u32, 5]);
+    let bytes = batch.as_bytes();
+    let first = batch.get(0).unwrap().len();
+
+    let mut iter = PacketIter::<[u32]>::new(&bytes[..bytes.len() - 1]);
+    let (values, _) = read_packet::<[u32], Vec<u32>>(iter.next().unwrap().unwrap()).unwrap();
+    assert_eq!(values, [1, 2, 3]);
+    assert!(iter.next().is_none());
+    assert_eq!(iter.remaining(), &bytes[first..bytes.len() - 1]);
+
+    let mut iter = PacketIter::<[u32]>::new(&[1, 0, 0, 0, 0, 0, 0, 0][..crate::size::SIZE_STACK]);
+    assert!(matches!(
+        iter.next(),
+        Some(Err(DeserializeError::WrongAddress))
+    ));
+    assert!(iter.next().is_none());
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_packet_size_zero_sized() {
+    use alloc::vec::Vec;
+
+    use crate::{
+        batch::PacketIter,
+        packet::{packet_size, read_packet, read_packet_size, write_packet_to_vec},
+        size::SIZE_STACK,
+    };
+
+    // Packet of zero-sized value consists of the reference.
+    // Reporting zero size would leave it unread in the stream.
+    let mut bytes = Vec::new();
+    let size = write_packet_to_vec::<(), _>((), &mut bytes);
+    assert_eq!(size, SIZE_STACK);
+    assert_eq!(packet_size::<(), _>(()), size);
+    assert_eq!(read_packet_size::<()>(&bytes), Some(size));
+    assert_eq!(read_packet::<(), ()>(&bytes).unwrap(), ((), size));
+
+    let mut stream = Vec::new();
+    for _ in 0..3 {
+        stream.extend_from_slice(&bytes[..size]);
+    }
+    let mut iter = PacketIter::<()>::new(&stream);
+    for _ in 0..3 {
+        assert_eq!(iter.next().unwrap().unwrap(), &bytes[..size]);
+    }
+    assert!(iter.next().is_none());
+    assert!(iter.remaining().is_empty());
+
+    assert_eq!(read_packet_size::<()>(&bytes[..size - 1]), None);
+}
+
 #[cfg(feature = "alloc")]
 #[test]
 fn test_zst_slice() {
@@ -434,6 +1209,13 @@ fn test_vlq() {
         let de = deserialize::<Vlq, u128>(&buffer[..size.0]).unwrap();
         assert_eq!(de, i);
     }
+
+    for header in [0xC0, 0xFF] {
+        assert!(matches!(
+            deserialize::<Vlq, u128>(&[0, header]),
+            Err(crate::deserialize::DeserializeError::IntegerOverflow)
+        ));
+    }
 }
 
 #[cfg(feature = "bincoded")]
@@ -489,6 +1271,7 @@ fn test_zero_sized_arrays() {
 #[test]
 fn test_recursive_types() {
     use alkahest_proc::alkahest;
+    use alloc::vec;
 
     let mut buffer = [0; 1024];
 
@@ -559,3 +1342,1680 @@ fn test_recursive_types() {
     let c = crate::deserialize_with_size::<A<i32>, C<i32>>(&buffer[..size], root).unwrap();
     assert_eq!(b, c);
 }
+
+#[cfg(feature = "derive")]
+#[test]
+fn test_generic_params() {
+    use alkahest_proc::alkahest;
+
+    #[derive(Debug, PartialEq)]
+    #[alkahest(Formula, Serialize, Deserialize)]
+    struct Packet<const N: usize> {
+        data: [u8; N],
+    }
+
+    #[alkahest(Serialize<Packet<N>>)]
+    struct PacketRef<'a, const N: usize> {
+        data: &'a [u8; N],
+    }
+
+    #[alkahest(Formula)]
+    struct Labeled<const N: usize> {
+        label: As<str>,
+        data: [u8; N],
+    }
+
+    #[alkahest(Serialize<Labeled<N>>)]
+    struct LabeledData<'a, const N: usize> {
+        label: &'a str,
+        data: [u8; N],
+    }
+
+    #[derive(Debug, PartialEq)]
+    #[alkahest(Deserialize<'de, Labeled<N>>)]
+    struct LabeledView<'de, const N: usize> {
+        label: &'de str,
+        data: [u8; N],
+    }
+
+    #[derive(Debug, PartialEq)]
+    #[alkahest(Formula, Serialize, Deserialize)]
+    enum Grid<T, const N: usize> {
+        Empty,
+        Cells([T; N]),
+    }
+
+    trait Entity {
+        type Id;
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Player;
+
+    impl Entity for Player {
+        type Id = u64;
+    }
+
+    #[derive(Debug, PartialEq)]
+    #[alkahest(Formula, Serialize, Deserialize)]
+    struct EntityRef<E: Entity> {
+        id: E::Id,
+    }
+
+    #[derive(Debug, PartialEq)]
+    #[alkahest(Formula, Serialize, Deserialize)]
+    struct Inner<T> {
+        value: T,
+    }
+
+    #[derive(Debug, PartialEq)]
+    #[alkahest(Formula, Serialize, Deserialize)]
+    struct Outer<T> {
+        inner: Inner<T>,
+        packet: Packet<2>,
+    }
+
+    let mut buffer = [0u8; 256];
+
+    let (size, root) =
+        serialize::<Packet<3>, _>(PacketRef { data: &[1, 2, 3] }, &mut buffer).unwrap();
+    let packet = deserialize_with_size::<Packet<3>, Packet<3>>(&buffer[..size], root).unwrap();
+    assert_eq!(packet, Packet { data: [1, 2, 3] });
+
+    let (size, root) = serialize::<Labeled<2>, _>(
+        LabeledData {
+            label: "four",
+            data: [4, 5],
+        },
+        &mut buffer,
+    )
+    .unwrap();
+    let labeled =
+        deserialize_with_size::<Labeled<2>, LabeledView<2>>(&buffer[..size], root).unwrap();
+    assert_eq!(
+        labeled,
+        LabeledView {
+            label: "four",
+            data: [4, 5],
+        }
+    );
+
+    let (size, root) = serialize::<Grid<u16, 2>, _>(Grid::Cells([6, 7]), &mut buffer).unwrap();
+    let grid = deserialize_with_size::<Grid<u16, 2>, Grid<u16, 2>>(&buffer[..size], root).unwrap();
+    assert_eq!(grid, Grid::Cells([6, 7]));
+
+    let (size, root) =
+        serialize::<EntityRef<Player>, _>(EntityRef::<Player> { id: 8 }, &mut buffer).unwrap();
+    let entity =
+        deserialize_with_size::<EntityRef<Player>, EntityRef<Player>>(&buffer[..size], root)
+            .unwrap();
+    assert_eq!(entity, EntityRef { id: 8 });
+
+    let outer = Outer {
+        inner: Inner { value: 9u32 },
+        packet: Packet { data: [10, 11] },
+    };
+    let (size, root) = serialize::<Outer<u32>, _>(outer, &mut buffer).unwrap();
+    let outer = deserialize_with_size::<Outer<u32>, Outer<u32>>(&buffer[..size], root).unwrap();
+    assert_eq!(
+        outer,
+        Outer {
+            inner: Inner { value: 9 },
+            packet: Packet { data: [10, 11] },
+        }
+    );
+}
+
+#[cfg(all(feature = "alloc", feature = "derive"))]
+#[test]
+fn test_lazy_fields() {
+    use alkahest_proc::alkahest;
+    use alloc::{string::String, vec, vec::Vec};
+
+    #[alkahest(Formula, Serialize, Deserialize)]
+    struct Player {
+        id: u32,
+        name: String,
+    }
+
+    #[alkahest(Formula, lazy)]
+    struct Snapshot {
+        tick: u64,
+        title: As<str>,
+        scores: Vec<u32>,
+        players: [Player],
+    }
+
+    #[alkahest(Serialize<Snapshot>)]
+    struct SnapshotData {
+        tick: u64,
+        title: &'static str,
+        scores: Vec<u32>,
+        players: Vec<Player>,
+    }
+
+    #[alkahest(Formula, lazy)]
+    struct Pair(u8, As<str>);
+
+    #[alkahest(Serialize<Pair>)]
+    struct PairData(u8, &'static str);
+
+    let mut buffer = [0u8; 1024];
+
+    let (size, root) = serialize::<Snapshot, _>(
+        SnapshotData {
+            tick: 42,
+            title: "final",
+            scores: vec![1, 2, 3],
+            players: vec![
+                Player {
+                    id: 1,
+                    name: "qwe".into(),
+                },
+                Player {
+                    id: 2,
+                    name: "rty".into(),
+                },
+            ],
+        },
+        &mut buffer,
+    )
+    .unwrap();
+
+    let lazy = deserialize_with_size::<Snapshot, Lazy<Snapshot>>(&buffer[..size], root).unwrap();
+
+    assert_eq!(lazy.field_tick().unwrap().get::<u64>().unwrap(), 42);
+    assert_eq!(lazy.field_title().unwrap().get::<&str>().unwrap(), "final");
+    assert_eq!(
+        lazy.field_scores().unwrap().get::<Vec<u32>>().unwrap(),
+        [1, 2, 3]
+    );
+
+    let players = lazy.field_players().unwrap();
+    let mut iter = players.sized_iter::<Player>();
+    let player = iter.next_back().unwrap().unwrap();
+    assert_eq!((player.id, &*player.name), (2, "rty"));
+    let player = iter.next().unwrap().unwrap();
+    assert_eq!((player.id, &*player.name), (1, "qwe"));
+    assert!(iter.next().is_none());
+
+    let (size, root) = serialize::<Pair, _>(PairData(7, "asd"), &mut buffer).unwrap();
+    let lazy = deserialize_with_size::<Pair, Lazy<Pair>>(&buffer[..size], root).unwrap();
+    assert_eq!(lazy.field_0().unwrap().get::<u8>().unwrap(), 7);
+    assert_eq!(lazy.field_1().unwrap().get::<&str>().unwrap(), "asd");
+}
+
+#[cfg(all(feature = "alloc", feature = "derive"))]
+#[test]
+fn test_transparent() {
+    use alkahest_proc::alkahest;
+    use alloc::{string::String, vec, vec::Vec};
+
+    #[alkahest(transparent)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct PlayerId(u64);
+
+    #[alkahest(transparent)]
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct Name {
+        value: String,
+    }
+
+    #[alkahest(transparent)]
+    #[derive(Debug, PartialEq, Eq)]
+    struct Wrapper<T>(T);
+
+    #[alkahest(Formula, Serialize, Deserialize)]
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct Player {
+        id: PlayerId,
+        name: Name,
+    }
+
+    const _: () = assert!(<PlayerId as Formula>::EXACT_SIZE);
+    const _: () = assert!(<PlayerId as Formula>::HEAPLESS);
+    assert_eq!(
+        <PlayerId as Formula>::MAX_STACK_SIZE,
+        <u64 as Formula>::MAX_STACK_SIZE
+    );
+    assert_eq!(
+        <Name as Formula>::MAX_STACK_SIZE,
+        <String as Formula>::MAX_STACK_SIZE
+    );
+
+    let mut buffer = [0u8; 256];
+
+    // Newtype and inner formula are interchangeable on the wire.
+    // Newtype over bare formula is serializable by reference.
+    let (size, root) = serialize::<PlayerId, _>(&PlayerId(42), &mut buffer).unwrap();
+    assert_eq!(
+        deserialize_with_size::<u64, u64>(&buffer[..size], root).unwrap(),
+        42
+    );
+
+    let (size, root) = serialize::<u64, _>(7u64, &mut buffer).unwrap();
+    assert_eq!(
+        deserialize_with_size::<PlayerId, PlayerId>(&buffer[..size], root).unwrap(),
+        PlayerId(7)
+    );
+
+    let name = Name {
+        value: "qwe".into(),
+    };
+    let (size, root) = serialize::<Name, _>(name.clone(), &mut buffer).unwrap();
+    assert_eq!(
+        deserialize_with_size::<String, String>(&buffer[..size], root).unwrap(),
+        "qwe"
+    );
+    assert_eq!(
+        deserialize_with_size::<Name, Name>(&buffer[..size], root).unwrap(),
+        name
+    );
+
+    let players = vec![
+        Player {
+            id: PlayerId(1),
+            name: Name {
+                value: "rty".into(),
+            },
+        },
+        Player {
+            id: PlayerId(2),
+            name: Name {
+                value: "uio".into(),
+            },
+        },
+    ];
+    let (size, root) = serialize::<[Player], _>(players.clone(), &mut buffer).unwrap();
+    assert_eq!(
+        deserialize_with_size::<[Player], Vec<Player>>(&buffer[..size], root).unwrap(),
+        players
+    );
+
+    let (size, root) = serialize::<Wrapper<u32>, _>(Wrapper(5u32), &mut buffer).unwrap();
+    assert_eq!(
+        deserialize_with_size::<u32, u32>(&buffer[..size], root).unwrap(),
+        5
+    );
+    let mut wrapper = Wrapper(0u32);
+    deserialize_in_place_with_size::<Wrapper<u32>, _>(&mut wrapper, &buffer[..size], root).unwrap();
+    assert_eq!(wrapper, Wrapper(5));
+}
+
+#[cfg(all(feature = "pod", feature = "alloc", feature = "derive"))]
+#[test]
+fn test_pod() {
+    use alkahest_proc::alkahest;
+    use alloc::{string::String, vec, vec::Vec};
+
+    use crate::{DeserializeError, PodSlice, SwapBytes};
+
+    #[derive(Clone, Copy, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+    #[repr(C)]
+    #[alkahest(pod)]
+    struct Vector3 {
+        x: f32,
+        y: f32,
+        z: f32,
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+    #[repr(C)]
+    #[alkahest(pod)]
+    struct Triangle {
+        vertices: [Vector3; 3],
+        normal: Vector3,
+        flags: u32,
+    }
+
+    #[alkahest(Formula)]
+    struct MeshFormula {
+        name: As<str>,
+        triangles: PodSlice<Triangle>,
+    }
+
+    #[alkahest(Serialize<MeshFormula>, SerializeRef<MeshFormula>, Deserialize<'_, MeshFormula>)]
+    #[derive(Debug, PartialEq)]
+    struct Mesh {
+        name: String,
+        triangles: Vec<Triangle>,
+    }
+
+    const _: () = assert!(<Triangle as Formula>::EXACT_SIZE);
+    assert_eq!(
+        <Triangle as Formula>::MAX_STACK_SIZE,
+        Some(core::mem::size_of::<Triangle>())
+    );
+
+    let triangle = |i: u32| {
+        let v = |j: u32| Vector3 {
+            x: (i * 10 + j) as f32,
+            y: 1.0,
+            z: -1.0,
+        };
+        Triangle {
+            vertices: [v(0), v(1), v(2)],
+            normal: v(3),
+            flags: i,
+        }
+    };
+
+    let mut buffer = [0u8; 1024];
+
+    // Single value is its in-memory representation.
+    let (size, root) = serialize::<Triangle, _>(triangle(1), &mut buffer).unwrap();
+    assert_eq!(size, core::mem::size_of::<Triangle>());
+    assert_eq!(&buffer[..size], bytemuck::bytes_of(&triangle(1)));
+    assert_eq!(
+        deserialize_with_size::<Triangle, Triangle>(&buffer[..size], root).unwrap(),
+        triangle(1)
+    );
+
+    // Slice is a single block of bytes.
+    let triangles: Vec<Triangle> = (0..5).map(triangle).collect();
+    let (size, root) = serialize::<PodSlice<Triangle>, _>(&triangles, &mut buffer).unwrap();
+    assert_eq!(&buffer[..size], bytemuck::cast_slice::<_, u8>(&triangles));
+    assert_eq!(
+        deserialize_with_size::<PodSlice<Triangle>, Vec<Triangle>>(&buffer[..size], root).unwrap(),
+        triangles
+    );
+
+    // `[F]` formula places elements in reverse order.
+    let mut slice_buffer = [0u8; 1024];
+    let (slice_size, _) = serialize::<[Triangle], _>(&triangles, &mut slice_buffer).unwrap();
+    assert_eq!(slice_size, size);
+    let reversed: Vec<Triangle> = triangles.iter().rev().copied().collect();
+    assert_eq!(
+        &slice_buffer[..size],
+        bytemuck::cast_slice::<_, u8>(&reversed)
+    );
+
+    let mesh = Mesh {
+        name: "mesh".into(),
+        triangles: vec![triangle(7), triangle(8)],
+    };
+    let (size, root) = serialize::<MeshFormula, _>(&mesh, &mut buffer).unwrap();
+    assert_eq!(
+        deserialize_with_size::<MeshFormula, Mesh>(&buffer[..size], root).unwrap(),
+        mesh
+    );
+
+    // Big-endian targets swap bytes of each field.
+    let mut swapped = triangle(1);
+    swapped.swap_bytes();
+    assert_eq!(swapped.flags, 1u32.swap_bytes());
+    assert_eq!(swapped.normal.y.to_bits(), 1.0f32.to_bits().swap_bytes());
+    swapped.swap_bytes();
+    assert_eq!(swapped, triangle(1));
+
+    // Truncated input is rejected.
+    let (size, root) = serialize::<PodSlice<Triangle>, _>(&triangles, &mut buffer).unwrap();
+    assert!(matches!(
+        deserialize_with_size::<PodSlice<Triangle>, Vec<Triangle>>(&buffer[..size - 1], root - 1),
+        Err(DeserializeError::WrongLength)
+    ));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_archive() {
+    use alloc::vec::Vec;
+
+    use crate::{
+        archive::{write_archive, write_archive_to_vec, Archive, ARCHIVE_HEADER_SIZE},
+        deserialize::DeserializeError,
+    };
+
+    type F = (u32, Vec<u32>);
+
+    let mut bytes = Vec::new();
+    let size = write_archive_to_vec::<F, _>((7u32, [1u32, 2, 3]), &mut bytes);
+    assert_eq!(size, bytes.len());
+    assert_eq!(&bytes[..8], b"ALKAHEST");
+
+    let mut written = Vec::new();
+    assert_eq!(
+        write_archive::<F, _, _>((7u32, [1u32, 2, 3]), &mut written).unwrap(),
+        size
+    );
+    assert_eq!(written, bytes);
+
+    // Trailing bytes are ignored.
+    bytes.extend_from_slice(&[0xFF; 5]);
+    let archive = Archive::<_, F>::new(&bytes[..]).unwrap();
+    assert_eq!(archive.size(), size);
+    let (a, b) = archive.get::<(u32, Vec<u32>)>().unwrap();
+    assert_eq!(a, 7);
+    assert_eq!(b, [1, 2, 3]);
+    assert_eq!(
+        archive.lazy().get::<(u32, Vec<u32>)>().unwrap().1,
+        [1, 2, 3]
+    );
+
+    let mut wrong = bytes.clone();
+    wrong[10] += 1;
+    assert!(matches!(
+        Archive::<_, F>::new(&wrong[..]),
+        Err(DeserializeError::Incompatible)
+    ));
+    assert!(matches!(
+        Archive::<_, F>::new(&bytes[..ARCHIVE_HEADER_SIZE - 1]),
+        Err(DeserializeError::OutOfBounds)
+    ));
+    assert!(Archive::<_, F>::new(&bytes[..size - 1]).is_err());
+
+    #[cfg(feature = "mmap")]
+    {
+        let path = std::env::temp_dir().join(format!("alkahest-{}.alk", std::process::id()));
+        std::fs::write(&path, &bytes).unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+
+        // SAFETY: The file is private to this test and is not modified while mapped.
+        #[allow(unsafe_code)]
+        let archive: crate::MmapArchive<F> = unsafe { Archive::open(&file) }.unwrap();
+        assert_eq!(archive.get::<(u32, Vec<u32>)>().unwrap().0, 7);
+        drop(archive);
+
+        std::fs::write(&path, &wrong).unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+        // SAFETY: Same as above.
+        #[allow(unsafe_code)]
+        let err = unsafe { Archive::<_, F>::open(&file) }.err().unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_checked_packet() {
+    use alloc::{string::String, vec, vec::Vec};
+
+    use crate::{
+        checksum::{
+            crc32c, crc32c_combine, read_packet_checked, write_packet_checked,
+            write_packet_checked_to_vec, write_packet_checked_to_writer, CHECKSUM_SIZE,
+        },
+        deserialize::DeserializeError,
+    };
+
+    assert_eq!(crc32c(b"123456789"), 0xE306_9283);
+    for split in 0..=9 {
+        let (first, second) = b"123456789".split_at(split);
+        assert_eq!(
+            crc32c_combine(crc32c(first), crc32c(second), second.len()),
+            0xE306_9283
+        );
+    }
+
+    let mut bytes = Vec::new();
+    let size = write_packet_checked_to_vec::<(u32, str), _>((42u32, "checked"), &mut bytes);
+    assert_eq!(size, bytes.len());
+
+    let mut written = Vec::new();
+    assert_eq!(
+        write_packet_checked_to_writer::<(u32, str), _, _>((42u32, "checked"), &mut written)
+            .unwrap(),
+        size
+    );
+    assert_eq!(written, bytes);
+
+    let mut buffer = [0; 64];
+    assert_eq!(
+        write_packet_checked::<(u32, str), _>((42u32, "checked"), &mut buffer).unwrap(),
+        size
+    );
+    assert_eq!(&buffer[..size], &bytes[..]);
+    assert!(
+        write_packet_checked::<(u32, str), _>((42u32, "checked"), &mut buffer[..size - 1]).is_err()
+    );
+
+    let ((a, b), consumed) = read_packet_checked::<(u32, str), (u32, String)>(&bytes).unwrap();
+    assert_eq!((a, b.as_str(), consumed), (42, "checked", size));
+
+    for idx in 0..size {
+        let mut corrupted = bytes.clone();
+        corrupted[idx] ^= 0x10;
+        assert!(read_packet_checked::<(u32, str), (u32, String)>(&corrupted).is_err());
+    }
+
+    // Checksum is updated while many values are written to the heap.
+    let values: Vec<Vec<u32>> = (0..100).map(|len| (0..len).collect()).collect();
+    let mut large = Vec::new();
+    let large_size = write_packet_checked_to_vec::<[Vec<u32>], _>(&values, &mut large);
+    let (packet, checksum) = large.split_at(large_size - CHECKSUM_SIZE);
+    assert_eq!(checksum, crc32c(packet).to_le_bytes());
+
+    let mut buffer = vec![0; large_size];
+    assert_eq!(
+        write_packet_checked::<[Vec<u32>], _>(&values, &mut buffer).unwrap(),
+        large_size
+    );
+    assert_eq!(buffer, large);
+
+    // Padding is written as zeros, reserved heap is passed through.
+    type Padded = [(Option<u32>, As<str>, Ref<[Option<u16>]>)];
+    let padded = (0..50)
+        .map(|idx: u32| {
+            let value = (idx % 3 == 0).then_some(idx);
+            let list: Vec<Option<u16>> =
+                (0..idx as u16).map(|x| (x % 2 == 0).then_some(x)).collect();
+            (value, "padded", list)
+        })
+        .collect::<Vec<_>>();
+    let mut buffer = vec![0xaa; 16384];
+    let padded_size = write_packet_checked::<Padded, _>(&padded, &mut buffer).unwrap();
+    let (packet, checksum) = buffer[..padded_size].split_at(padded_size - CHECKSUM_SIZE);
+    assert_eq!(checksum, crc32c(packet).to_le_bytes());
+
+    let mut padded_vec = vec![0xaa; 100];
+    assert_eq!(
+        write_packet_checked_to_vec::<Padded, _>(&padded, &mut padded_vec),
+        padded_size
+    );
+    let (packet, checksum) = padded_vec.split_at(padded_size - CHECKSUM_SIZE);
+    assert_eq!(checksum, crc32c(packet).to_le_bytes());
+
+    let (de, _) = read_packet_checked::<[Vec<u32>], Vec<Vec<u32>>>(&large).unwrap();
+    assert_eq!(de, values);
+
+    let mut corrupted = bytes.clone();
+    corrupted[size - CHECKSUM_SIZE - 1] ^= 1;
+    assert!(matches!(
+        read_packet_checked::<(u32, str), (u32, String)>(&corrupted),
+        Err(DeserializeError::ChecksumMismatch)
+    ));
+    assert!(matches!(
+        read_packet_checked::<(u32, str), (u32, String)>(&bytes[..size - 1]),
+        Err(DeserializeError::OutOfBounds)
+    ));
+}
+
+#[cfg(any(feature = "lz4", feature = "zstd", feature = "deflate"))]
+#[test]
+fn test_compressed() {
+    use alloc::{string::String, vec, vec::Vec};
+
+    use crate::{
+        compressed::{Compressed, Compression},
+        deserialize::DeserializeError,
+        packet::{read_packet, write_packet_to_vec},
+        size::SIZE_STACK,
+    };
+
+    fn test_compression<C: Compression>() {
+        type F<C> = (u32, Compressed<(Vec<u32>, String), C>);
+
+        let values = vec![7u32; 1000];
+        let name = String::from("compressed");
+
+        let mut bytes = Vec::new();
+        let size = write_packet_to_vec::<F<C>, _>((1u32, (&values, &name)), &mut bytes);
+        assert!(size < values.len() * 4 / 10);
+
+        let ((a, (b, c)), _) = read_packet::<F<C>, (u32, (Vec<u32>, String))>(&bytes).unwrap();
+        assert_eq!(a, 1);
+        assert_eq!(b, values);
+        assert_eq!(c, name);
+
+        // Other fields are readable without decompression.
+        let ((a, _), _) = read_packet::<(u32, Vec<u8>), (u32, Vec<u8>)>(&bytes).unwrap();
+        assert_eq!(a, 1);
+
+        // Corrupt compressed payload, keeping packet reference and LZ4 length prefix.
+        let mut corrupted = bytes.clone();
+        let stack = <F<C> as crate::formula::Formula>::MAX_STACK_SIZE.unwrap();
+        for byte in &mut corrupted[2 * SIZE_STACK..size - stack] {
+            *byte = 0xFF;
+        }
+        assert!(matches!(
+            read_packet::<F<C>, (u32, (Vec<u32>, String))>(&corrupted),
+            Err(DeserializeError::Incompatible)
+        ));
+
+        // Decompressed data larger than the limit is rejected.
+        type Limited<C> = (u32, Compressed<(Vec<u32>, String), C, 1000>);
+        assert!(matches!(
+            read_packet::<Limited<C>, (u32, (Vec<u32>, String))>(&bytes),
+            Err(DeserializeError::WrongLength)
+        ));
+
+        let bomb = C::compress(&vec![0; 1 << 20]);
+        assert!(bomb.len() < 1 << 13);
+        assert!(matches!(
+            C::decompress(&bomb, 1 << 19),
+            Err(DeserializeError::WrongLength)
+        ));
+        assert_eq!(C::decompress(&bomb, 1 << 20).unwrap().len(), 1 << 20);
+    }
+
+    #[cfg(feature = "lz4")]
+    test_compression::<crate::compressed::Lz4>();
+
+    #[cfg(feature = "zstd")]
+    test_compression::<crate::compressed::Zstd>();
+
+    #[cfg(feature = "deflate")]
+    test_compression::<crate::compressed::Deflate>();
+}
+
+#[cfg(any(feature = "lz4", feature = "zstd", feature = "deflate"))]
+#[test]
+fn test_compressed_exceeds_limit() {
+    use alloc::{vec, vec::Vec};
+
+    use crate::{
+        compressed::Compressed,
+        deserialize::DeserializeError,
+        packet::{read_packet, write_packet_to_vec},
+    };
+
+    #[cfg(feature = "lz4")]
+    type C = crate::compressed::Lz4;
+
+    #[cfg(all(not(feature = "lz4"), feature = "zstd"))]
+    type C = crate::compressed::Zstd;
+
+    #[cfg(all(not(feature = "lz4"), not(feature = "zstd")))]
+    type C = crate::compressed::Deflate;
+
+    // Limit is not checked on serialization.
+    let mut bytes = Vec::new();
+    write_packet_to_vec::<Compressed<Vec<u32>, C, 1000>, _>(vec![7u32; 1000], &mut bytes);
+
+    assert!(matches!(
+        read_packet::<Compressed<Vec<u32>, C, 1000>, Vec<u32>>(&bytes),
+        Err(DeserializeError::WrongLength)
+    ));
+
+    let (value, _) = read_packet::<Compressed<Vec<u32>, C, 8000>, Vec<u32>>(&bytes).unwrap();
+    assert_eq!(value, vec![7u32; 1000]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_ref_width() {
+    use alloc::{string::String, vec::Vec};
+
+    use crate::{
+        packet::{read_packet, write_packet_to_vec},
+        size::{FixedUsize, SIZE_STACK},
+    };
+
+    type Narrow = (Ref<str, u8>, Ref<[u32], u16>);
+    type Wide = (Ref<str>, Ref<[u32], FixedUsize>);
+
+    assert_eq!(<Ref<str, u8> as Formula>::MAX_STACK_SIZE, Some(2));
+    assert_eq!(<Ref<u32, u16> as Formula>::MAX_STACK_SIZE, Some(2));
+    assert_eq!(<Ref<str, u64> as Formula>::MAX_STACK_SIZE, Some(16));
+
+    let value = ("narrow", [1u32, 2, 3]);
+
+    let mut narrow = Vec::new();
+    let narrow_size = write_packet_to_vec::<Narrow, _>(value, &mut narrow);
+    let ((s, v), _) = read_packet::<Narrow, (String, Vec<u32>)>(&narrow).unwrap();
+    assert_eq!(s, "narrow");
+    assert_eq!(v, [1, 2, 3]);
+
+    let mut default = Vec::new();
+    let default_size = write_packet_to_vec::<Wide, _>(value, &mut default);
+    let ((s, v), _) = read_packet::<Wide, (&str, Vec<u32>)>(&default).unwrap();
+    assert_eq!(s, "narrow");
+    assert_eq!(v, [1, 2, 3]);
+
+    assert_eq!(default_size - narrow_size, 4 * SIZE_STACK - 2 - 4);
+
+    // Same layout as default width.
+    let mut plain = Vec::new();
+    write_packet_to_vec::<(String, Vec<u32>), _>(value, &mut plain);
+    assert_eq!(plain, default);
+}
+
+#[test]
+fn test_ref_width_overflow() {
+    use crate::buffer::BufferExhausted;
+
+    type F = (Ref<[u8; 300], u8>,);
+
+    let mut buffer = [0u8; 1024];
+    assert_eq!(
+        serialize::<F, _>(([0u8; 300],), &mut buffer),
+        Err(BufferExhausted)
+    );
+
+    // Slow path without size hint.
+    assert_eq!(
+        serialize::<Ref<[u8], u8>, _>(core::iter::repeat(0u8).take(300), &mut buffer),
+        Err(BufferExhausted)
+    );
+
+    assert_eq!(serialized_size::<F, _>(([0u8; 300],)), (301, 1));
+
+    #[cfg(feature = "std")]
+    {
+        let mut output = alloc::vec::Vec::new();
+        let err = crate::serialize::serialize_to_writer::<F, _, _>(([0u8; 300],), &mut output)
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+#[should_panic(expected = "does not fit reference width")]
+fn test_ref_width_overflow_infallible() {
+    let mut bytes = alloc::vec::Vec::new();
+    crate::serialize::serialize_to_vec::<Ref<[u8; 300], u8>, _>([0u8; 300], &mut bytes);
+}
+
+#[test]
+#[should_panic(expected = "Reserved enough space")]
+fn test_ref_broken_size_hint() {
+    use crate::{buffer::Buffer, serialize::Sizes};
+
+    struct Liar;
+
+    impl Serialize<[u8]> for Liar {
+        fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+        where
+            B: Buffer,
+        {
+            <[u8; 8] as Serialize<[u8]>>::serialize([1; 8], sizes, buffer)
+        }
+
+        fn size_hint(&self) -> Option<Sizes> {
+            Some(Sizes::with_stack(4))
+        }
+    }
+
+    let mut buffer = [0u8; 64];
+    let _ = serialize::<Ref<[u8]>, _>(Liar, &mut buffer);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_write_to_writer_broken_size_hint() {
+    use alloc::vec::Vec;
+
+    use crate::{buffer::Buffer, packet::write_packet_to_writer, serialize::Sizes};
+
+    /// Size hint is too large to reserve heap for the value,
+    /// so mismatch is found only after the header is written.
+    struct Liar;
+
+    impl Serialize<[u8]> for Liar {
+        fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+        where
+            B: Buffer,
+        {
+            <[u8; 8] as Serialize<[u8]>>::serialize([1; 8], sizes, buffer)
+        }
+
+        fn size_hint(&self) -> Option<Sizes> {
+            Some(Sizes::with_stack(100_000))
+        }
+    }
+
+    let mut output = Vec::new();
+    let err = write_packet_to_writer::<[u8], _, _>(Liar, &mut output).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_ref_vlq_width() {
+    use alloc::{string::String, vec, vec::Vec};
+
+    use crate::packet::{read_packet, write_packet_to_vec};
+
+    type F = (u32, Ref<str, Vlq>, Ref<[u32], Vlq>);
+
+    let value = (5u32, "vlq", [1u32, 2, 3]);
+
+    let mut bytes = Vec::new();
+    let size = write_packet_to_vec::<F, _>(value, &mut bytes);
+    let ((a, b, c), _) = read_packet::<F, (u32, String, Vec<u32>)>(&bytes).unwrap();
+    assert_eq!(a, 5);
+    assert_eq!(b, "vlq");
+    assert_eq!(c, [1, 2, 3]);
+
+    let mut plain = Vec::new();
+    let plain_size = write_packet_to_vec::<(u32, String, Vec<u32>), _>(value, &mut plain);
+    assert!(size < plain_size);
+
+    // Last field needs no length prefix.
+    let mut bytes = Vec::new();
+    write_packet_to_vec::<Ref<str, Vlq>, _>("vlq", &mut bytes);
+    let (s, _) = read_packet::<Ref<str, Vlq>, &str>(&bytes).unwrap();
+    assert_eq!(s, "vlq");
+    let address = 2 * crate::size::SIZE_STACK + 3;
+    assert_eq!(
+        bytes.len(),
+        address + 1 + crate::vlq::size_hint(address).stack
+    );
+
+    // Vlq mode of standard formulas is wire-compatible with `Ref`.
+    let value = vec![String::from("a"), String::from("bc")];
+    let mut bytes = Vec::new();
+    let size = write_packet_to_vec::<VlqVec<VlqString>, _>(&value, &mut bytes);
+    let (de, _) = read_packet::<Ref<[Ref<str, Vlq>], Vlq>, Vec<String>>(&bytes).unwrap();
+    assert_eq!(de, value);
+
+    let mut plain = Vec::new();
+    let plain_size = write_packet_to_vec::<Vec<String>, _>(&value, &mut plain);
+    assert!(size < plain_size);
+}
+
+#[cfg(all(feature = "alloc", feature = "derive"))]
+#[test]
+fn test_field_width() {
+    use alkahest_proc::alkahest;
+    use alloc::{string::String, vec::Vec};
+
+    use crate::{
+        packet::{read_packet, write_packet_to_vec},
+        size::SIZE_STACK,
+    };
+
+    #[alkahest(Formula, lazy, width = u8)]
+    struct Narrow {
+        tail: u32,
+        name: As<str>,
+        values: As<[u32]>,
+        note: As<str>,
+    }
+
+    #[alkahest(Formula)]
+    struct Wide {
+        tail: u32,
+        name: As<str>,
+        values: As<[u32]>,
+        note: As<str>,
+    }
+
+    #[alkahest(Formula, width = u16)]
+    enum Message {
+        Text { text: As<str>, note: As<str> },
+        Empty,
+    }
+
+    #[alkahest(Serialize<Narrow>, Deserialize<'_, Narrow>)]
+    #[alkahest(Serialize<Wide>, Deserialize<'_, Wide>)]
+    #[derive(Debug, PartialEq)]
+    struct Data {
+        tail: u32,
+        name: String,
+        values: Vec<u32>,
+        note: String,
+    }
+
+    #[alkahest(Serialize<Message>, Deserialize<'_, Message>)]
+    #[derive(Debug, PartialEq)]
+    enum MessageData {
+        Text { text: String, note: String },
+        Empty,
+    }
+
+    let data = || Data {
+        tail: 42,
+        name: "narrow".into(),
+        values: alloc::vec![1, 2, 3],
+        note: "last".into(),
+    };
+
+    let mut narrow = Vec::new();
+    let narrow_size = write_packet_to_vec::<Narrow, _>(data(), &mut narrow);
+    let (value, _) = read_packet::<Narrow, Data>(&narrow).unwrap();
+    assert_eq!(value, data());
+
+    let mut wide = Vec::new();
+    let wide_size = write_packet_to_vec::<Wide, _>(data(), &mut wide);
+    let (value, _) = read_packet::<Wide, Data>(&wide).unwrap();
+    assert_eq!(value, data());
+
+    // Two fields are prefixed with their size.
+    assert_eq!(wide_size - narrow_size, 2 * (SIZE_STACK - 1));
+
+    let mut buffer = [0u8; 1024];
+    let (size, root) = serialize::<Narrow, _>(data(), &mut buffer).unwrap();
+    let lazy = deserialize_with_size::<Narrow, Lazy<Narrow>>(&buffer[..size], root).unwrap();
+    assert_eq!(
+        lazy.field_values().unwrap().get::<Vec<u32>>().unwrap(),
+        [1, 2, 3]
+    );
+    assert_eq!(lazy.field_note().unwrap().get::<&str>().unwrap(), "last");
+
+    let message = || MessageData::Text {
+        text: "hello".into(),
+        note: "world".into(),
+    };
+    let mut bytes = Vec::new();
+    write_packet_to_vec::<Message, _>(message(), &mut bytes);
+    let (value, _) = read_packet::<Message, MessageData>(&bytes).unwrap();
+    assert_eq!(value, message());
+
+    // Size prefix does not fit `u8`.
+    let long = Data {
+        name: "x".repeat(300),
+        ..data()
+    };
+    assert!(serialize::<Narrow, _>(long, &mut buffer).is_err());
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_packet_width() {
+    use alloc::vec::Vec;
+
+    use crate::{
+        batch::PacketIter,
+        buffer::{BufferExhausted, CheckedFixedBuffer},
+        packet::{
+            packet_size, read_packet, read_packet_size, write_packet_into, write_packet_to_vec,
+            Packet,
+        },
+        size::SIZE_STACK,
+    };
+
+    type Narrow = Packet<(u32, [u16]), u8>;
+
+    let mut narrow = Vec::new();
+    let narrow_size = write_packet_to_vec::<Narrow, _>((5u32, [1u16, 2]), &mut narrow);
+    assert_eq!(narrow_size, 2 + 4 + 4);
+    assert_eq!(packet_size::<Narrow, _>((5u32, [1u16, 2])), narrow_size);
+    assert_eq!(read_packet_size::<Narrow>(&narrow), Some(narrow_size));
+
+    let ((a, b), _) = read_packet::<Narrow, (u32, Vec<u16>)>(&narrow).unwrap();
+    assert_eq!((a, b), (5, alloc::vec![1, 2]));
+
+    // Default width writes the same bytes as the value formula.
+    let mut plain = Vec::new();
+    write_packet_to_vec::<(u32, [u16]), _>((5u32, [1u16, 2]), &mut plain);
+    let mut default = Vec::new();
+    write_packet_to_vec::<Packet<(u32, [u16])>, _>((5u32, [1u16, 2]), &mut default);
+    assert_eq!(plain, default);
+    assert_eq!(plain.len() - narrow_size, 2 * (SIZE_STACK - 1));
+
+    // Exact size formula has address only.
+    let mut bytes = Vec::new();
+    write_packet_to_vec::<Packet<u32, u16>, _>(7u32, &mut bytes);
+    assert_eq!(bytes.len(), 2 + 4);
+
+    let mut iter = PacketIter::<Narrow>::new(&narrow);
+    assert_eq!(iter.next().unwrap().unwrap(), &narrow[..]);
+    assert!(iter.next().is_none());
+
+    let mut buffer = [0u8; 1024];
+    assert_eq!(
+        write_packet_into::<Packet<[u8], u8>, _, _>(
+            [0u8; 300],
+            CheckedFixedBuffer::new(&mut buffer)
+        ),
+        Err(BufferExhausted)
+    );
+}
+
+#[test]
+fn test_zigzag() {
+    use crate::{deserialize::DeserializeError, zigzag::ZigZag};
+
+    let mut buffer = [0u8; 256];
+
+    for value in [0i64, -1, 1, -64, 63, i64::MIN, i64::MAX] {
+        let (size, root) = serialize::<ZigZag, i64>(value, &mut buffer).unwrap();
+        assert_eq!(
+            deserialize_with_size::<ZigZag, i64>(&buffer[..size], root).unwrap(),
+            value
+        );
+        assert_eq!(
+            deserialize_with_size::<ZigZag, i128>(&buffer[..size], root).unwrap(),
+            i128::from(value)
+        );
+    }
+
+    for value in [i8::MIN, -1, 0, i8::MAX] {
+        let (size, root) = serialize::<ZigZag, i8>(value, &mut buffer).unwrap();
+        assert_eq!(
+            deserialize_with_size::<ZigZag, i8>(&buffer[..size], root).unwrap(),
+            value
+        );
+    }
+
+    let (size, root) = serialize::<ZigZag, i16>(-129, &mut buffer).unwrap();
+    assert!(matches!(
+        deserialize_with_size::<ZigZag, i8>(&buffer[..size], root),
+        Err(DeserializeError::IntegerOverflow)
+    ));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_delta() {
+    use alloc::vec::Vec;
+
+    use crate::zigzag::{Delta, ZigZag};
+
+    let values: Vec<i32> = (0..100).map(|idx| 1_000_000 - idx * 3).collect();
+
+    let mut buffer = [0u8; 1024];
+    let (size, root) = serialize::<Delta<[i32]>, _>(&values, &mut buffer).unwrap();
+    assert_eq!(size, 4 + 99);
+    let de = deserialize_with_size::<Delta<[i32]>, Vec<i32>>(&buffer[..size], root).unwrap();
+    assert_eq!(de, values);
+
+    // Wrapping deltas and non-last field.
+    type F = (Delta<[u8]>, ZigZag);
+    let value = ([0u8, 255, 1, 128], -3i32);
+    let (size, root) = serialize::<F, _>(value, &mut buffer).unwrap();
+    let (bytes, tail) = deserialize_with_size::<F, (Vec<u8>, i32)>(&buffer[..size], root).unwrap();
+    assert_eq!(bytes, [0, 255, 1, 128]);
+    assert_eq!(tail, -3);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_endian() {
+    use alloc::vec::Vec;
+
+    use crate::endian::{Be, Le};
+
+    type Header = (Be<u16>, Le<u32>, Be<f32>, Be<i64>);
+
+    let mut buffer = [0u8; 256];
+    let (size, root) =
+        serialize::<Header, _>((0x0102u16, 0x0304_0506u32, 1.5f32, -2i64), &mut buffer).unwrap();
+    assert_eq!(size, 18);
+
+    // Stack grows from the back, so the first field is at the end.
+    assert_eq!(&buffer[16..18], &[1, 2]);
+    assert_eq!(&buffer[12..16], &[6, 5, 4, 3]);
+    assert_eq!(&buffer[8..12], &1.5f32.to_be_bytes());
+    assert_eq!(&buffer[..8], &(-2i64).to_be_bytes());
+
+    let (a, b, c, d) =
+        deserialize_with_size::<Header, (u16, u64, f64, i64)>(&buffer[..size], root).unwrap();
+    assert_eq!((a, b, c, d), (0x0102, 0x0304_0506, 1.5, -2));
+
+    let values = [1u32, 0x0A0B_0C0D, u32::MAX];
+    let (size, root) = serialize::<[Be<u32>], _>(values, &mut buffer).unwrap();
+    let de = deserialize_with_size::<[Be<u32>], Vec<u32>>(&buffer[..size], root).unwrap();
+    assert_eq!(de, values);
+
+    let lazy = deserialize_with_size::<[Be<u32>], Lazy<[Be<u32>]>>(&buffer[..size], root).unwrap();
+    let mut iter = lazy.iter::<u32>();
+    assert_eq!(iter.nth(1).unwrap().unwrap(), 0x0A0B_0C0D);
+
+    #[cfg(feature = "derive")]
+    {
+        use crate::{alkahest, packet::read_packet, write_packet_to_vec};
+
+        #[alkahest(Formula)]
+        struct WireHeader {
+            magic: Be<u32>,
+            len: Be<u16>,
+        }
+
+        #[alkahest(Serialize<WireHeader>, SerializeRef<WireHeader>, Deserialize<'_, WireHeader>)]
+        #[derive(Debug, PartialEq)]
+        struct Header {
+            magic: u32,
+            len: u16,
+        }
+
+        let header = Header {
+            magic: 0xCAFE_BABE,
+            len: 12,
+        };
+        let mut bytes = Vec::new();
+        write_packet_to_vec::<WireHeader, _>(&header, &mut bytes);
+        assert!(bytes.ends_with(&[0, 12, 0xCA, 0xFE, 0xBA, 0xBE]));
+        let (de, _) = read_packet::<WireHeader, Header>(&bytes).unwrap();
+        assert_eq!(de, header);
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_bits() {
+    use alloc::vec::Vec;
+
+    use crate::{
+        bits::{BitSet, Bits},
+        deserialize::DeserializeError,
+    };
+
+    let mut buffer = [0u8; 256];
+
+    type Input = (Bits<3>, Bits<5>, BitSet, u8);
+
+    for len in [0, 1, 7, 8, 9, 20] {
+        let flags: Vec<bool> = (0..len).map(|idx| idx % 3 == 0).collect();
+        let value = (5u8, [true, false, false, true, true], &flags, 42u8);
+
+        let (size, root) = serialize::<Input, _>(value, &mut buffer).unwrap();
+        let (a, b, c, d) =
+            deserialize_with_size::<Input, (u32, [bool; 5], Vec<bool>, u8)>(&buffer[..size], root)
+                .unwrap();
+        assert_eq!(a, 5);
+        assert_eq!(b, [true, false, false, true, true]);
+        assert_eq!(c, flags);
+        assert_eq!(d, 42);
+    }
+
+    let (size, root) = serialize::<Bits<12>, u16>(0xABC, &mut buffer).unwrap();
+    assert_eq!(size, 2);
+    assert!(matches!(
+        deserialize_with_size::<Bits<12>, u8>(&buffer[..size], root),
+        Err(DeserializeError::IntegerOverflow)
+    ));
+
+    // Bits above `N` must be unset.
+    assert!(matches!(
+        deserialize_with_size::<Bits<4>, u8>(&[0x1F], 1),
+        Err(DeserializeError::Incompatible)
+    ));
+}
+
+#[test]
+#[should_panic(expected = "does not fit 4 bits")]
+fn test_bits_overflow() {
+    let mut buffer = [0u8; 16];
+    let _ = serialize::<crate::bits::Bits<4>, u8>(16, &mut buffer);
+}
+
+#[cfg(feature = "derive")]
+#[test]
+#[should_panic(expected = "does not fit 3 bits")]
+fn test_bits_derive_overflow() {
+    use alkahest_proc::alkahest;
+
+    use crate::bits::Bits;
+
+    #[alkahest(Formula)]
+    struct InputFormula {
+        buttons: Bits<3>,
+        axis: u8,
+    }
+
+    #[alkahest(Serialize<InputFormula>)]
+    struct Input {
+        buttons: u8,
+        axis: u8,
+    }
+
+    let mut buffer = [0u8; 16];
+    let _ = serialize::<InputFormula, _>(
+        Input {
+            buttons: 8,
+            axis: 1,
+        },
+        &mut buffer,
+    );
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn test_bits_packed() {
+    use alkahest_proc::alkahest;
+
+    use crate::{bits::BitField, deserialize::DeserializeError};
+
+    #[alkahest(bits)]
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Shape {
+        Empty,
+        Dot(bool),
+        Line {
+            #[alkahest(bits = 4)]
+            len: u8,
+            vertical: bool,
+        },
+    }
+
+    #[alkahest(bits)]
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Flags<T>(bool, T, #[alkahest(bits = 2)] u16);
+
+    #[alkahest(bits)]
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Cell {
+        visible: bool,
+        shape: Option<Shape>,
+        flags: Flags<bool>,
+    }
+
+    #[alkahest(Formula, Serialize, Deserialize)]
+    #[derive(Debug, PartialEq)]
+    struct Frame {
+        id: u16,
+        cell: Cell,
+    }
+
+    assert_eq!(<Shape as BitField>::BITS, 2 + 5);
+    assert_eq!(<Flags<bool> as BitField>::BITS, 4);
+    assert_eq!(<Cell as BitField>::BITS, 1 + 8 + 4);
+    assert_eq!(<Cell as Formula>::MAX_STACK_SIZE, Some(2));
+    assert_eq!(<Frame as Formula>::MAX_STACK_SIZE, Some(4));
+
+    let mut buffer = [0u8; 16];
+
+    for shape in [
+        None,
+        Some(Shape::Empty),
+        Some(Shape::Dot(true)),
+        Some(Shape::Line {
+            len: 15,
+            vertical: true,
+        }),
+    ] {
+        let cell = Cell {
+            visible: true,
+            shape,
+            flags: Flags(false, true, 3),
+        };
+
+        let (size, root) = serialize::<Cell, _>(cell, &mut buffer).unwrap();
+        assert_eq!(size, 2);
+        assert_eq!(
+            deserialize_with_size::<Cell, Cell>(&buffer[..size], root).unwrap(),
+            cell
+        );
+
+        let frame = Frame { id: 7, cell };
+        let (size, root) = serialize::<Frame, _>(frame, &mut buffer).unwrap();
+        assert_eq!(size, 4);
+        assert_eq!(
+            deserialize_with_size::<Frame, Frame>(&buffer[..size], root).unwrap(),
+            Frame { id: 7, cell }
+        );
+    }
+
+    // `Dot(true)` is variant 1 followed by the flag.
+    let (size, root) = serialize::<Shape, _>(Shape::Dot(true), &mut buffer).unwrap();
+    assert_eq!(&buffer[..size], &[0b101]);
+
+    // Variant 3 does not exist.
+    assert!(matches!(
+        deserialize_with_size::<Shape, Shape>(&[0b11], root),
+        Err(DeserializeError::WrongVariant(3))
+    ));
+
+    // Bits past the fields of the variant must be unset.
+    assert!(matches!(
+        deserialize_with_size::<Shape, Shape>(&[0b1001], root),
+        Err(DeserializeError::Incompatible)
+    ));
+
+    // Bits past the whole value must be unset.
+    assert!(matches!(
+        deserialize_with_size::<Shape, Shape>(&[0b1000_0000], root),
+        Err(DeserializeError::Incompatible)
+    ));
+}
+
+#[cfg(feature = "derive")]
+#[test]
+#[should_panic(expected = "does not fit 2 bits")]
+fn test_bits_packed_overflow() {
+    use alkahest_proc::alkahest;
+
+    #[alkahest(bits)]
+    struct Narrow {
+        flag: bool,
+        #[alkahest(bits = 2)]
+        value: u8,
+    }
+
+    let mut buffer = [0u8; 16];
+    let _ = serialize::<Narrow, _>(
+        Narrow {
+            flag: true,
+            value: 4,
+        },
+        &mut buffer,
+    );
+}
+
+#[test]
+fn test_serialize_to_array() {
+    use crate::{
+        bits::Bits,
+        deserialize::deserialize_array,
+        endian::{Be, Le},
+        serialize::serialize_to_array,
+    };
+
+    type Frame = (Be<u16>, Bits<4>, [Le<u32>; 2], bool);
+
+    let value = (0x1234u16, 9u8, [1u32, 2], true);
+    let frame: [u8; 12] = serialize_to_array::<Frame, _, 12>(value);
+
+    let mut buffer = [0u8; 12];
+    serialize::<Frame, _>(value, &mut buffer).unwrap();
+    assert_eq!(frame, buffer);
+
+    let (a, b, c, d) = deserialize_array::<Frame, (u16, u8, [u32; 2], bool), 12>(&frame).unwrap();
+    assert_eq!(a, 0x1234);
+    assert_eq!(b, 9);
+    assert_eq!(c, [1, 2]);
+    assert!(d);
+}
+
+#[cfg(all(feature = "pod", feature = "alloc"))]
+#[test]
+fn test_aligned() {
+    use crate::{
+        aligned::Aligned,
+        deserialize::DeserializeError,
+        packet::{read_packet, write_packet_unchecked},
+    };
+
+    type F = (u8, Aligned<[u32]>, Aligned<[f64]>);
+
+    let positions = [1.0f64, 2.5, -3.0];
+    let value = (7u8, &[1u32, 2, 3][..], &positions[..]);
+
+    // `u64` storage keeps the buffer aligned for all elements.
+    let mut storage = [0u64; 16];
+    let bytes = bytemuck::cast_slice_mut::<u64, u8>(&mut storage);
+    let size = write_packet_unchecked::<F, _>(value, bytes);
+
+    let mut vec = Vec::new();
+    crate::packet::write_packet_to_vec::<F, _>(value, &mut vec);
+    assert_eq!(vec, bytes[..size]);
+
+    let ((a, b, c), _) = read_packet::<F, (u8, &[u32], &[f64])>(&bytes[..size]).unwrap();
+    assert_eq!(a, 7);
+    assert_eq!(b, [1, 2, 3]);
+    assert_eq!(c, positions);
+
+    // Elements are borrowed from the buffer.
+    let range = bytes.as_ptr_range();
+    assert!(range.contains(&b.as_ptr().cast()));
+    assert!(range.contains(&c.as_ptr().cast()));
+
+    // Shifted input can't be borrowed, but still can be copied.
+    let mut shifted = [0u64; 17];
+    let shifted = &mut bytemuck::cast_slice_mut::<u64, u8>(&mut shifted)[1..];
+    shifted[..size].copy_from_slice(&bytes[..size]);
+
+    assert!(matches!(
+        read_packet::<F, (u8, &[u32], &[f64])>(&shifted[..size]),
+        Err(DeserializeError::Misaligned)
+    ));
+
+    let ((a, b, c), _) = read_packet::<F, (u8, Vec<u32>, Vec<f64>)>(&shifted[..size]).unwrap();
+    assert_eq!(a, 7);
+    assert_eq!(b, [1, 2, 3]);
+    assert_eq!(c, positions);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_par() {
+    use alloc::{string::String, vec::Vec};
+
+    use rayon::prelude::*;
+
+    use crate::{
+        buffer::BufferExhausted,
+        endian::Be,
+        lazy::Lazy,
+        par::{serialize_par, serialize_par_to_vec},
+        reference::Ref,
+    };
+
+    type F = (u32, Be<u16>, [u8; 3]);
+
+    let values: Vec<(u32, u16, [u8; 3])> = (0..1000u32)
+        .map(|idx| (idx, idx as u16 * 3, [idx as u8; 3]))
+        .collect();
+
+    let mut expected = alloc::vec![0u8; 16384];
+    let (size, root) = serialize::<[F], _>(values.iter().copied(), &mut expected).unwrap();
+
+    let mut buffer = alloc::vec![0u8; 16384];
+    assert_eq!(
+        serialize_par::<F, _>(values.par_iter().copied(), &mut buffer),
+        Ok((size, root))
+    );
+    assert_eq!(buffer[..size], expected[..size]);
+
+    let mut vec = Vec::new();
+    assert_eq!(
+        serialize_par_to_vec::<F, _>(values.par_iter().copied(), &mut vec),
+        (size, root)
+    );
+    assert_eq!(vec[..size], expected[..size]);
+
+    assert_eq!(
+        serialize_par::<F, _>(values.par_iter().copied(), &mut buffer[..size - 1]),
+        Err(BufferExhausted)
+    );
+
+    let lazy = deserialize_with_size::<[F], Lazy<[F]>>(&buffer[..size], root).unwrap();
+    let decoded = lazy
+        .par_iter::<(u32, u16, [u8; 3])>()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(decoded, values);
+
+    // Zero-sized elements.
+    let (size, root) =
+        serialize_par::<(), _>((0..10).into_par_iter().map(|_| ()), &mut buffer).unwrap();
+    let lazy = deserialize_with_size::<[()], Lazy<[()]>>(&buffer[..size], root).unwrap();
+    assert_eq!(lazy.par_iter::<()>().count(), 10);
+
+    // Sized elements may refer to the heap.
+    let names = ["alpha", "beta", "gamma"];
+    let (size, root) = serialize::<[Ref<str>], _>(names, &mut buffer).unwrap();
+    let lazy =
+        deserialize_with_size::<[Ref<str>], Lazy<[Ref<str>]>>(&buffer[..size], root).unwrap();
+    let decoded = lazy
+        .par_iter::<String>()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(decoded, names);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_lazy_random_access() {
+    use alloc::{string::String, vec::Vec};
+
+    use crate::{lazy::Lazy, reference::Ref};
+
+    let mut buffer = [0u8; 4096];
+
+    type F = [(u32, Ref<str>)];
+
+    let scores: Vec<(u32, String)> = (0..50u32)
+        .map(|idx| (idx * 10, alloc::format!("player{}", idx)))
+        .collect();
+
+    let (size, root) = serialize::<F, _>(&scores, &mut buffer).unwrap();
+    let lazy = deserialize_with_size::<F, Lazy<F>>(&buffer[..size], root).unwrap();
+
+    assert_eq!(lazy.len().unwrap(), 50);
+    assert!(!lazy.is_empty().unwrap());
+    assert_eq!(
+        lazy.get_elem::<(u32, &str)>(42).unwrap().unwrap(),
+        (420, "player42")
+    );
+    assert!(lazy.get_elem::<(u32, &str)>(50).is_none());
+
+    let page = lazy.slice(10..20).unwrap();
+    assert_eq!(page.len().unwrap(), 10);
+    assert_eq!(page.get::<Vec<(u32, String)>>().unwrap(), scores[10..20]);
+    assert_eq!(
+        page.get_elem::<(u32, &str)>(0).unwrap().unwrap(),
+        (100, "player10")
+    );
+
+    let sub = page.slice(5..).unwrap();
+    assert_eq!(sub.len().unwrap(), 5);
+    assert_eq!(sub.get::<Vec<(u32, String)>>().unwrap(), scores[15..20]);
+    assert!(lazy.slice(50..).unwrap().is_empty().unwrap());
+    assert_eq!(lazy.slice(..=2).unwrap().len().unwrap(), 3);
+
+    let by_score =
+        |score: u32| lazy.binary_search_by_key(&score, |&(score, _): &(u32, &str)| score);
+    assert_eq!(by_score(250).unwrap(), Ok(25));
+    assert_eq!(by_score(255).unwrap(), Err(26));
+    assert_eq!(by_score(1000).unwrap(), Err(50));
+
+    let (size, root) = serialize::<[u32], _>(0..100u32, &mut buffer).unwrap();
+    let lazy = deserialize_with_size::<[u32], Lazy<[u32]>>(&buffer[..size], root).unwrap();
+    assert_eq!(lazy.binary_search(&77u32).unwrap(), Ok(77));
+    assert_eq!(
+        lazy.slice(50..60).unwrap().binary_search(&77u32).unwrap(),
+        Err(10)
+    );
+
+    // Zero-sized elements.
+    let (size, root) = serialize::<[()], _>([(), (), ()], &mut buffer).unwrap();
+    let lazy = deserialize_with_size::<[()], Lazy<[()]>>(&buffer[..size], root).unwrap();
+    assert_eq!(lazy.len().unwrap(), 3);
+    assert!(lazy.get_elem::<()>(2).is_some());
+    assert!(lazy.get_elem::<()>(3).is_none());
+
+    // Count of zero-sized elements is missing.
+    let lazy = deserialize_with_size::<[()], Lazy<[()]>>(&buffer[..size], 0).unwrap();
+    assert!(lazy.len().is_err());
+    assert!(lazy.get_elem::<()>(0).unwrap().is_err());
+}
+
+#[test]
+fn test_lazy_slice_out_of_bounds() {
+    use core::ops::Bound;
+
+    use crate::{deserialize::DeserializeError, lazy::Lazy};
+
+    let mut buffer = [0u8; 64];
+    let (size, root) = serialize::<[u32], _>([1u32, 2, 3], &mut buffer).unwrap();
+    let lazy = deserialize_with_size::<[u32], Lazy<[u32]>>(&buffer[..size], root).unwrap();
+    assert!(matches!(
+        lazy.slice(2..4),
+        Err(DeserializeError::OutOfBounds)
+    ));
+    assert!(matches!(
+        lazy.slice((Bound::Included(3), Bound::Excluded(2))),
+        Err(DeserializeError::OutOfBounds)
+    ));
+    assert!(matches!(
+        lazy.slice((Bound::Excluded(usize::MAX), Bound::Unbounded)),
+        Err(DeserializeError::OutOfBounds)
+    ));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_indexed() {
+    use alloc::{string::String, vec::Vec};
+
+    use crate::{deserialize::DeserializeError, indexed::Indexed};
+
+    let mut buffer = [0u8; 1024];
+
+    let names = ["alice", "", "bob", "carol"];
+    let (size, root) = serialize::<Indexed<As<str>>, _>(&names, &mut buffer).unwrap();
+    let lazy =
+        deserialize_with_size::<Indexed<As<str>>, Lazy<Indexed<As<str>>>>(&buffer[..size], root)
+            .unwrap();
+
+    assert_eq!(lazy.len().unwrap(), 4);
+    for (idx, name) in names.iter().enumerate().rev() {
+        assert_eq!(lazy.get_elem::<&str>(idx).unwrap().unwrap(), *name);
+    }
+    assert!(lazy.get_elem::<&str>(4).is_none());
+
+    let all =
+        deserialize_with_size::<Indexed<As<str>>, Vec<String>>(&buffer[..size], root).unwrap();
+    assert_eq!(all, names);
+
+    // Sized elements are bounded by the table too.
+    let (size, root) = serialize::<Indexed<u16>, _>(&[1u16, 2, 3], &mut buffer).unwrap();
+    let lazy =
+        deserialize_with_size::<Indexed<u16>, Lazy<Indexed<u16>>>(&buffer[..size], root).unwrap();
+    assert_eq!(lazy.get_elem::<u16>(1).unwrap().unwrap(), 2);
+
+    let (size, root) = serialize::<Indexed<u16>, _>(&[0u16; 0], &mut buffer).unwrap();
+    let lazy =
+        deserialize_with_size::<Indexed<u16>, Lazy<Indexed<u16>>>(&buffer[..size], root).unwrap();
+    assert!(lazy.is_empty().unwrap());
+
+    // Offsets out of element area are rejected.
+    let (size, root) = serialize::<Indexed<As<str>>, _>(&names, &mut buffer).unwrap();
+    let table = size - crate::size::SIZE_STACK * 2;
+    buffer[table..table + crate::size::SIZE_STACK].fill(0xff);
+    let lazy =
+        deserialize_with_size::<Indexed<As<str>>, Lazy<Indexed<As<str>>>>(&buffer[..size], root)
+            .unwrap();
+    assert!(matches!(
+        lazy.get_elem::<&str>(0).unwrap(),
+        Err(DeserializeError::WrongLength)
+    ));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_iter_double_ended() {
+    use alloc::vec::Vec;
+
+    use crate::{lazy::Lazy, r#as::As, reference::Ref};
+
+    let mut buffer = [0u8; 1024];
+
+    let (size, root) = serialize::<[u32], _>(0..10u32, &mut buffer).unwrap();
+    let lazy = deserialize_with_size::<[u32], Lazy<[u32]>>(&buffer[..size], root).unwrap();
+
+    let mut iter = lazy.iter::<u32>().into_sized();
+    assert_eq!(iter.len(), 10);
+    assert_eq!(iter.next().unwrap().unwrap(), 0);
+    assert_eq!(iter.next_back().unwrap().unwrap(), 9);
+    assert_eq!(iter.nth_back(2).unwrap().unwrap(), 6);
+    assert_eq!(iter.len(), 5);
+    assert_eq!(
+        iter.rev().collect::<Result<Vec<_>, _>>().unwrap(),
+        [5, 4, 3, 2, 1]
+    );
+
+    let reversed = lazy
+        .iter::<u32>()
+        .into_sized()
+        .skip(3)
+        .rev()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(reversed, [9, 8, 7, 6, 5, 4, 3]);
+
+    let names = ["alpha", "beta", "gamma", "delta"];
+    let (size, root) = serialize::<[Ref<str>], _>(names, &mut buffer).unwrap();
+    let lazy =
+        deserialize_with_size::<[Ref<str>], Lazy<[Ref<str>]>>(&buffer[..size], root).unwrap();
+    let mut iter = lazy.iter::<&str>().into_sized();
+    assert_eq!(iter.len(), 4);
+    assert_eq!(iter.nth_back(1).unwrap().unwrap(), "gamma");
+    assert_eq!(iter.next().unwrap().unwrap(), "alpha");
+    assert_eq!(iter.next_back().unwrap().unwrap(), "beta");
+    assert!(iter.next_back().is_none());
+    assert!(iter.next().is_none());
+
+    // Skipping unsized elements reads only their lengths.
+    let (size, root) = serialize::<[As<str>], _>(names, &mut buffer).unwrap();
+    let lazy = deserialize_with_size::<[As<str>], Lazy<[As<str>]>>(&buffer[..size], root).unwrap();
+    let rest = lazy
+        .iter::<&str>()
+        .skip(2)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(rest, ["gamma", "delta"]);
+
+    // Zero-sized elements.
+    let (size, root) = serialize::<[()], _>([(), (), ()], &mut buffer).unwrap();
+    let lazy = deserialize_with_size::<[()], Lazy<[()]>>(&buffer[..size], root).unwrap();
+    let mut iter = lazy.iter::<()>().into_sized();
+    assert_eq!(iter.len(), 3);
+    assert!(iter.next_back().unwrap().is_ok());
+    assert_eq!(iter.rev().count(), 2);
+}
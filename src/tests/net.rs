@@ -127,18 +127,16 @@ fn test_net_packet() {
     for message in packet.game_messages.iter::<GameMessageRead>() {
         match message.unwrap() {
             GameMessageRead::Client(ClientMessageRead::ClientData { nickname, clan }) => {
-                drop(nickname);
-                drop(clan);
+                let _ = (nickname, clan);
             }
             GameMessageRead::Client(ClientMessageRead::Chat(message)) => {
-                drop(message);
+                let _ = message;
             }
             GameMessageRead::Server(ServerMessageRead::ServerData(data)) => {
-                drop(data);
+                let _ = data;
             }
             GameMessageRead::Server(ServerMessageRead::ClientChat { client_id, message }) => {
-                drop(client_id);
-                drop(message);
+                let _ = (client_id, message);
             }
         }
     }
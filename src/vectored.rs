@@ -0,0 +1,343 @@
+use alloc::vec::Vec;
+use core::convert::Infallible;
+use std::io::IoSlice;
+
+use ::bytes::Bytes;
+
+use crate::{
+    buffer::Buffer,
+    packet::{write_packet_into, PacketFormula},
+    serialize::Serialize,
+};
+
+/// Default minimal size of shared bytes that are not copied.
+const DEFAULT_MIN_SHARED: usize = 1024;
+
+/// Packet serialized as a sequence of segments.
+///
+/// Large [`bytes::Bytes`](::bytes::Bytes) values serialized with
+/// [`Bytes`](crate::Bytes) formula are not copied into the packet.
+/// Instead they are kept as separate segments,
+/// so that the packet can be sent with [`std::io::Write::write_vectored`].
+///
+/// Concatenated segments are identical to the packet
+/// produced by [`write_packet_to_vec`](crate::write_packet_to_vec).
+pub struct VectoredPacket {
+    /// Heap bytes written into the packet.
+    heap: Vec<u8>,
+
+    /// Shared bytes inserted into the heap.
+    /// Each pair is offset in `heap` bytes and the shared bytes placed there.
+    heap_shared: Vec<(usize, Bytes)>,
+
+    /// Size of the heap including shared bytes.
+    heap_len: usize,
+
+    /// Stack bytes are placed at the end of this vector.
+    stack: Vec<u8>,
+
+    /// Shared bytes on the stack.
+    /// Each pair is stack size before the bytes were written and the bytes.
+    stack_shared: Vec<(usize, Bytes)>,
+
+    /// Shared bytes smaller than this are copied.
+    min_shared: usize,
+}
+
+impl Default for VectoredPacket {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VectoredPacket {
+    /// Creates new empty packet.
+    #[must_use]
+    #[inline]
+    pub fn new() -> Self {
+        VectoredPacket {
+            heap: Vec::new(),
+            heap_shared: Vec::new(),
+            heap_len: 0,
+            stack: Vec::new(),
+            stack_shared: Vec::new(),
+            min_shared: DEFAULT_MIN_SHARED,
+        }
+    }
+
+    /// Sets minimal size of shared bytes that are kept as separate segment.
+    /// Smaller shared bytes are copied into the packet.
+    #[must_use]
+    #[inline]
+    pub fn with_min_shared(mut self, min_shared: usize) -> Self {
+        self.min_shared = min_shared.max(1);
+        self
+    }
+
+    /// Returns size of the packet in bytes.
+    #[must_use]
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.heap_len
+    }
+
+    /// Returns `true` if packet is empty.
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.heap_len == 0
+    }
+
+    /// Removes all segments of the packet.
+    /// Keeps allocated memory.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.heap.clear();
+        self.heap_shared.clear();
+        self.heap_len = 0;
+        self.stack_shared.clear();
+    }
+
+    /// Returns segments of the packet in order.
+    #[must_use]
+    pub fn io_slices(&self) -> Vec<IoSlice<'_>> {
+        let mut slices = Vec::with_capacity(self.heap_shared.len() * 2 + 1);
+        let mut start = 0;
+        for (at, bytes) in &self.heap_shared {
+            if *at > start {
+                slices.push(IoSlice::new(&self.heap[start..*at]));
+            }
+            slices.push(IoSlice::new(bytes));
+            start = *at;
+        }
+        if self.heap.len() > start {
+            slices.push(IoSlice::new(&self.heap[start..]));
+        }
+        slices
+    }
+
+    /// Copies the packet into contiguous vector.
+    #[must_use]
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut output = Vec::with_capacity(self.heap_len);
+        for slice in self.io_slices() {
+            output.extend_from_slice(&slice);
+        }
+        output
+    }
+
+    /// Returns total size of shared bytes that are
+    /// placed before `stack` on the stack.
+    fn shared_below(&self, stack: usize) -> usize {
+        self.stack_shared
+            .iter()
+            .take_while(|(at, _)| *at < stack)
+            .map(|(at, bytes)| {
+                debug_assert!(at + bytes.len() <= stack, "Shared bytes are overwritten");
+                bytes.len()
+            })
+            .sum()
+    }
+
+    #[cold]
+    fn do_reserve_stack(&mut self, stack: usize, additional: usize) {
+        let old_len = self.stack.len();
+        let new_len = (stack + additional).max(old_len * 2);
+        self.stack.resize(new_len, 0);
+        self.stack
+            .copy_within(old_len - stack..old_len, new_len - stack);
+    }
+
+    /// Returns stack bytes at the stack size `stack`.
+    /// Ensures that `len` bytes can be written there.
+    #[inline(always)]
+    fn stack_bytes(&mut self, stack: usize, len: usize) -> &mut [u8] {
+        let stack = stack - self.shared_below(stack);
+        if self.stack.len() - stack < len {
+            self.do_reserve_stack(stack, len);
+        }
+        let at = self.stack.len() - stack - len;
+        &mut self.stack[at..][..len]
+    }
+
+    /// Copies shared bytes into the heap, so that it is contiguous.
+    #[cold]
+    fn inline_heap_shared(&mut self) {
+        if self.heap_shared.is_empty() {
+            return;
+        }
+
+        let mut heap = Vec::with_capacity(self.heap_len);
+        let mut start = 0;
+        for (at, bytes) in self.heap_shared.drain(..) {
+            heap.extend_from_slice(&self.heap[start..at]);
+            heap.extend_from_slice(&bytes);
+            start = at;
+        }
+        heap.extend_from_slice(&self.heap[start..]);
+        self.heap = heap;
+    }
+
+    /// Appends stack bytes from physical stack size `stack` to the heap.
+    #[inline(always)]
+    fn move_inline(&mut self, stack: usize, len: usize) {
+        let start = self.stack.len() - stack;
+        self.heap.extend_from_slice(&self.stack[start..][..len]);
+        self.heap_len += len;
+    }
+}
+
+/// Buffer that writes into [`VectoredPacket`].
+///
+/// Heap is append-only, except for already reserved bytes.
+pub(crate) struct VectoredBuffer<'a> {
+    packet: &'a mut VectoredPacket,
+}
+
+impl<'a> Buffer for VectoredBuffer<'a> {
+    type Error = Infallible;
+    type Reborrow<'b> = VectoredBuffer<'b> where 'a: 'b;
+
+    #[inline(always)]
+    fn reborrow(&mut self) -> Self::Reborrow<'_> {
+        VectoredBuffer {
+            packet: self.packet,
+        }
+    }
+
+    #[inline(always)]
+    fn write_stack(&mut self, heap: usize, stack: usize, bytes: &[u8]) -> Result<(), Infallible> {
+        debug_assert_eq!(heap, self.packet.heap_len);
+        self.packet
+            .stack_bytes(stack, bytes.len())
+            .copy_from_slice(bytes);
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn pad_stack(&mut self, heap: usize, stack: usize, len: usize) -> Result<(), Infallible> {
+        debug_assert_eq!(heap, self.packet.heap_len);
+        self.packet.stack_bytes(stack, len).fill(0);
+        Ok(())
+    }
+
+    fn move_to_heap(&mut self, heap: usize, stack: usize, len: usize) {
+        debug_assert!(stack >= len);
+        debug_assert_eq!(heap, self.packet.heap_len);
+
+        // Shared bytes in the moved part of the stack.
+        let bottom = stack - len;
+        let mut shared = core::mem::take(&mut self.packet.stack_shared);
+        let first = shared.partition_point(|(at, _)| *at < bottom);
+
+        let mut top = stack;
+        let mut physical = stack - shared.iter().map(|(_, bytes)| bytes.len()).sum::<usize>();
+
+        // Stack is moved from the top.
+        for (at, bytes) in shared.drain(first..).rev() {
+            debug_assert!(at + bytes.len() <= top);
+            let inline = top - at - bytes.len();
+            self.packet.move_inline(physical, inline);
+            physical -= inline;
+
+            self.packet.heap_len += bytes.len();
+            self.packet
+                .heap_shared
+                .push((self.packet.heap.len(), bytes));
+            top = at;
+        }
+        self.packet.stack_shared = shared;
+        self.packet.move_inline(physical, top - bottom);
+    }
+
+    /// Heap with shared bytes is not contiguous,
+    /// so shared bytes written so far are copied into the heap.
+    #[inline]
+    fn reserve_heap(
+        &mut self,
+        heap: usize,
+        _stack: usize,
+        len: usize,
+    ) -> Result<&mut [u8], Infallible> {
+        self.packet.inline_heap_shared();
+        if self.packet.heap.len() < heap + len {
+            self.packet.heap_len += heap + len - self.packet.heap.len();
+            self.packet.heap.resize(heap + len, 0);
+        }
+        Ok(&mut self.packet.heap[..heap + len])
+    }
+
+    #[inline(always)]
+    fn reserve_heap_at(
+        &mut self,
+        heap: usize,
+        _stack: usize,
+        len: usize,
+    ) -> Result<&mut [u8], Infallible> {
+        let mut at = heap;
+        let mut shared_len = 0;
+        for (offset, bytes) in &self.packet.heap_shared {
+            if offset + shared_len >= heap {
+                assert!(
+                    offset + shared_len >= heap + len,
+                    "Shared bytes are overwritten"
+                );
+                break;
+            }
+            debug_assert!(offset + shared_len + bytes.len() <= heap);
+            shared_len += bytes.len();
+            at -= bytes.len();
+        }
+
+        if self.packet.heap.len() < at + len {
+            self.packet.heap_len += at + len - self.packet.heap.len();
+            self.packet.heap.resize(at + len, 0);
+        }
+        Ok(&mut self.packet.heap[at..][..len])
+    }
+
+    #[inline(always)]
+    fn prefer_reserve(&self, len: usize) -> bool {
+        // Large shared bytes may be inside the value.
+        self.packet.heap_shared.is_empty() && len < self.packet.min_shared
+    }
+
+    #[inline(always)]
+    fn write_shared(&mut self, heap: usize, stack: usize, bytes: &Bytes) -> Result<(), Infallible> {
+        if bytes.len() < self.packet.min_shared {
+            return self.write_stack(heap, stack, bytes);
+        }
+
+        debug_assert_eq!(heap, self.packet.heap_len);
+
+        // Drop shared bytes left from previous writes at this stack size.
+        let first = self
+            .packet
+            .stack_shared
+            .partition_point(|(at, _)| *at < stack);
+        self.packet.stack_shared.truncate(first);
+        self.packet.stack_shared.push((stack, bytes.clone()));
+        Ok(())
+    }
+}
+
+/// Writes packet with the value into [`VectoredPacket`].
+/// Returns the number of bytes in the packet.
+///
+/// Large [`bytes::Bytes`](::bytes::Bytes) values serialized with
+/// [`Bytes`](crate::Bytes) formula are referenced by the packet
+/// instead of being copied.
+/// Previous content of the packet is cleared.
+#[inline]
+pub fn write_packet_vectored<F, T>(value: T, packet: &mut VectoredPacket) -> usize
+where
+    F: PacketFormula + ?Sized,
+    T: Serialize<F::Formula>,
+{
+    packet.clear();
+    match write_packet_into::<F, T, _>(value, VectoredBuffer { packet }) {
+        Ok(size) => size,
+        Err(never) => match never {},
+    }
+}
@@ -2,7 +2,9 @@ use crate::{
     buffer::Buffer,
     deserialize::{Deserialize, DeserializeError, Deserializer},
     formula::Formula,
+    reference::Ref,
     serialize::{write_bytes, Serialize, Sizes},
+    width::Width,
 };
 
 /// Formula for Variable-Length Quantity encoding.
@@ -62,7 +64,7 @@ impl Formula for Vlq {
     const HEAPLESS: bool = true;
 }
 
-trait VlqType: Copy {
+pub(crate) trait VlqType: Copy {
     fn less_eq(&self, byte: u8) -> bool;
 
     /// Shifts the value right by 8 bits, and assigns the result to `self`.
@@ -162,22 +164,84 @@ where
     T: VlqType,
 {
     #[inline(always)]
-    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
-        deserialize(de)
+    fn deserialize(mut de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        deserialize(&mut de)
     }
 
     #[inline(always)]
     fn deserialize_in_place(
         &mut self,
-        deserializer: Deserializer<'de>,
+        mut deserializer: Deserializer<'de>,
     ) -> Result<(), DeserializeError> {
-        *self = deserialize(deserializer)?;
+        *self = deserialize(&mut deserializer)?;
         Ok(())
     }
 }
 
+/// Sizes and addresses in references are encoded with [`Vlq`].
+///
+/// References with this width have no fixed stack size.
+/// Such references are most compact as the last field of a formula,
+/// where no length prefix is required.
+impl Width for Vlq {
+    const SIZE: Option<usize> = None;
+
+    #[inline(always)]
+    fn write<B>(value: usize, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        serialize(value, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn read(de: &mut Deserializer<'_>) -> Result<usize, DeserializeError> {
+        deserialize(de)
+    }
+}
+
+/// `String` formula with size and address encoded with [`Vlq`].
+///
+/// Accepts the same types as `String` formula,
+/// but a short string takes two bytes of the stack instead of
+/// two [`FixedUsize`](crate::FixedUsize) values.
+/// As non-last field of a formula it is prefixed
+/// with [`FixedUsize`](crate::FixedUsize) stack size, like any formula
+/// without fixed stack size.
+///
+/// # Examples
+///
+/// ```
+/// # use alkahest::*;
+///
+/// let mut buffer = [0u8; 64];
+/// let (size, root) = serialize::<VlqString, _>("hello", &mut buffer).unwrap();
+/// assert_eq!(size, 7);
+/// let value = deserialize_with_size::<VlqString, &str>(&buffer[..size], root).unwrap();
+/// assert_eq!(value, "hello");
+/// ```
+pub type VlqString = Ref<str, Vlq>;
+
+/// `Vec<F>` and slice reference formula with length and address
+/// encoded with [`Vlq`].
+///
+/// Accepts the same types as `Vec<F>` formula.
+/// See [`VlqString`] for the layout notes.
+///
+/// # Examples
+///
+/// ```
+/// # use alkahest::*;
+///
+/// let mut buffer = [0u8; 64];
+/// let (size, root) = serialize::<VlqVec<VlqString>, _>(["a", "b"], &mut buffer).unwrap();
+/// let value = deserialize_with_size::<VlqVec<VlqString>, Vec<String>>(&buffer[..size], root).unwrap();
+/// assert_eq!(value, ["a", "b"]);
+/// ```
+pub type VlqVec<F> = Ref<[F], Vlq>;
+
 #[inline(always)]
-fn size_hint<T>(mut value: T) -> Sizes
+pub(crate) fn size_hint<T>(mut value: T) -> Sizes
 where
     T: VlqType,
 {
@@ -203,7 +267,7 @@ where
 }
 
 #[inline(always)]
-fn serialize<T, B>(mut value: T, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+pub(crate) fn serialize<T, B>(mut value: T, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
 where
     T: VlqType,
     B: Buffer,
@@ -236,7 +300,7 @@ where
 }
 
 #[inline(always)]
-fn deserialize<T>(mut de: Deserializer) -> Result<T, DeserializeError>
+pub(crate) fn deserialize<T>(de: &mut Deserializer) -> Result<T, DeserializeError>
 where
     T: VlqType,
 {
@@ -245,11 +309,8 @@ where
     let (tail, msb) = match header {
         0x00..=0x7F => (header >> 4, header & 0x0F),
         0x80..=0xBF => (header & 0x3F, 0),
-        0xC0..=0xFF => {
-            unimplemented!(
-                "Decoding for values that require more than 63 bytes is not implemented yet."
-            )
-        }
+        // Values that require more than 63 bytes do not fit any integer type.
+        0xC0..=0xFF => return Err(DeserializeError::IntegerOverflow),
     };
 
     let mut value = T::from_lsb(msb);
@@ -0,0 +1,134 @@
+//!
+//! This module provides encodings of sizes and addresses in references.
+//!
+
+use core::mem::size_of;
+
+use crate::{
+    buffer::Buffer,
+    deserialize::{DeserializeError, Deserializer},
+    serialize::Sizes,
+    size::{FixedUsize, FixedUsizeType},
+};
+
+/// Encoding of sizes and addresses stored in references.
+///
+/// Used as parameter of [`Ref`](crate::Ref) formula
+/// to choose reference width per formula instead of global `fixed*` features.
+///
+/// Implemented for [`FixedUsize`], which follows the global feature,
+/// and for `u8`, `u16`, `u32` and `u64`.
+///
+/// Width is applied to:
+///
+/// * [`Ref`](crate::Ref) references.
+///   `Vec<F>` and `String` formulas have the same layout as `Ref<[F]>`
+///   and `Ref<str>`, use `Ref<[F], W>` and `Ref<str, W>` to store them
+///   with another width. [`VlqVec`](crate::VlqVec) and [`VlqString`](crate::VlqString)
+///   are such formulas with [`Vlq`](crate::Vlq) width.
+/// * Sizes of unsized fields of derived formulas
+///   with `#[alkahest(Formula, width = W)]` attribute.
+/// * Packet header with [`Packet`](crate::Packet) formula.
+///
+/// Field sizes and packet header require width with fixed size,
+/// so [`Vlq`](crate::Vlq) is rejected at compile time there.
+/// Custom formulas write references with
+/// [`write_reference_with`](crate::advanced::write_reference_with)
+/// and fields with [`write_field_with`](crate::advanced::write_field_with).
+/// Other formulas, like tuples, arrays and slices,
+/// store sizes and addresses with [`FixedUsize`].
+///
+/// Addresses are offsets from the start of the whole serialized buffer.
+/// If an address does not fit the width, [`Buffer::width_overflow`]
+/// is called and serialization fails, so narrow widths are only suitable
+/// for small messages.
+/// Functions that return an error, like [`serialize`](crate::serialize),
+/// report it as error, infallible ones panic.
+pub trait Width {
+    /// Size of encoded value in bytes.
+    /// `None` if encoded size depends on the value.
+    const SIZE: Option<usize>;
+
+    /// Writes the value to the stack.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if buffer write fails
+    /// or value cannot be encoded, see [`Buffer::width_overflow`].
+    fn write<B>(value: usize, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer;
+
+    /// Reads the value from the stack.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if the value cannot be decoded.
+    fn read(de: &mut Deserializer<'_>) -> Result<usize, DeserializeError>;
+}
+
+impl Width for FixedUsize {
+    const SIZE: Option<usize> = Some(size_of::<FixedUsizeType>());
+
+    #[inline(always)]
+    fn write<B>(value: usize, sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        let bytes = FixedUsize::truncate_unchecked(value).to_le_bytes();
+        buffer.write_stack(sizes.heap, sizes.stack, &bytes)?;
+        sizes.stack += bytes.len();
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn read(de: &mut Deserializer<'_>) -> Result<usize, DeserializeError> {
+        let mut bytes = [0; size_of::<FixedUsizeType>()];
+        bytes.copy_from_slice(de.read_bytes(size_of::<FixedUsizeType>())?);
+        match FixedUsize::from_le_bytes(bytes) {
+            Ok(value) => Ok(value.into()),
+            Err(_) => Err(DeserializeError::InvalidUsize(
+                FixedUsizeType::from_le_bytes(bytes),
+            )),
+        }
+    }
+}
+
+macro_rules! impl_width {
+    ($($ty:ident)*) => {
+        $(
+            impl Width for $ty {
+                const SIZE: Option<usize> = Some(size_of::<$ty>());
+
+                #[inline(always)]
+                fn write<B>(value: usize, sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+                where
+                    B: Buffer,
+                {
+                    let bytes = match $ty::try_from(value) {
+                        Ok(value) => value.to_le_bytes(),
+                        Err(_) => {
+                            buffer.width_overflow()?;
+                            [0; size_of::<$ty>()]
+                        }
+                    };
+                    buffer.write_stack(sizes.heap, sizes.stack, &bytes)?;
+                    sizes.stack += bytes.len();
+                    Ok(())
+                }
+
+                #[inline(always)]
+                fn read(de: &mut Deserializer<'_>) -> Result<usize, DeserializeError> {
+                    let mut bytes = [0; size_of::<$ty>()];
+                    bytes.copy_from_slice(de.read_bytes(size_of::<$ty>())?);
+                    match usize::try_from($ty::from_le_bytes(bytes)) {
+                        Ok(value) => Ok(value),
+                        Err(_) => Err(DeserializeError::WrongAddress),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_width!(u8 u16 u32 u64);
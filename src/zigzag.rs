@@ -0,0 +1,340 @@
+use core::marker::PhantomData;
+
+use crate::{
+    buffer::Buffer,
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::Formula,
+    serialize::{Serialize, Sizes},
+    vlq::{self, VlqType},
+};
+
+/// Formula for signed integers encoded with ZigZag and [`Vlq`](crate::Vlq).
+///
+/// Signed value is mapped to unsigned so that small negative values
+/// become small unsigned values: `0, -1, 1, -2, 2, ...` map to `0, 1, 2, 3, 4, ...`.
+/// The result is encoded with `Vlq`.
+///
+/// # Examples
+///
+/// ```
+/// # use alkahest::*;
+///
+/// let mut buffer = [0u8; 1024];
+///
+/// let (size, root) = serialize::<ZigZag, i64>(-1, &mut buffer).unwrap();
+/// assert_eq!(size, 1);
+/// let value = deserialize_with_size::<ZigZag, i8>(&buffer[..size], root).unwrap();
+/// assert_eq!(-1, value);
+/// ```
+///
+/// If deserialize type can't fit the value, an error is returned.
+///
+/// ```
+/// # use alkahest::*;
+///
+/// let mut buffer = [0u8; 1024];
+///
+/// let (size, root) = serialize::<ZigZag, i32>(-70000, &mut buffer).unwrap();
+/// let err = deserialize_with_size::<ZigZag, i16>(&buffer[..size], root).unwrap_err();
+/// assert!(matches!(err, DeserializeError::IntegerOverflow));
+/// ```
+pub struct ZigZag;
+
+impl Formula for ZigZag {
+    const MAX_STACK_SIZE: Option<usize> = None;
+    const EXACT_SIZE: bool = false;
+    const HEAPLESS: bool = true;
+}
+
+/// Integer types that can be ZigZag encoded.
+pub(crate) trait ZigZagType: Copy {
+    type Unsigned: VlqType;
+
+    fn zigzag(self) -> Self::Unsigned;
+
+    fn unzigzag(value: Self::Unsigned) -> Self;
+}
+
+/// Integer types that can be delta encoded.
+pub(crate) trait DeltaType: Copy {
+    type Signed: ZigZagType;
+
+    fn delta(self, prev: Self) -> Self::Signed;
+
+    fn undelta(delta: Self::Signed, prev: Self) -> Self;
+
+    fn zero() -> Self;
+}
+
+macro_rules! impl_zigzag {
+    ($($signed:ident $unsigned:ident),*) => {
+        $(
+            impl ZigZagType for $signed {
+                type Unsigned = $unsigned;
+
+                #[inline(always)]
+                fn zigzag(self) -> $unsigned {
+                    ((self << 1) ^ (self >> ($signed::BITS - 1))) as $unsigned
+                }
+
+                #[inline(always)]
+                fn unzigzag(value: $unsigned) -> $signed {
+                    ((value >> 1) as $signed) ^ -((value & 1) as $signed)
+                }
+            }
+
+            impl DeltaType for $signed {
+                type Signed = $signed;
+
+                #[inline(always)]
+                fn delta(self, prev: $signed) -> $signed {
+                    self.wrapping_sub(prev)
+                }
+
+                #[inline(always)]
+                fn undelta(delta: $signed, prev: $signed) -> $signed {
+                    prev.wrapping_add(delta)
+                }
+
+                #[inline(always)]
+                fn zero() -> $signed {
+                    0
+                }
+            }
+
+            impl DeltaType for $unsigned {
+                type Signed = $signed;
+
+                #[inline(always)]
+                fn delta(self, prev: $unsigned) -> $signed {
+                    self.wrapping_sub(prev) as $signed
+                }
+
+                #[inline(always)]
+                fn undelta(delta: $signed, prev: $unsigned) -> $unsigned {
+                    prev.wrapping_add(delta as $unsigned)
+                }
+
+                #[inline(always)]
+                fn zero() -> $unsigned {
+                    0
+                }
+            }
+        )*
+    };
+}
+
+impl_zigzag!(i8 u8, i16 u16, i32 u32, i64 u64, i128 u128, isize usize);
+
+impl<T> Serialize<ZigZag> for T
+where
+    T: ZigZagType,
+{
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(vlq::size_hint(self.zigzag()))
+    }
+
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        vlq::serialize(self.zigzag(), sizes, buffer)
+    }
+}
+
+impl<'de, T> Deserialize<'de, ZigZag> for T
+where
+    T: ZigZagType,
+{
+    #[inline(always)]
+    fn deserialize(mut de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        Ok(T::unzigzag(vlq::deserialize(&mut de)?))
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, mut de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        *self = T::unzigzag(vlq::deserialize(&mut de)?);
+        Ok(())
+    }
+}
+
+/// Formula for sequences of integers that stores differences
+/// between consecutive elements as [`ZigZag`] values.
+///
+/// Use as `Delta<[i32]>` or `Delta<[u64]>`.
+/// Slowly changing sequences, like positions or timestamps,
+/// are encoded in a byte or two per element.
+///
+/// Differences wrap around on overflow, so any sequence can be encoded.
+///
+/// # Examples
+///
+/// ```
+/// # use alkahest::*;
+///
+/// let mut buffer = [0u8; 1024];
+///
+/// let (size, root) = serialize::<Delta<[u32]>, _>(&[1000u32, 1001, 1003, 1002][..], &mut buffer).unwrap();
+/// assert_eq!(size, 5);
+/// let value = deserialize_with_size::<Delta<[u32]>, Vec<u32>>(&buffer[..size], root).unwrap();
+/// assert_eq!(value, [1000, 1001, 1003, 1002]);
+/// ```
+pub struct Delta<F: ?Sized> {
+    marker: PhantomData<fn(&F) -> &F>,
+}
+
+impl<F> Formula for Delta<[F]>
+where
+    F: DeltaType,
+{
+    const MAX_STACK_SIZE: Option<usize> = None;
+    const EXACT_SIZE: bool = false;
+    const HEAPLESS: bool = true;
+}
+
+#[inline(always)]
+fn delta_size_hint<T>(values: &[T]) -> Sizes
+where
+    T: DeltaType,
+{
+    let mut prev = T::zero();
+    let mut sizes = Sizes::ZERO;
+    for &value in values {
+        sizes += vlq::size_hint(value.delta(prev).zigzag());
+        prev = value;
+    }
+    sizes
+}
+
+#[inline(always)]
+fn serialize_delta<T, B>(values: &[T], sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+where
+    T: DeltaType,
+    B: Buffer,
+{
+    let mut prev = T::zero();
+    for &value in values {
+        vlq::serialize(value.delta(prev).zigzag(), sizes, buffer.reborrow())?;
+        prev = value;
+    }
+    Ok(())
+}
+
+impl<T> Serialize<Delta<[T]>> for &[T]
+where
+    T: DeltaType,
+{
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(delta_size_hint(self))
+    }
+
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        serialize_delta(self, sizes, buffer)
+    }
+}
+
+impl<T, const N: usize> Serialize<Delta<[T]>> for [T; N]
+where
+    T: DeltaType,
+{
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(delta_size_hint(self))
+    }
+
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        serialize_delta(&self, sizes, buffer)
+    }
+}
+
+impl<T, const N: usize> Serialize<Delta<[T]>> for &[T; N]
+where
+    T: DeltaType,
+{
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(delta_size_hint(*self))
+    }
+
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        serialize_delta(self, sizes, buffer)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> Serialize<Delta<[T]>> for alloc::vec::Vec<T>
+where
+    T: DeltaType,
+{
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(delta_size_hint(self))
+    }
+
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        serialize_delta(&self, sizes, buffer)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> Serialize<Delta<[T]>> for &alloc::vec::Vec<T>
+where
+    T: DeltaType,
+{
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(delta_size_hint(self))
+    }
+
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        serialize_delta(self, sizes, buffer)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'de, T> Deserialize<'de, Delta<[T]>> for alloc::vec::Vec<T>
+where
+    T: DeltaType,
+{
+    #[inline]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        let mut values = alloc::vec::Vec::new();
+        <Self as Deserialize<'de, Delta<[T]>>>::deserialize_in_place(&mut values, de)?;
+        Ok(values)
+    }
+
+    #[inline]
+    fn deserialize_in_place(&mut self, mut de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        self.clear();
+        let mut prev = T::zero();
+        while de.stack() > 0 {
+            let delta = T::Signed::unzigzag(vlq::deserialize(&mut de)?);
+            prev = T::undelta(delta, prev);
+            self.push(prev);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,20 @@
+// Errors reported by the derive macros themselves.
+#[cfg(feature = "derive")]
+#[test]
+fn derive_errors() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
+
+// Mismatches with formula defined elsewhere are reported from constant
+// evaluation and by the compiler, so these snapshots include its diagnostics,
+// which wording changes between releases.
+// They are generated with toolchain pinned in `.github/workflows/ui.yml`,
+// other CI jobs skip this test.
+// Update the pin when regenerating snapshots with `TRYBUILD=overwrite`.
+#[cfg(feature = "derive")]
+#[test]
+fn derive_formula_mismatch() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/rustc/*.rs");
+}
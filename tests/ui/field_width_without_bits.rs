@@ -0,0 +1,9 @@
+use alkahest::alkahest;
+
+#[alkahest(Formula, Serialize, Deserialize)]
+struct Input {
+    #[alkahest(bits = 3)]
+    buttons: u8,
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+use alkahest::{alkahest, Formula};
+
+#[derive(Formula)]
+struct ClientData {
+    name: u32,
+    level: u32,
+}
+
+#[alkahest(Serialize<ClientData>)]
+struct Client {
+    level: u32,
+    name: u32,
+}
+
+fn main() {}
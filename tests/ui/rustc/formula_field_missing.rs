@@ -0,0 +1,14 @@
+use alkahest::{alkahest, Formula};
+
+#[derive(Formula)]
+struct ClientData {
+    name: u32,
+    level: u32,
+}
+
+#[alkahest(Deserialize<'_, ClientData>)]
+struct Client {
+    name: u32,
+}
+
+fn main() {}
@@ -0,0 +1,16 @@
+use alkahest::{alkahest, Formula};
+
+#[derive(Formula)]
+struct ClientData {
+    name: u32,
+    level: u32,
+}
+
+#[alkahest(Serialize<ClientData>)]
+struct Client {
+    name: u32,
+    level: u32,
+    clan: u32,
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+use alkahest::{alkahest, Formula};
+
+#[derive(Formula)]
+enum ClientMessage {
+    ClientData { name: u32 },
+    Chat(u32),
+}
+
+#[alkahest(Deserialize<'_, ClientMessage>)]
+enum Message {
+    ClientData { name: u32 },
+    Foo(u32),
+}
+
+fn main() {}
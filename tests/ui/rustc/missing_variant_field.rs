@@ -0,0 +1,15 @@
+use alkahest::{alkahest, Formula};
+
+#[derive(Formula)]
+enum ClientMessage {
+    ClientData { name: u32, level: u32 },
+    Chat(u32),
+}
+
+#[alkahest(Serialize<ClientMessage>)]
+enum Message {
+    ClientData { name: u32, level: u32, clan: u32 },
+    Chat(u32),
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use alkahest::{alkahest, Formula};
+
+#[derive(Formula)]
+struct ClientData {
+    name: u32,
+}
+
+#[alkahest(Deserialize<'a, ClientData>)]
+struct Client {
+    name: u32,
+}
+
+fn main() {}
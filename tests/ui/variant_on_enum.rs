@@ -0,0 +1,13 @@
+use alkahest::{alkahest, Formula};
+
+#[derive(Formula)]
+enum ClientMessage {
+    Chat(u32),
+}
+
+#[alkahest(Serialize<ClientMessage @ Chat>)]
+enum Message {
+    Chat(u32),
+}
+
+fn main() {}
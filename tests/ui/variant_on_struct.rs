@@ -0,0 +1,8 @@
+use alkahest::alkahest;
+
+#[alkahest(Formula, Serialize<Client @ Player>)]
+struct Client {
+    name: u32,
+}
+
+fn main() {}
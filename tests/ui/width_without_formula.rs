@@ -0,0 +1,8 @@
+use alkahest::alkahest;
+
+#[alkahest(Serialize, width = u16)]
+struct Input {
+    name: u32,
+}
+
+fn main() {}